@@ -0,0 +1,373 @@
+//! Connecting to brltty through BrlAPI, and the task which owns the connection.
+
+use std::{env, path::PathBuf, time::Duration};
+
+use async_channel::{Receiver, Sender};
+use async_io::Timer;
+use async_net::unix::UnixStream;
+use futures_lite::{AsyncReadExt, AsyncWriteExt, FutureExt};
+use futures_util::{future::BoxFuture, FutureExt as FatExt};
+use odilia_common::{
+	errors::{BrailleError, OdiliaError},
+	events::ScreenReaderEvent,
+};
+use smol_cancellation_token::CancellationToken;
+
+use crate::{
+	display::{fit, BrailleDisplay},
+	keys::{BrailleKey, KeyBindings},
+	protocol::{decode_header, AuthMethod, Packet, PacketType, HEADER_SIZE, PROTOCOL_VERSION},
+};
+
+/// Where brltty may put its sockets; which one depends on how it was built.
+const SOCKET_DIRS: [&str; 2] = ["/var/lib/BrlAPI", "/run/brltty/BrlAPI"];
+/// The key shared with brltty, when it asks for one.
+const DEFAULT_KEY_FILE: &str = "/etc/brlapi.key";
+/// How long brltty has to accept Odilia and hand it the display, before giving up on it.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A connection to brltty, ready to write to the display.
+#[derive(Debug)]
+pub struct BrlApiConnection {
+	pub(crate) stream: UnixStream,
+	columns: usize,
+}
+
+impl BrlApiConnection {
+	/// The number of cells on the display.
+	#[must_use]
+	pub fn columns(&self) -> usize {
+		self.columns
+	}
+}
+
+pub(crate) async fn read_packet(stream: &mut UnixStream) -> Result<Packet, OdiliaError> {
+	let mut header = [0; HEADER_SIZE];
+	stream.read_exact(&mut header).await?;
+	let (size, kind) = decode_header(header)?;
+	let mut payload = vec![0; size];
+	stream.read_exact(&mut payload).await?;
+	Ok(Packet::new(kind, payload))
+}
+
+pub(crate) async fn write_packet(
+	stream: &mut UnixStream,
+	packet: &Packet,
+) -> Result<(), OdiliaError> {
+	stream.write_all(&packet.encode()?).await?;
+	Ok(())
+}
+
+/// Read the next packet, which should be of type `kind`.
+async fn expect(stream: &mut UnixStream, kind: PacketType) -> Result<Packet, OdiliaError> {
+	let packet = read_packet(stream).await?;
+	match packet.kind {
+		received if received == kind => Ok(packet),
+		PacketType::Error => {
+			let code = packet.u32s().first().copied().unwrap_or_default();
+			Err(BrailleError::Refused(code).into())
+		}
+		other => Err(BrailleError::UnexpectedPacket(other.code()).into()),
+	}
+}
+
+/// The socket brltty listens on; `BRLAPI_HOST` (like `:1`) picks another display than the first.
+fn socket_path() -> PathBuf {
+	let number = env::var("BRLAPI_HOST")
+		.ok()
+		.and_then(|host| host.rsplit(':').next().map(ToString::to_string))
+		.filter(|number| !number.is_empty())
+		.unwrap_or_else(|| "0".to_string());
+	SOCKET_DIRS
+		.iter()
+		.map(|dir| PathBuf::from(dir).join(&number))
+		.find(|path| path.exists())
+		.unwrap_or_else(|| PathBuf::from(SOCKET_DIRS[0]).join(number))
+}
+
+/// The key file to authenticate with; `BRLAPI_AUTH` may name another one, as `keyfile:PATH`.
+fn key_file() -> PathBuf {
+	env::var("BRLAPI_AUTH").ok().map_or_else(
+		|| PathBuf::from(DEFAULT_KEY_FILE),
+		|auth| PathBuf::from(auth.strip_prefix("keyfile:").unwrap_or(&auth)),
+	)
+}
+
+/// The virtual terminals leading to the user's session, as brltty expects them: X sets
+/// `WINDOWPATH`, and logind sets `XDG_VTNR`.
+/// With none, brltty gives Odilia the display wherever the user is.
+fn tty_path() -> Vec<u32> {
+	let path = env::var("WINDOWPATH")
+		.or_else(|_| env::var("XDG_VTNR"))
+		.unwrap_or_default();
+	path.split(':').filter_map(|tty| tty.trim().parse().ok()).collect()
+}
+
+async fn authenticate(stream: &mut UnixStream, methods: &[u32]) -> Result<(), OdiliaError> {
+	for method in methods.iter().filter_map(|code| AuthMethod::from_code(*code)) {
+		match method {
+			AuthMethod::None => return Ok(()),
+			AuthMethod::Credentials => {
+				write_packet(stream, &Packet::auth(method, &[])).await?;
+				expect(stream, PacketType::Ack).await?;
+				return Ok(());
+			}
+			AuthMethod::Key => {
+				let path = key_file();
+				let key = match async_fs::read(&path).await {
+					Ok(key) => key,
+					Err(e) => {
+						tracing::debug!(error = %e, ?path, "Could not read the BrlAPI key");
+						continue;
+					}
+				};
+				write_packet(stream, &Packet::auth(method, &key)).await?;
+				expect(stream, PacketType::Ack).await?;
+				return Ok(());
+			}
+		}
+	}
+	Err(BrailleError::Authentication.into())
+}
+
+/// Agree on a version, authenticate, find out how big the display is, and take control of it.
+async fn handshake(mut stream: UnixStream, ttys: &[u32]) -> Result<BrlApiConnection, OdiliaError> {
+	let version = expect(&mut stream, PacketType::Version).await?;
+	let version = version.u32s().first().copied().unwrap_or_default();
+	if version != PROTOCOL_VERSION {
+		return Err(BrailleError::UnsupportedVersion(version).into());
+	}
+	write_packet(&mut stream, &Packet::from_u32s(PacketType::Version, &[PROTOCOL_VERSION]))
+		.await?;
+	let methods = expect(&mut stream, PacketType::Auth).await?.u32s();
+	authenticate(&mut stream, &methods).await?;
+	write_packet(&mut stream, &Packet::new(PacketType::GetDisplaySize, Vec::new())).await?;
+	let size = expect(&mut stream, PacketType::GetDisplaySize).await?.u32s();
+	let [columns, rows] = size[..] else {
+		return Err(BrailleError::Malformed.into());
+	};
+	let columns = usize::try_from(columns.saturating_mul(rows.max(1)))?;
+	write_packet(&mut stream, &Packet::enter_tty_mode(ttys)?).await?;
+	expect(&mut stream, PacketType::Ack).await?;
+	Ok(BrlApiConnection { stream, columns })
+}
+
+/// Connect to brltty, and take control of the braille display.
+///
+/// # Errors
+///
+/// If brltty is not running, if it refuses the connection, or if it does not answer within
+/// [`CONNECT_TIMEOUT`].
+#[tracing::instrument(level = "debug", err)]
+pub async fn create_brlapi_connection() -> Result<BrlApiConnection, OdiliaError> {
+	let connecting = async {
+		let stream = UnixStream::connect(socket_path()).await?;
+		handshake(stream, &tty_path()).await
+	};
+	let timeout = async {
+		Timer::after(CONNECT_TIMEOUT).await;
+		Err(BrailleError::TimedOut.into())
+	};
+	let connection = connecting.or(timeout).await?;
+	tracing::debug!(columns = connection.columns, "Connected to brltty");
+	Ok(connection)
+}
+
+/// A [`BrailleDisplay`] driven by brltty.
+///
+/// Packets are sent over a channel to the task running [`handle_brlapi_commands`], which owns
+/// the actual connection.
+#[derive(Debug, Clone)]
+pub struct BrlApiDisplay {
+	requests: Sender<Packet>,
+	columns: usize,
+}
+
+impl BrlApiDisplay {
+	#[must_use]
+	pub fn new(requests: Sender<Packet>, connection: &BrlApiConnection) -> Self {
+		BrlApiDisplay { requests, columns: connection.columns }
+	}
+}
+
+impl BrailleDisplay for BrlApiDisplay {
+	fn columns(&self) -> usize {
+		self.columns
+	}
+	fn write(
+		&self,
+		text: String,
+		cursor: Option<usize>,
+	) -> BoxFuture<'_, Result<(), OdiliaError>> {
+		Box::pin(async move {
+			let packet = Packet::write_text(
+				&fit(&text, self.columns),
+				cursor,
+				self.columns,
+			)?;
+			self.requests
+				.send(packet)
+				.await
+				.map_err(|_| BrailleError::Closed.into())
+		})
+	}
+}
+
+/// The key code of a `Key` packet: a big-endian `u64`, sent as two numbers.
+fn key_code(packet: &Packet) -> Option<u64> {
+	match packet.u32s()[..] {
+		[high, low] => Some((u64::from(high) << 32) | u64::from(low)),
+		_ => None,
+	}
+}
+
+/// Read everything brltty sends on its own, until the connection breaks.
+/// Keys pressed on the display are sent on as the events they are bound to.
+async fn receive(
+	mut stream: UnixStream,
+	events: Sender<ScreenReaderEvent>,
+	bindings: KeyBindings,
+) -> Result<(), OdiliaError> {
+	loop {
+		let packet = read_packet(&mut stream).await?;
+		match packet.kind {
+			PacketType::Key => {
+				let key = key_code(&packet).and_then(BrailleKey::from_code);
+				let Some(event) = key.and_then(|key| bindings.event(key)) else {
+					tracing::trace!(?key, "Braille key is not bound");
+					continue;
+				};
+				if events.send(event).await.is_err() {
+					tracing::debug!("Input events are no longer handled");
+				}
+			}
+			// writes are not acknowledged; if one fails, brltty says so this way
+			PacketType::Error | PacketType::Exception => {
+				let code = packet.u32s().first().copied().unwrap_or_default();
+				tracing::warn!(code, "brltty refused a request");
+			}
+			kind => tracing::trace!(?kind, "Ignoring BrlAPI packet"),
+		}
+	}
+}
+
+/// A handler task for packets to send to brltty, and keys pressed on the display; these are sent
+/// to `events`, the same channel input servers send theirs on.
+/// This function will run until it gets cancelled via the cancellation token, at which point the
+/// display is handed back to brltty.
+///
+/// # Errors
+///
+/// If the connection to brltty breaks.
+#[tracing::instrument(level = "debug", skip_all, err)]
+pub async fn handle_brlapi_commands(
+	connection: BrlApiConnection,
+	requests: Receiver<Packet>,
+	events: Sender<ScreenReaderEvent>,
+	bindings: KeyBindings,
+	shutdown: CancellationToken,
+) -> Result<(), OdiliaError> {
+	let mut stream = connection.stream;
+	let receiving = receive(stream.clone(), events, bindings);
+	let sending = async {
+		loop {
+			let next = requests.recv().map(Ok).or(shutdown.cancelled().map(Err)).await;
+			let Ok(Ok(packet)) = next else {
+				break;
+			};
+			write_packet(&mut stream, &packet).await?;
+		}
+		tracing::debug!("Handing the braille display back to brltty");
+		write_packet(&mut stream, &Packet::new(PacketType::LeaveTtyMode, Vec::new())).await
+	};
+	sending.or(receiving).await
+}
+
+#[cfg(test)]
+mod tests {
+	use async_channel::unbounded;
+	use async_net::unix::UnixStream;
+	use futures_lite::future::{block_on, or, zip};
+	use odilia_common::{
+		errors::{BrailleError, OdiliaError},
+		events::RouteBraille,
+	};
+
+	use super::{expect, handshake, read_packet, receive, write_packet};
+	use crate::{
+		keys::KeyBindings,
+		protocol::{AuthMethod, Packet, PacketType, PROTOCOL_VERSION},
+	};
+
+	/// Play brltty's side of the handshake, for a 40 cell display.
+	async fn serve(mut server: UnixStream) -> Vec<u32> {
+		let send = |kind, values: &[u32]| Packet::from_u32s(kind, values);
+		write_packet(&mut server, &send(PacketType::Version, &[PROTOCOL_VERSION]))
+			.await
+			.expect("Sent version");
+		expect(&mut server, PacketType::Version)
+			.await
+			.expect("Client version");
+		write_packet(&mut server, &send(PacketType::Auth, &[AuthMethod::None.code()]))
+			.await
+			.expect("Sent authentication methods");
+		expect(&mut server, PacketType::GetDisplaySize)
+			.await
+			.expect("Size asked for");
+		write_packet(&mut server, &send(PacketType::GetDisplaySize, &[40, 1]))
+			.await
+			.expect("Sent size");
+		let ttys = expect(&mut server, PacketType::EnterTtyMode)
+			.await
+			.expect("Entered tty");
+		write_packet(&mut server, &send(PacketType::Ack, &[]))
+			.await
+			.expect("Sent ack");
+		ttys.u32s()
+	}
+
+	#[test]
+	fn handshake_with_mock_server() {
+		let (client, server) = UnixStream::pair().expect("Socket pair");
+		let (connection, ttys) = block_on(zip(handshake(client, &[7]), serve(server)));
+		assert_eq!(connection.expect("Handshake succeeds").columns(), 40);
+		assert_eq!(ttys[..2], [1, 7]);
+	}
+
+	#[test]
+	fn unsupported_version() {
+		let (client, mut server) = UnixStream::pair().expect("Socket pair");
+		let connection = block_on(async {
+			write_packet(&mut server, &Packet::from_u32s(PacketType::Version, &[7]))
+				.await
+				.expect("Sent version");
+			handshake(client, &[]).await
+		});
+		assert!(matches!(
+			connection,
+			Err(OdiliaError::Braille(BrailleError::UnsupportedVersion(7)))
+		));
+		// the client hung up without answering
+		assert!(block_on(read_packet(&mut server)).is_err());
+	}
+
+	#[test]
+	fn keys_become_events() {
+		let (client, mut server) = UnixStream::pair().expect("Socket pair");
+		let (events, received) = unbounded();
+		let event = block_on(async {
+			// a key which is not bound, then the routing key above the third cell
+			for code in [0x2000_0001, 0x2001_0002] {
+				let key = Packet::from_u32s(PacketType::Key, &[0, code]);
+				write_packet(&mut server, &key).await.expect("Sent key");
+			}
+			let receiving = async {
+				let _ = receive(client, events, KeyBindings::default()).await;
+				None
+			};
+			or(async { received.recv().await.ok() }, receiving).await
+		});
+		assert_eq!(event, Some(RouteBraille(2).into()));
+		assert!(received.try_recv().is_err());
+	}
+}
@@ -0,0 +1,36 @@
+use std::fmt::Debug;
+
+use futures_util::future::BoxFuture;
+use odilia_common::errors::OdiliaError;
+
+use crate::region::Line;
+
+/// Something which can show braille.
+///
+/// Like Odilia's speech backends, this is a trait so that handlers do not need to know whether
+/// they are writing to a real display through brltty, or to memory in a test.
+///
+/// Methods return boxed futures so that the trait can be used as `Arc<dyn BrailleDisplay>`.
+pub trait BrailleDisplay: Debug + Send + Sync {
+	/// The number of cells on the display.
+	fn columns(&self) -> usize;
+	/// Show `text` from the first cell of the display onwards, with the cursor on cell `cursor`
+	/// (counted from 0) if it is set.
+	/// Text longer than the display is cut off, and shorter text is padded with blank cells.
+	fn write(
+		&self,
+		text: String,
+		cursor: Option<usize>,
+	) -> BoxFuture<'_, Result<(), OdiliaError>>;
+
+	/// Show the part of `line` which has the cursor in it; see [`Line::window`].
+	fn show(&self, line: &Line) -> BoxFuture<'_, Result<(), OdiliaError>> {
+		let window = line.window(self.columns());
+		self.write(window.text, window.cursor)
+	}
+}
+
+/// `text`, cut off or padded with spaces to be exactly `columns` characters long.
+pub(crate) fn fit(text: &str, columns: usize) -> String {
+	text.chars().chain(std::iter::repeat(' ')).take(columns).collect()
+}
@@ -0,0 +1,207 @@
+//! Keys pressed on the braille display, and the events they are bound to.
+//!
+//! Odilia does not tell brltty which driver it expects, so keys arrive as brltty commands: the
+//! display's own key table has already turned thumb keys into commands like `FWINLT` (pan left),
+//! and routing keys into `ROUTE` with the cell they are above.
+
+use std::{collections::HashMap, hash::BuildHasher};
+
+use odilia_common::{
+	errors::BrailleError,
+	events::{Direction, PanBraille, RouteBraille, ScreenReaderEvent},
+};
+
+/// The bits of a key code saying what kind of key it is; commands are the only kind handled.
+const KEY_TYPE_MASK: u64 = 0xE000_0000;
+const KEY_TYPE_COMMAND: u64 = 0x2000_0000;
+/// A command's block (which command it is, for commands which take an argument).
+const COMMAND_BLOCK_MASK: u64 = 0x1FFF_0000;
+const COMMAND_BLOCK_SHIFT: u32 = 16;
+/// A command's argument, or the command itself in block 0.
+const COMMAND_ARGUMENT_MASK: u64 = 0xFFFF;
+/// The block of routing keys; the argument is the cell.
+const BLOCK_ROUTE: u64 = 1;
+
+/// The commands keys can be bound to, by the names brltty's key tables use.
+pub const COMMANDS: [(&str, u32); 17] = [
+	("LNUP", 1),
+	("LNDN", 2),
+	("WINUP", 3),
+	("WINDN", 4),
+	("TOP", 9),
+	("BOT", 10),
+	("CHRLT", 19),
+	("CHRRT", 20),
+	("HWINLT", 21),
+	("HWINRT", 22),
+	("FWINLT", 23),
+	("FWINRT", 24),
+	("LNBEG", 27),
+	("LNEND", 28),
+	("HOME", 29),
+	("BACK", 30),
+	("RETURN", 31),
+];
+
+/// A key pressed on the display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BrailleKey {
+	/// The routing key above a cell, counted from 0.
+	Route(usize),
+	/// Any other command, by its number in block 0.
+	Command(u32),
+}
+
+impl BrailleKey {
+	/// The key a BrlAPI key code is for, if it is a command Odilia knows about.
+	#[must_use]
+	pub fn from_code(code: u64) -> Option<Self> {
+		if code & KEY_TYPE_MASK != KEY_TYPE_COMMAND {
+			return None;
+		}
+		let argument = code & COMMAND_ARGUMENT_MASK;
+		match (code & COMMAND_BLOCK_MASK) >> COMMAND_BLOCK_SHIFT {
+			0 => u32::try_from(argument).ok().map(BrailleKey::Command),
+			BLOCK_ROUTE => usize::try_from(argument).ok().map(BrailleKey::Route),
+			_ => None,
+		}
+	}
+}
+
+/// The number of the command called `name`, like `FWINLT`.
+#[must_use]
+pub fn command_code(name: &str) -> Option<u32> {
+	COMMANDS.iter()
+		.find(|(command, _)| command.eq_ignore_ascii_case(name))
+		.map(|(_, code)| *code)
+}
+
+/// Which event each key on the display sends.
+/// Routing keys always route; every other key is looked up by its command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyBindings {
+	inner: Vec<(u32, ScreenReaderEvent)>,
+}
+
+impl Default for KeyBindings {
+	fn default() -> Self {
+		let mut bindings = KeyBindings::new();
+		for (name, event) in [
+			("FWINLT", PanBraille(Direction::Backward).into()),
+			("FWINRT", PanBraille(Direction::Forward).into()),
+		] {
+			let _ = bindings.insert(name, event);
+		}
+		bindings
+	}
+}
+
+impl KeyBindings {
+	/// Create a new, empty set of bindings; only routing keys do anything.
+	#[must_use]
+	pub fn new() -> Self {
+		KeyBindings { inner: Vec::new() }
+	}
+	/// The default bindings, with `overrides` (command names, like `LNUP`, to events) added
+	/// on top.
+	///
+	/// # Errors
+	///
+	/// If any of the commands is not one of [`COMMANDS`].
+	pub fn with_overrides<S: BuildHasher>(
+		overrides: &HashMap<String, ScreenReaderEvent, S>,
+	) -> Result<Self, BrailleError> {
+		let mut bindings = KeyBindings::default();
+		for (name, event) in overrides {
+			bindings.insert(name, event.clone())?;
+		}
+		Ok(bindings)
+	}
+	/// Bind the command called `name` to `event`, returning what it was bound to before.
+	///
+	/// # Errors
+	///
+	/// If `name` is not one of [`COMMANDS`].
+	pub fn insert(
+		&mut self,
+		name: &str,
+		event: ScreenReaderEvent,
+	) -> Result<Option<ScreenReaderEvent>, BrailleError> {
+		let code = command_code(name)
+			.ok_or_else(|| BrailleError::UnknownCommand(name.to_string()))?;
+		if let Some(existing) = self.inner.iter_mut().find(|(bound, _)| *bound == code) {
+			return Ok(Some(std::mem::replace(&mut existing.1, event)));
+		}
+		self.inner.push((code, event));
+		Ok(None)
+	}
+	/// The event to send for `key`, if it is bound to one.
+	#[must_use]
+	pub fn event(&self, key: BrailleKey) -> Option<ScreenReaderEvent> {
+		match key {
+			BrailleKey::Route(cell) => Some(RouteBraille(cell).into()),
+			BrailleKey::Command(code) => self
+				.inner
+				.iter()
+				.find(|(bound, _)| *bound == code)
+				.map(|(_, event)| event.clone()),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::HashMap;
+
+	use odilia_common::{
+		errors::BrailleError,
+		events::{Direction, PanBraille, RouteBraille, SayAll, ScreenReaderEvent},
+	};
+
+	use super::{BrailleKey, KeyBindings};
+
+	#[test]
+	fn key_codes() {
+		assert_eq!(BrailleKey::from_code(0x2000_0017), Some(BrailleKey::Command(23)));
+		assert_eq!(BrailleKey::from_code(0x2001_0005), Some(BrailleKey::Route(5)));
+		// flags in the upper half are ignored
+		assert_eq!(
+			BrailleKey::from_code(0x0000_0001_2000_0001),
+			Some(BrailleKey::Command(1))
+		);
+		// keyboard symbols, and blocks of commands not handled
+		assert_eq!(BrailleKey::from_code(0x0000_0061), None);
+		assert_eq!(BrailleKey::from_code(0x2022_0001), None);
+	}
+
+	#[test]
+	fn default_bindings() {
+		let bindings = KeyBindings::default();
+		assert_eq!(
+			bindings.event(BrailleKey::Command(23)),
+			Some(PanBraille(Direction::Backward).into())
+		);
+		assert_eq!(bindings.event(BrailleKey::Route(3)), Some(RouteBraille(3).into()));
+		assert_eq!(bindings.event(BrailleKey::Command(1)), None);
+	}
+
+	#[test]
+	fn overrides() {
+		let overrides =
+			HashMap::from([("lnup".to_string(), ScreenReaderEvent::from(SayAll))]);
+		let bindings = KeyBindings::with_overrides(&overrides).expect("Known command");
+		assert_eq!(bindings.event(BrailleKey::Command(1)), Some(SayAll.into()));
+		// the defaults are kept
+		assert!(bindings.event(BrailleKey::Command(24)).is_some());
+		let mut bindings = KeyBindings::new();
+		assert!(matches!(
+			bindings.insert("NOSUCHKEY", SayAll.into()),
+			Err(BrailleError::UnknownCommand(_))
+		));
+		assert_eq!(bindings.insert("FWINRT", SayAll.into()).ok(), Some(None));
+		assert_eq!(
+			bindings.insert("FWINRT", PanBraille(Direction::Forward).into()).ok(),
+			Some(Some(SayAll.into()))
+		);
+	}
+}
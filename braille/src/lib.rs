@@ -0,0 +1,39 @@
+#![deny(
+	clippy::all,
+	clippy::pedantic,
+	clippy::cargo,
+	clippy::map_unwrap_or,
+	clippy::unwrap_used,
+	unsafe_code
+)]
+#![allow(clippy::multiple_crate_versions)]
+
+//! Braille output for Odilia, through brltty's BrlAPI.
+
+mod brlapi;
+mod display;
+pub mod keys;
+#[cfg(feature = "liblouis")]
+mod louis;
+mod memory;
+pub mod protocol;
+pub mod region;
+mod supervisor;
+pub mod translation;
+mod viewport;
+
+pub use brlapi::{
+	create_brlapi_connection, handle_brlapi_commands, BrlApiConnection, BrlApiDisplay,
+};
+pub use display::BrailleDisplay;
+pub use keys::{BrailleKey, KeyBindings};
+#[cfg(feature = "liblouis")]
+pub use louis::Louis;
+pub use memory::MemoryDisplay;
+pub use region::{Line, Region, RegionKind, Window};
+pub use supervisor::supervise_brlapi_commands;
+pub use translation::{
+	default_translator, translate_line, translate_regions, ComputerBraille, Translation,
+	Translator,
+};
+pub use viewport::{Routed, Viewport};
@@ -0,0 +1,160 @@
+//! Translation with liblouis, the braille translator most screen readers use.
+//!
+//! liblouis is a C library; this is the small part of its interface Odilia needs. Its tables are
+//! looked up in `LOUIS_TABLEPATH`, or where liblouis was installed.
+
+#![allow(unsafe_code)]
+
+use std::{
+	ffi::{c_char, c_int, c_void, CString},
+	ptr,
+	sync::Mutex,
+};
+
+use odilia_common::errors::{BrailleError, OdiliaError};
+
+use crate::translation::{Translation, Translator, BRAILLE_BLANK};
+
+/// Output cells as dot patterns rather than as characters of the table's display table.
+const DOTS_IO: c_int = 4;
+/// With `DOTS_IO`, output dot patterns as Unicode braille.
+const UC_BRL: c_int = 64;
+/// Contractions can make the output longer than the input (capital signs, number signs), but
+/// never by this much.
+const OUTPUT_FACTOR: usize = 4;
+/// liblouis keeps its compiled tables in global state, which is not safe to share between
+/// threads.
+static LIBLOUIS: Mutex<()> = Mutex::new(());
+
+#[link(name = "louis")]
+extern "C" {
+	fn lou_translate(
+		table_list: *const c_char,
+		inbuf: *const c_void,
+		inlen: *mut c_int,
+		outbuf: *mut c_void,
+		outlen: *mut c_int,
+		typeform: *mut u16,
+		spacing: *mut c_char,
+		output_pos: *mut c_int,
+		input_pos: *mut c_int,
+		cursor_pos: *mut c_int,
+		mode: c_int,
+	) -> c_int;
+	fn lou_charSize() -> c_int;
+}
+
+/// A [`Translator`] using liblouis.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Louis;
+
+/// `text` as liblouis' wide characters: 16 or 32 bits, depending on how it was built.
+/// With 16 bits, characters outside the basic plane have to be replaced, to keep one unit per
+/// character.
+fn widen(text: &[char], size: c_int) -> Vec<u8> {
+	if size == 4 {
+		text.iter().flat_map(|c| u32::from(*c).to_ne_bytes()).collect()
+	} else {
+		text.iter()
+			.flat_map(|c| u16::try_from(u32::from(*c)).unwrap_or(0xFFFD).to_ne_bytes())
+			.collect()
+	}
+}
+
+/// The cells in `output`, as Unicode braille; only the dots are kept.
+fn cells(output: &[u8], size: c_int) -> String {
+	let units: Vec<u32> = if size == 4 {
+		output.chunks_exact(4)
+			.map(|unit| u32::from_ne_bytes([unit[0], unit[1], unit[2], unit[3]]))
+			.collect()
+	} else {
+		output.chunks_exact(2)
+			.map(|unit| u32::from(u16::from_ne_bytes([unit[0], unit[1]])))
+			.collect()
+	};
+	units.into_iter()
+		.filter_map(|unit| char::from_u32(BRAILLE_BLANK | (unit & 0xFF)))
+		.collect()
+}
+
+fn count(value: c_int) -> usize {
+	usize::try_from(value).unwrap_or_default()
+}
+
+impl Translator for Louis {
+	fn translate(&self, table: &str, text: &str) -> Result<Translation, OdiliaError> {
+		let chars = text.chars().collect::<Vec<char>>();
+		if chars.is_empty() {
+			return Ok(Translation::default());
+		}
+		let refused = || BrailleError::Table(table.to_string());
+		let table_list = CString::new(table).map_err(|_| refused())?;
+		let capacity = chars.len() * OUTPUT_FACTOR + 16;
+		let mut in_len = c_int::try_from(chars.len()).map_err(|_| refused())?;
+		let mut out_len = c_int::try_from(capacity).map_err(|_| refused())?;
+		let mut output_pos = vec![0; chars.len()];
+		let mut input_pos = vec![0; capacity];
+		let _guard = LIBLOUIS.lock()?;
+		// SAFETY: lou_charSize has no arguments, and only reports how liblouis was built.
+		let size = unsafe { lou_charSize() };
+		let input = widen(&chars, size);
+		let mut output = vec![0u8; capacity * count(size)];
+		// SAFETY: the table name is NUL terminated; the input holds `in_len` characters, the
+		// output and input positions have room for `out_len`, and the output positions for
+		// `in_len`. Typeforms, spacing and the cursor are optional, and passed as null.
+		let translated = unsafe {
+			lou_translate(
+				table_list.as_ptr(),
+				input.as_ptr().cast(),
+				ptr::addr_of_mut!(in_len),
+				output.as_mut_ptr().cast(),
+				ptr::addr_of_mut!(out_len),
+				ptr::null_mut(),
+				ptr::null_mut(),
+				output_pos.as_mut_ptr(),
+				input_pos.as_mut_ptr(),
+				ptr::null_mut(),
+				DOTS_IO | UC_BRL,
+			)
+		};
+		if translated == 0 {
+			return Err(refused().into());
+		}
+		let out_len = count(out_len);
+		output.truncate(out_len * count(size));
+		Ok(Translation {
+			braille: cells(&output, size),
+			to_braille: output_pos.into_iter().map(count).collect(),
+			to_print: input_pos.into_iter().take(out_len).map(count).collect(),
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::Louis;
+	use crate::translation::Translator;
+
+	#[test]
+	fn uncontracted() {
+		let translation =
+			Louis.translate("en-us-g1.ctb", "hello").expect("Table is installed");
+		assert_eq!(translation.braille, "⠓⠑⠇⠇⠕");
+		assert_eq!(translation.to_braille, [0, 1, 2, 3, 4]);
+	}
+
+	#[test]
+	fn contracted() {
+		let translation = Louis
+			.translate("en-us-g2.ctb", "the cat")
+			.expect("Table is installed");
+		assert_eq!(translation.braille, "⠮⠀⠉⠁⠞");
+		assert_eq!(translation.to_braille, [0, 0, 0, 1, 2, 3, 4]);
+		assert_eq!(translation.to_print, [0, 3, 4, 5, 6]);
+	}
+
+	#[test]
+	fn missing_table() {
+		assert!(Louis.translate("no-such-table.ctb", "hello").is_err());
+	}
+}
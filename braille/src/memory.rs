@@ -0,0 +1,82 @@
+use std::sync::{Arc, Mutex, PoisonError};
+
+use futures_util::future::{ready, BoxFuture};
+use odilia_common::errors::OdiliaError;
+
+use crate::display::{fit, BrailleDisplay};
+
+/// A [`BrailleDisplay`] which keeps what would be shown in memory, for tests, or for when there is
+/// no display to connect to.
+///
+/// Clones share the same cells, so a clone may be handed to the screen reader while the original is
+/// kept around to check what is on the display.
+#[derive(Debug, Clone)]
+pub struct MemoryDisplay {
+	columns: usize,
+	contents: Arc<Mutex<(String, Option<usize>)>>,
+}
+
+impl MemoryDisplay {
+	/// A blank display with `columns` cells.
+	#[must_use]
+	pub fn new(columns: usize) -> Self {
+		MemoryDisplay { columns, contents: Arc::new(Mutex::new((fit("", columns), None))) }
+	}
+	/// Everything on the display, including blank cells at the end.
+	#[must_use]
+	pub fn text(&self) -> String {
+		self.contents.lock().unwrap_or_else(PoisonError::into_inner).0.clone()
+	}
+	/// The cell the cursor is on, if it is shown.
+	#[must_use]
+	pub fn cursor(&self) -> Option<usize> {
+		self.contents.lock().unwrap_or_else(PoisonError::into_inner).1
+	}
+}
+
+impl BrailleDisplay for MemoryDisplay {
+	fn columns(&self) -> usize {
+		self.columns
+	}
+	fn write(
+		&self,
+		text: String,
+		cursor: Option<usize>,
+	) -> BoxFuture<'_, Result<(), OdiliaError>> {
+		let cursor = cursor.filter(|cursor| *cursor < self.columns);
+		let res = self
+			.contents
+			.lock()
+			.map(|mut contents| *contents = (fit(&text, self.columns), cursor))
+			.map_err(OdiliaError::from);
+		Box::pin(ready(res))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use futures_lite::future::block_on;
+
+	use super::MemoryDisplay;
+	use crate::{region::Line, BrailleDisplay};
+
+	#[test]
+	fn shows_the_window_with_the_cursor() {
+		let display = MemoryDisplay::new(8);
+		assert_eq!(display.text(), " ".repeat(8));
+		let line = Line::for_object(Some("Name"), Some("entry"), Some("Jo"), Some(2));
+		block_on(display.show(&line)).expect("Writing to memory never fails");
+		// "Name edt Jo" is 11 characters; the cursor, after "Jo", is in the second window
+		assert_eq!(display.text(), " Jo     ");
+		assert_eq!(display.cursor(), Some(3));
+	}
+
+	#[test]
+	fn long_text_is_cut_off() {
+		let display = MemoryDisplay::new(4);
+		block_on(display.write("abcdef".to_string(), Some(5)))
+			.expect("Writing to memory never fails");
+		assert_eq!(display.text(), "abcd");
+		assert_eq!(display.cursor(), None);
+	}
+}
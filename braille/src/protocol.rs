@@ -0,0 +1,278 @@
+//! The packets of brltty's BrlAPI protocol.
+//!
+//! Every packet is a header of two big-endian `u32`s, the size of the payload and the type of the
+//! packet, followed by the payload. Numbers in payloads are big-endian `u32`s as well.
+
+use odilia_common::errors::BrailleError;
+
+/// The version of the protocol this crate speaks.
+pub const PROTOCOL_VERSION: u32 = 8;
+/// brltty refuses anything bigger than this, and so does Odilia.
+pub const MAX_PACKET_SIZE: usize = 4096;
+/// The size of a packet's header.
+pub const HEADER_SIZE: usize = 8;
+
+/// Bits of the flags of a `Write` packet, saying which fields follow.
+const WRITE_REGION: u32 = 0x02;
+const WRITE_TEXT: u32 = 0x04;
+const WRITE_CURSOR: u32 = 0x20;
+const WRITE_CHARSET: u32 = 0x40;
+/// The character set text is written in.
+const CHARSET: &str = "UTF-8";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketType {
+	Version,
+	Auth,
+	GetDisplaySize,
+	EnterTtyMode,
+	LeaveTtyMode,
+	Key,
+	Write,
+	Ack,
+	Error,
+	Exception,
+	/// A packet this crate does not know about.
+	Other(u32),
+}
+
+impl PacketType {
+	#[must_use]
+	pub fn code(self) -> u32 {
+		match self {
+			PacketType::Version => u32::from(b'v'),
+			PacketType::Auth => u32::from(b'a'),
+			PacketType::GetDisplaySize => u32::from(b's'),
+			PacketType::EnterTtyMode => u32::from(b't'),
+			PacketType::LeaveTtyMode => u32::from(b'L'),
+			PacketType::Key => u32::from(b'k'),
+			PacketType::Write => u32::from(b'w'),
+			PacketType::Ack => u32::from(b'A'),
+			PacketType::Error => u32::from(b'e'),
+			PacketType::Exception => u32::from(b'E'),
+			PacketType::Other(code) => code,
+		}
+	}
+	#[must_use]
+	pub fn from_code(code: u32) -> Self {
+		[
+			PacketType::Version,
+			PacketType::Auth,
+			PacketType::GetDisplaySize,
+			PacketType::EnterTtyMode,
+			PacketType::LeaveTtyMode,
+			PacketType::Key,
+			PacketType::Write,
+			PacketType::Ack,
+			PacketType::Error,
+			PacketType::Exception,
+		]
+		.into_iter()
+		.find(|kind| kind.code() == code)
+		.unwrap_or(PacketType::Other(code))
+	}
+}
+
+/// The ways a client can prove it may use the display, in the order brltty offers them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthMethod {
+	/// Anyone may connect.
+	None,
+	/// The client sends the contents of a shared key file.
+	Key,
+	/// brltty checks the credentials of the process on the other end of the socket.
+	Credentials,
+}
+
+impl AuthMethod {
+	#[must_use]
+	pub fn from_code(code: u32) -> Option<Self> {
+		Some(match u8::try_from(code).ok()? {
+			b'N' => AuthMethod::None,
+			b'K' => AuthMethod::Key,
+			b'C' => AuthMethod::Credentials,
+			_ => return None,
+		})
+	}
+	#[must_use]
+	pub fn code(self) -> u32 {
+		u32::from(match self {
+			AuthMethod::None => b'N',
+			AuthMethod::Key => b'K',
+			AuthMethod::Credentials => b'C',
+		})
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Packet {
+	pub kind: PacketType,
+	pub payload: Vec<u8>,
+}
+
+fn push_u32(payload: &mut Vec<u8>, value: u32) {
+	payload.extend_from_slice(&value.to_be_bytes());
+}
+
+/// A number as sent in a packet; numbers which do not fit are malformed.
+fn to_u32(value: usize) -> Result<u32, BrailleError> {
+	u32::try_from(value).map_err(|_| BrailleError::Malformed)
+}
+
+impl Packet {
+	#[must_use]
+	pub fn new(kind: PacketType, payload: Vec<u8>) -> Self {
+		Packet { kind, payload }
+	}
+	/// A packet of the given type, with a payload made of `values`.
+	#[must_use]
+	pub fn from_u32s(kind: PacketType, values: &[u32]) -> Self {
+		let mut payload = Vec::with_capacity(values.len() * 4);
+		for value in values {
+			push_u32(&mut payload, *value);
+		}
+		Packet { kind, payload }
+	}
+	/// The payload, read as a list of numbers; any bytes left over are ignored.
+	#[must_use]
+	pub fn u32s(&self) -> Vec<u32> {
+		self.payload
+			.chunks_exact(4)
+			.map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+			.collect()
+	}
+	/// Authenticate with the given `method`; `key` is only sent for [`AuthMethod::Key`].
+	#[must_use]
+	pub fn auth(method: AuthMethod, key: &[u8]) -> Self {
+		let mut packet = Packet::from_u32s(PacketType::Auth, &[method.code()]);
+		if method == AuthMethod::Key {
+			packet.payload.extend_from_slice(key);
+		}
+		packet
+	}
+	/// Take control of the display while the user is on the given tty, given as the path of
+	/// virtual terminal numbers leading to it.
+	/// No driver name is sent, so keys are reported as brltty commands rather than raw codes.
+	///
+	/// # Errors
+	///
+	/// If there are more ttys than can be counted in a packet.
+	pub fn enter_tty_mode(ttys: &[u32]) -> Result<Self, BrailleError> {
+		let mut packet =
+			Packet::from_u32s(PacketType::EnterTtyMode, &[to_u32(ttys.len())?]);
+		for tty in ttys {
+			push_u32(&mut packet.payload, *tty);
+		}
+		packet.payload.push(0);
+		Ok(packet)
+	}
+	/// Fill the whole display with `text`, with the cursor on cell `cursor` (counted from 0).
+	/// `text` should be exactly `columns` characters long.
+	///
+	/// # Errors
+	///
+	/// If the text is too big to fit in a packet.
+	pub fn write_text(
+		text: &str,
+		cursor: Option<usize>,
+		columns: usize,
+	) -> Result<Self, BrailleError> {
+		let flags = WRITE_REGION | WRITE_TEXT | WRITE_CURSOR | WRITE_CHARSET;
+		// cells are counted from 1 here; 0 means there is no cursor
+		let cursor = cursor
+			.filter(|cursor| *cursor < columns)
+			.map_or(0, |cursor| cursor + 1);
+		let mut packet = Packet::from_u32s(
+			PacketType::Write,
+			&[flags, 1, to_u32(columns)?, to_u32(text.len())?],
+		);
+		packet.payload.extend_from_slice(text.as_bytes());
+		push_u32(&mut packet.payload, to_u32(cursor)?);
+		packet.payload
+			.push(u8::try_from(CHARSET.len()).map_err(|_| BrailleError::Malformed)?);
+		packet.payload.extend_from_slice(CHARSET.as_bytes());
+		if packet.payload.len() > MAX_PACKET_SIZE {
+			return Err(BrailleError::Malformed);
+		}
+		Ok(packet)
+	}
+	/// The packet, header and all, as sent over the socket.
+	///
+	/// # Errors
+	///
+	/// If the payload is bigger than [`MAX_PACKET_SIZE`].
+	pub fn encode(&self) -> Result<Vec<u8>, BrailleError> {
+		if self.payload.len() > MAX_PACKET_SIZE {
+			return Err(BrailleError::Malformed);
+		}
+		let mut bytes = Vec::with_capacity(HEADER_SIZE + self.payload.len());
+		push_u32(&mut bytes, to_u32(self.payload.len())?);
+		push_u32(&mut bytes, self.kind.code());
+		bytes.extend_from_slice(&self.payload);
+		Ok(bytes)
+	}
+}
+
+/// Read a packet's header: the size of its payload, and its type.
+///
+/// # Errors
+///
+/// If the payload is bigger than [`MAX_PACKET_SIZE`].
+pub fn decode_header(header: [u8; HEADER_SIZE]) -> Result<(usize, PacketType), BrailleError> {
+	let size = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+	let kind = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+	let size = usize::try_from(size).map_err(|_| BrailleError::Malformed)?;
+	if size > MAX_PACKET_SIZE {
+		return Err(BrailleError::Malformed);
+	}
+	Ok((size, PacketType::from_code(kind)))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{decode_header, AuthMethod, Packet, PacketType, HEADER_SIZE};
+
+	#[test]
+	fn header_round_trip() {
+		let packet = Packet::from_u32s(PacketType::Version, &[8]);
+		let bytes = packet.encode().expect("A small packet can be encoded");
+		assert_eq!(bytes, [0, 0, 0, 4, 0, 0, 0, b'v', 0, 0, 0, 8]);
+		let header: [u8; HEADER_SIZE] = bytes[..HEADER_SIZE].try_into().expect("8 bytes");
+		assert_eq!(decode_header(header).expect("Valid header"), (4, PacketType::Version));
+		assert_eq!(
+			PacketType::from_code(u32::from(b'?')),
+			PacketType::Other(u32::from(b'?'))
+		);
+	}
+
+	#[test]
+	fn key_authentication() {
+		let packet = Packet::auth(AuthMethod::Key, b"secret");
+		assert_eq!(&packet.payload[..4], &[0, 0, 0, b'K']);
+		assert_eq!(&packet.payload[4..], b"secret");
+		assert_eq!(Packet::auth(AuthMethod::Credentials, b"secret").payload.len(), 4);
+		assert_eq!(AuthMethod::from_code(u32::from(b'N')), Some(AuthMethod::None));
+		assert_eq!(AuthMethod::from_code(0x1_0000), None);
+	}
+
+	#[test]
+	fn write_text() {
+		let packet = Packet::write_text("hé ", Some(1), 3).expect("Fits in a packet");
+		let numbers = packet.u32s();
+		// flags, region start and size, then the text's length in bytes
+		assert_eq!(numbers[..4], [0x66, 1, 3, 4]);
+		assert_eq!(&packet.payload[16..20], "hé ".as_bytes());
+		assert_eq!(&packet.payload[20..24], &[0, 0, 0, 2]);
+		assert_eq!(&packet.payload[24..], b"\x05UTF-8");
+		// a cursor off the display is not shown
+		let packet = Packet::write_text("abc", Some(3), 3).expect("Fits in a packet");
+		assert_eq!(&packet.payload[19..23], &[0, 0, 0, 0]);
+	}
+
+	#[test]
+	fn enter_tty_mode() {
+		let packet = Packet::enter_tty_mode(&[1, 7]).expect("Fits in a packet");
+		assert_eq!(packet.u32s()[..3], [2, 1, 7]);
+		assert_eq!(packet.payload.last(), Some(&0));
+	}
+}
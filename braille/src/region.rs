@@ -0,0 +1,249 @@
+//! What is shown on the braille display: a line made of regions.
+//!
+//! A line for an object is its name, an abbreviation of its role, and the line of its text the
+//! cursor is on, separated by spaces. Only as much of the line as fits is shown at a time; the
+//! part with the cursor in it is chosen, so that the cursor is always visible.
+
+/// What a [`Region`] shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionKind {
+	Name,
+	Role,
+	Text,
+}
+
+/// One part of a [`Line`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+	pub kind: RegionKind,
+	pub text: String,
+	/// The character offset of the cursor within `text`, if it is in this region.
+	pub cursor: Option<usize>,
+}
+
+/// The part of a [`Line`] which fits on the display.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Window {
+	/// The character offset within the line of the first character shown.
+	pub start: usize,
+	pub text: String,
+	/// The cell the cursor is on, counted from the start of the display.
+	pub cursor: Option<usize>,
+}
+
+/// A line of braille, made of regions separated by spaces.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Line {
+	regions: Vec<Region>,
+	/// The character offset within the object's text of the start of the line shown.
+	text_start: usize,
+}
+
+impl Line {
+	/// The line for an object: its name, the abbreviation of the role named `role` (like
+	/// "push button"), and the line of `text` which `caret` (a character offset into all of
+	/// `text`) is on.
+	#[must_use]
+	pub fn for_object(
+		name: Option<&str>,
+		role: Option<&str>,
+		text: Option<&str>,
+		caret: Option<usize>,
+	) -> Self {
+		let mut line = Line::default();
+		if let Some(name) = name {
+			line.push(RegionKind::Name, name, None);
+		}
+		if let Some(role) = role {
+			line.push(RegionKind::Role, abbreviate(role), None);
+		}
+		if let Some(text) = text {
+			let caret_at = caret.unwrap_or_default().min(text.chars().count());
+			let (current, cursor) = line_at(text, caret_at);
+			line.text_start = caret_at - cursor;
+			line.push(RegionKind::Text, current, caret.map(|_| cursor));
+		}
+		line
+	}
+	/// Add a region to the end of the line.
+	/// Empty text is skipped, unless the cursor is in it, as in an empty text field.
+	pub fn push(&mut self, kind: RegionKind, text: impl Into<String>, cursor: Option<usize>) {
+		let text = text.into();
+		if !text.is_empty() || cursor.is_some() {
+			self.regions.push(Region { kind, text, cursor });
+		}
+	}
+	/// A line of exactly these regions, empty or not; used to keep a translated line's regions
+	/// in step with the print ones.
+	pub(crate) fn from_regions(regions: Vec<Region>, text_start: usize) -> Self {
+		Line { regions, text_start }
+	}
+	#[must_use]
+	pub fn regions(&self) -> &[Region] {
+		&self.regions
+	}
+	/// The character offset within the object's text of the start of the line shown, so that
+	/// offsets within the text region can be turned back into offsets within the whole text.
+	#[must_use]
+	pub fn text_start(&self) -> usize {
+		self.text_start
+	}
+	/// The whole line, with a space between each region.
+	#[must_use]
+	pub fn text(&self) -> String {
+		self.regions
+			.iter()
+			.map(|region| region.text.as_str())
+			.collect::<Vec<&str>>()
+			.join(" ")
+	}
+	/// The character offset of the cursor within [`Line::text`], if there is one.
+	#[must_use]
+	pub fn cursor(&self) -> Option<usize> {
+		let mut start = 0;
+		for region in &self.regions {
+			if let Some(cursor) = region.cursor {
+				return Some(start + cursor.min(region.text.chars().count()));
+			}
+			start += region.text.chars().count() + 1;
+		}
+		None
+	}
+	/// The part of the line to show on a display with `columns` cells: the one the cursor is in,
+	/// or the start of the line if there is no cursor.
+	#[must_use]
+	pub fn window(&self, columns: usize) -> Window {
+		let columns = columns.max(1);
+		let start = self.cursor().map_or(0, |cursor| cursor / columns * columns);
+		self.window_at(start, columns)
+	}
+	/// The `columns` cells of the line from character offset `start`; the cursor is only shown if
+	/// it is among them.
+	#[must_use]
+	pub fn window_at(&self, start: usize, columns: usize) -> Window {
+		let columns = columns.max(1);
+		Window {
+			start,
+			text: self.text().chars().skip(start).take(columns).collect(),
+			cursor: self
+				.cursor()
+				.and_then(|cursor| cursor.checked_sub(start))
+				.filter(|cursor| *cursor < columns),
+		}
+	}
+	/// The region character offset `offset` of [`Line::text`] is in, and the offset within it.
+	/// The space after a region counts as the end of it, so that the cursor can be put after its
+	/// last character.
+	#[must_use]
+	pub fn locate(&self, offset: usize) -> Option<(usize, usize)> {
+		let mut start = 0;
+		for (index, region) in self.regions.iter().enumerate() {
+			let length = region.text.chars().count();
+			if offset <= start + length {
+				return Some((index, offset - start));
+			}
+			start += length + 1;
+		}
+		None
+	}
+}
+
+/// The line of `text` that character offset `caret` is on, and the offset of `caret` within it.
+#[must_use]
+pub fn line_at(text: &str, caret: usize) -> (String, usize) {
+	let chars = text.chars().collect::<Vec<char>>();
+	let caret = caret.min(chars.len());
+	let start = chars[..caret]
+		.iter()
+		.rposition(|c| *c == '\n')
+		.map_or(0, |newline| newline + 1);
+	let line = chars[start..].iter().take_while(|c| **c != '\n').collect();
+	(line, caret - start)
+}
+
+/// The short form of a role's name, to save cells; roles without one are shown in full.
+#[must_use]
+pub fn abbreviate(role: &str) -> &str {
+	match role {
+		"push button" | "button" => "btn",
+		"check box" => "chk",
+		"radio button" => "rdo",
+		"toggle button" => "tgl",
+		"combo box" => "cbo",
+		"entry" | "password text" | "text" => "edt",
+		"spin button" => "spn",
+		"slider" => "sld",
+		"link" => "lnk",
+		"heading" => "h",
+		"list" => "lst",
+		"list item" => "li",
+		"menu" => "mnu",
+		"menu item" | "check menu item" | "radio menu item" => "mnuitm",
+		"menu bar" => "mnubar",
+		"page tab" => "tab",
+		"page tab list" => "tablst",
+		"tree" => "tre",
+		"tree item" => "treitm",
+		"table" => "tbl",
+		"table cell" => "cell",
+		"dialog" => "dlg",
+		"frame" | "window" => "wnd",
+		"tool bar" => "tlbar",
+		"status bar" => "stbar",
+		"label" | "static" | "paragraph" => "",
+		role => role,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{line_at, Line, RegionKind, Window};
+
+	#[test]
+	fn object_line() {
+		let line = Line::for_object(Some("OK"), Some("push button"), None, None);
+		assert_eq!(line.text(), "OK btn");
+		assert_eq!(line.cursor(), None);
+		// plain text has no role shown
+		let label = Line::for_object(Some("Hello"), Some("label"), None, None);
+		assert_eq!(label.regions().len(), 1);
+	}
+
+	#[test]
+	fn cursor_in_text() {
+		let line = Line::for_object(Some("Name"), Some("entry"), Some("Jo"), Some(2));
+		assert_eq!(line.text(), "Name edt Jo");
+		assert_eq!(line.cursor(), Some(11));
+		// an empty field still shows where the cursor is
+		let empty = Line::for_object(Some("Name"), Some("entry"), Some(""), Some(0));
+		assert_eq!(
+			empty.regions().last().map(|region| region.kind),
+			Some(RegionKind::Text)
+		);
+		assert_eq!(empty.cursor(), Some(9));
+	}
+
+	#[test]
+	fn only_the_current_line_of_text() {
+		assert_eq!(line_at("one\ntwo\nthree", 5), ("two".to_string(), 1));
+		assert_eq!(line_at("one\ntwo", 3), ("one".to_string(), 3));
+		assert_eq!(line_at("one\ntwo", 4), ("two".to_string(), 0));
+		assert_eq!(line_at("", 10), (String::new(), 0));
+		let line = Line::for_object(None, None, Some("one\ntwo"), Some(6));
+		assert_eq!(line.text(), "two");
+		assert_eq!(line.cursor(), Some(2));
+	}
+
+	#[test]
+	fn window_follows_the_cursor() {
+		let mut line = Line::default();
+		line.push(RegionKind::Text, "abcdefghij", Some(7));
+		assert_eq!(
+			line.window(4),
+			Window { start: 4, text: "efgh".to_string(), cursor: Some(3) }
+		);
+		let mut no_cursor = Line::default();
+		no_cursor.push(RegionKind::Name, "abcdefghij", None);
+		assert_eq!(no_cursor.window(4).text, "abcd");
+	}
+}
@@ -0,0 +1,146 @@
+//! Keeping the braille display alive when the connection to brltty breaks.
+//!
+//! When [`crate::handle_brlapi_commands`] fails, the supervisor reconnects, waiting longer
+//! between each failed attempt. Only the latest line written in the meantime is kept, and shown
+//! again once the display is back; anything older would be out of date.
+
+use std::time::Duration;
+
+use async_channel::{Receiver, Sender};
+use async_io::Timer;
+use futures_lite::FutureExt;
+use futures_util::FutureExt as FatExt;
+use odilia_common::{errors::OdiliaError, events::ScreenReaderEvent};
+use smol_cancellation_token::CancellationToken;
+
+use crate::{
+	brlapi::write_packet, create_brlapi_connection, handle_brlapi_commands, protocol::Packet,
+	BrlApiConnection, KeyBindings,
+};
+
+/// How long to wait before the first attempt to reconnect.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(250);
+/// The longest wait between attempts to reconnect.
+const MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+/// Wait for `delay`, keeping the latest packet requested in the meantime in `latest`.
+/// Returns `false` if Odilia is shutting down.
+async fn wait(
+	delay: Duration,
+	requests: &Receiver<Packet>,
+	latest: &mut Option<Packet>,
+	shutdown: &CancellationToken,
+) -> bool {
+	let mut timer = Timer::after(delay);
+	loop {
+		let next = requests
+			.recv()
+			.map(|packet| Some(packet.ok()))
+			.or((&mut timer).map(|_| None))
+			.or(shutdown.cancelled().map(|()| Some(None)));
+		match next.await {
+			// shutting down, or nothing can be shown any more
+			Some(None) => return false,
+			Some(Some(packet)) => *latest = Some(packet),
+			None => return true,
+		}
+	}
+}
+
+/// Run [`crate::handle_brlapi_commands`], reconnecting to brltty whenever the connection fails.
+///
+/// # Errors
+///
+/// None at the moment: failures are logged, and reconnecting is retried until `shutdown` is
+/// cancelled. The `Result` is kept so this can be used in place of
+/// [`crate::handle_brlapi_commands`].
+#[tracing::instrument(level = "debug", skip_all, err)]
+pub async fn supervise_brlapi_commands(
+	mut connection: BrlApiConnection,
+	requests: Receiver<Packet>,
+	events: Sender<ScreenReaderEvent>,
+	bindings: KeyBindings,
+	shutdown: CancellationToken,
+) -> Result<(), OdiliaError> {
+	let columns = connection.columns();
+	loop {
+		let handled = handle_brlapi_commands(
+			connection,
+			requests.clone(),
+			events.clone(),
+			bindings.clone(),
+			shutdown.clone(),
+		)
+		.await;
+		let Err(e) = handled else {
+			return Ok(());
+		};
+		tracing::error!(error = ?e, "Lost the connection to brltty; reconnecting");
+		let mut latest = None;
+		let mut delay = INITIAL_BACKOFF;
+		connection = loop {
+			if !wait(delay, &requests, &mut latest, &shutdown).await {
+				return Ok(());
+			}
+			delay = (delay * 2).min(MAX_BACKOFF);
+			match create_brlapi_connection().await {
+				Ok(new_connection) => break new_connection,
+				Err(e) => {
+					tracing::debug!(error = ?e, ?delay, "Could not reconnect");
+				}
+			}
+		};
+		tracing::info!("Reconnected to brltty");
+		if connection.columns() != columns {
+			tracing::warn!(
+				columns,
+				now = connection.columns(),
+				"The braille display changed size; lines are still made for the old one"
+			);
+		}
+		if let Some(packet) = latest {
+			if let Err(e) = write_packet(&mut connection.stream, &packet).await {
+				tracing::debug!(error = ?e, "Could not show the latest line again");
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+
+	use async_channel::unbounded;
+	use futures_lite::future::block_on;
+	use smol_cancellation_token::CancellationToken;
+
+	use super::{wait, MAX_BACKOFF};
+	use crate::protocol::{Packet, PacketType};
+
+	#[test]
+	fn only_the_latest_packet_is_kept() {
+		let (requests, received) = unbounded();
+		for text in ["old", "new"] {
+			requests.try_send(Packet::write_text(text, None, 3).expect("Fits"))
+				.expect("Sent");
+		}
+		let mut latest = None;
+		let waited = block_on(wait(
+			Duration::from_millis(10),
+			&received,
+			&mut latest,
+			&CancellationToken::new(),
+		));
+		assert!(waited);
+		assert_eq!(latest, Some(Packet::write_text("new", None, 3).expect("Fits")));
+	}
+
+	#[test]
+	fn shutting_down_stops_waiting() {
+		let (_requests, received) = unbounded::<Packet>();
+		let shutdown = CancellationToken::new();
+		shutdown.cancel();
+		let mut latest = Some(Packet::new(PacketType::Ack, Vec::new()));
+		assert!(!block_on(wait(MAX_BACKOFF, &received, &mut latest, &shutdown)));
+	}
+}
@@ -0,0 +1,231 @@
+//! Translating print to braille, and mapping cursor positions between the two.
+//!
+//! Contracted braille is shorter than the print it comes from ("the" is a single cell in English
+//! grade 2), so a character offset in the text is not the cell the cursor goes on. Every
+//! translation keeps, for each print character, the cell it starts at, and for each cell, the
+//! print character it came from.
+
+use std::fmt::Debug;
+
+use odilia_common::errors::OdiliaError;
+
+use crate::region::{Line, Region};
+
+/// The first Unicode braille pattern, the blank cell; dots are bits counted up from dot 1.
+pub const BRAILLE_BLANK: u32 = 0x2800;
+
+/// Dot 7, added to capital letters in computer braille.
+const DOT_7: u32 = 0x40;
+
+/// The 64 six-dot cells in order of their dots, as North American Braille ASCII: the cell for
+/// character `c` has the dots given by its position here.
+const BRAILLE_ASCII: &str = " A1B'K2L@CIF/MSP\"E3H9O6R^DJG>NTQ,*5<-U8V.%[$+X!&;:4\\0Z7(_?W]#Y)=";
+
+/// Shown for characters computer braille has no cell for.
+const UNKNOWN: char = '\u{28FF}';
+
+/// Print text in braille.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Translation {
+	/// The braille, as Unicode braille patterns, one character per cell.
+	pub braille: String,
+	/// For each character of the print text, the cell it starts at.
+	pub to_braille: Vec<usize>,
+	/// For each cell, the character of the print text it came from.
+	pub to_print: Vec<usize>,
+}
+
+impl Translation {
+	/// A translation with one cell for each print character.
+	#[must_use]
+	pub fn one_to_one(braille: String) -> Self {
+		let cells = braille.chars().count();
+		Translation {
+			braille,
+			to_braille: (0..cells).collect(),
+			to_print: (0..cells).collect(),
+		}
+	}
+	/// The cell for the cursor at character offset `print` of the text; a cursor after the
+	/// last character goes after the last cell.
+	#[must_use]
+	pub fn braille_offset(&self, print: usize) -> usize {
+		self.to_braille.get(print).copied().unwrap_or(self.to_print.len())
+	}
+	/// The character offset of the text that `cell` shows; used to route the caret to a cell.
+	#[must_use]
+	pub fn print_offset(&self, cell: usize) -> usize {
+		self.to_print.get(cell).copied().unwrap_or(self.to_braille.len())
+	}
+}
+
+/// Something which turns print into braille, according to a table.
+pub trait Translator: Debug + Send + Sync {
+	/// Translate `text` with `table`, a liblouis table name (or comma separated list of them)
+	/// like `en-us-g2.ctb`.
+	///
+	/// # Errors
+	///
+	/// If the table can not be found or compiled.
+	fn translate(&self, table: &str, text: &str) -> Result<Translation, OdiliaError>;
+}
+
+/// Eight dot computer braille: one cell per character, and no tables.
+/// This is what is shown when Odilia is built without liblouis; the table is ignored.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ComputerBraille;
+
+/// The computer braille cell for `c`; capitals have dot 7, and cells pass through unchanged.
+#[must_use]
+pub fn computer_braille(c: char) -> char {
+	let code = u32::from(c);
+	if (BRAILLE_BLANK..=BRAILLE_BLANK + 0xFF).contains(&code) {
+		return c;
+	}
+	let (base, dots) = match code {
+		0x20..=0x3F => (code, 0),
+		0x40..=0x5F => (code, DOT_7),
+		// lowercase letters and the rest share the cells of the row above, without dot 7
+		0x60..=0x7E => (code - 0x20, 0),
+		_ => return UNKNOWN,
+	};
+	BRAILLE_ASCII
+		.chars()
+		.position(|cell| u32::from(cell) == base)
+		.and_then(|pattern| u32::try_from(pattern).ok())
+		.and_then(|pattern| char::from_u32(BRAILLE_BLANK + pattern + dots))
+		.unwrap_or(UNKNOWN)
+}
+
+impl Translator for ComputerBraille {
+	fn translate(&self, _table: &str, text: &str) -> Result<Translation, OdiliaError> {
+		Ok(Translation::one_to_one(text.chars().map(computer_braille).collect()))
+	}
+}
+
+/// The translator to use: liblouis when Odilia is built with it, computer braille otherwise.
+#[cfg(feature = "liblouis")]
+#[must_use]
+pub fn default_translator() -> Box<dyn Translator> {
+	Box::new(crate::louis::Louis)
+}
+
+/// The translator to use: liblouis when Odilia is built with it, computer braille otherwise.
+#[cfg(not(feature = "liblouis"))]
+#[must_use]
+pub fn default_translator() -> Box<dyn Translator> {
+	Box::new(ComputerBraille)
+}
+
+/// Translate each region of `line` on its own.
+///
+/// # Errors
+///
+/// If any region can not be translated.
+pub fn translate_regions(
+	translator: &dyn Translator,
+	table: &str,
+	line: &Line,
+) -> Result<Vec<Translation>, OdiliaError> {
+	line.regions()
+		.iter()
+		.map(|region| translator.translate(table, &region.text))
+		.collect()
+}
+
+/// `line` in braille, from the translations of its regions; the cursor is kept on the cell for
+/// its character.
+pub(crate) fn braille_line(line: &Line, translations: &[Translation]) -> Line {
+	let regions = line
+		.regions()
+		.iter()
+		.zip(translations)
+		.map(|(region, translation)| Region {
+			kind: region.kind,
+			text: translation.braille.clone(),
+			cursor: region.cursor.map(|cursor| translation.braille_offset(cursor)),
+		})
+		.collect();
+	Line::from_regions(regions, line.text_start())
+}
+
+/// Translate each region of `line` on its own, keeping the cursor on the cell for its character.
+///
+/// # Errors
+///
+/// If any region can not be translated.
+pub fn translate_line(
+	translator: &dyn Translator,
+	table: &str,
+	line: &Line,
+) -> Result<Line, OdiliaError> {
+	let translations = translate_regions(translator, table, line)?;
+	Ok(braille_line(line, &translations))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{computer_braille, translate_line, ComputerBraille, Translation, Translator};
+	use crate::region::{Line, RegionKind};
+
+	/// "the cat" in English grade 2: "the" is one cell, and so is the space.
+	fn the_cat() -> Translation {
+		Translation {
+			braille: "⠮⠀⠉⠁⠞".to_string(),
+			to_braille: vec![0, 0, 0, 1, 2, 3, 4],
+			to_print: vec![0, 3, 4, 5, 6],
+		}
+	}
+
+	#[derive(Debug)]
+	struct Contracted;
+
+	impl Translator for Contracted {
+		fn translate(
+			&self,
+			_table: &str,
+			text: &str,
+		) -> Result<Translation, odilia_common::errors::OdiliaError> {
+			Ok(if text == "the cat" {
+				the_cat()
+			} else {
+				ComputerBraille.translate("", text)?
+			})
+		}
+	}
+
+	#[test]
+	fn cursor_mapping() {
+		let translation = the_cat();
+		// anywhere in "the" is on its cell
+		assert_eq!(translation.braille_offset(2), 0);
+		assert_eq!(translation.braille_offset(4), 2);
+		// after the end of the text
+		assert_eq!(translation.braille_offset(7), 5);
+		assert_eq!(translation.print_offset(1), 3);
+		assert_eq!(translation.print_offset(5), 7);
+	}
+
+	#[test]
+	fn computer_braille_cells() {
+		assert_eq!(computer_braille('a'), '⠁');
+		assert_eq!(computer_braille('A'), '⡁');
+		assert_eq!(computer_braille('1'), '⠂');
+		assert_eq!(computer_braille(' '), '⠀');
+		assert_eq!(computer_braille('{'), '⠪');
+		assert_eq!(computer_braille('⠮'), '⠮');
+		assert_eq!(computer_braille('é'), '⣿');
+		let translation = ComputerBraille.translate("", "Hi").expect("Always translates");
+		assert_eq!(translation, Translation::one_to_one("⡓⠊".to_string()));
+	}
+
+	#[test]
+	fn lines_keep_their_cursor() {
+		let mut line = Line::default();
+		line.push(RegionKind::Role, "edt", None);
+		line.push(RegionKind::Text, "the cat", Some(5));
+		let translated = translate_line(&Contracted, "", &line).expect("Translates");
+		assert_eq!(translated.text(), "⠑⠙⠞ ⠮⠀⠉⠁⠞");
+		assert_eq!(translated.cursor(), Some(4 + 3));
+	}
+}
@@ -0,0 +1,136 @@
+//! What is on the display, kept so that panning and routing keys can act on it.
+
+use odilia_common::{errors::OdiliaError, events::Direction};
+
+use crate::{
+	region::{Line, RegionKind, Window},
+	translation::{braille_line, translate_regions, Translation, Translator},
+};
+
+/// What a routing key was pressed over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Routed {
+	/// The object's name or role; pressing it activates the object.
+	Object,
+	/// A character of the object's text, as an offset into all of its text.
+	Text(usize),
+}
+
+/// The line last shown, in print and in braille, and the part of it on the display.
+#[derive(Debug, Clone, Default)]
+pub struct Viewport {
+	print: Line,
+	braille: Line,
+	translations: Vec<Translation>,
+	/// The first cell of the braille line which is on the display.
+	start: usize,
+}
+
+impl Viewport {
+	/// Translate `print`, and show the part of it with the cursor in it.
+	///
+	/// # Errors
+	///
+	/// If the line can not be translated.
+	pub fn new(
+		translator: &dyn Translator,
+		table: &str,
+		print: Line,
+		columns: usize,
+	) -> Result<Self, OdiliaError> {
+		let translations = translate_regions(translator, table, &print)?;
+		let braille = braille_line(&print, &translations);
+		let start = braille.window(columns).start;
+		Ok(Viewport { print, braille, translations, start })
+	}
+	/// The part of the line on a display with `columns` cells.
+	#[must_use]
+	pub fn window(&self, columns: usize) -> Window {
+		self.braille.window_at(self.start, columns)
+	}
+	/// Move a display's width towards the end or the start of the line.
+	/// Returns false if there is nothing more to show that way.
+	pub fn pan(&mut self, direction: &Direction, columns: usize) -> bool {
+		let columns = columns.max(1);
+		match direction {
+			Direction::Forward => {
+				if self.start + columns >= self.braille.text().chars().count() {
+					return false;
+				}
+				self.start += columns;
+			}
+			Direction::Backward => {
+				if self.start == 0 {
+					return false;
+				}
+				self.start = self.start.saturating_sub(columns);
+			}
+		}
+		true
+	}
+	/// What is under `cell`, counted from the start of the display.
+	#[must_use]
+	pub fn route(&self, cell: usize) -> Option<Routed> {
+		let (index, offset) = self.braille.locate(self.start + cell)?;
+		match self.print.regions().get(index)?.kind {
+			RegionKind::Text => {
+				let translation = self.translations.get(index)?;
+				Some(Routed::Text(
+					self.print.text_start() + translation.print_offset(offset),
+				))
+			}
+			RegionKind::Name | RegionKind::Role => Some(Routed::Object),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use odilia_common::events::Direction;
+
+	use super::{Routed, Viewport};
+	use crate::{region::Line, translation::ComputerBraille};
+
+	#[test]
+	fn panning() {
+		let line =
+			Line::for_object(Some("Message"), Some("entry"), Some("hello world"), None);
+		let mut viewport =
+			Viewport::new(&ComputerBraille, "", line, 8).expect("Always translates");
+		assert_eq!(viewport.window(8).start, 0);
+		assert!(viewport.pan(&Direction::Forward, 8));
+		assert_eq!(viewport.window(8).start, 8);
+		assert!(viewport.pan(&Direction::Forward, 8));
+		// "Message edt hello world" is 23 cells; there is nothing after the third window
+		assert!(!viewport.pan(&Direction::Forward, 8));
+		assert!(viewport.pan(&Direction::Backward, 8));
+		assert!(viewport.pan(&Direction::Backward, 8));
+		assert!(!viewport.pan(&Direction::Backward, 8));
+	}
+
+	#[test]
+	fn routing() {
+		let line = Line::for_object(Some("To"), Some("entry"), Some("one\ntwo"), Some(5));
+		let viewport =
+			Viewport::new(&ComputerBraille, "", line, 40).expect("Always translates");
+		// "To edt two"
+		assert_eq!(viewport.route(0), Some(Routed::Object));
+		assert_eq!(viewport.route(4), Some(Routed::Object));
+		// the second line of the text starts at offset 4
+		assert_eq!(viewport.route(7), Some(Routed::Text(4)));
+		assert_eq!(viewport.route(9), Some(Routed::Text(6)));
+		// just after the text, where typing would go
+		assert_eq!(viewport.route(10), Some(Routed::Text(7)));
+		assert_eq!(viewport.route(11), None);
+	}
+
+	#[test]
+	fn cursor_follows_panning() {
+		let line = Line::for_object(None, None, Some("abcdefghij"), Some(9));
+		let mut viewport =
+			Viewport::new(&ComputerBraille, "", line, 4).expect("Always translates");
+		assert_eq!(viewport.window(4).cursor, Some(1));
+		assert!(viewport.pan(&Direction::Backward, 4));
+		assert_eq!(viewport.window(4).cursor, None);
+	}
+}
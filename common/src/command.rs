@@ -2,7 +2,7 @@
 
 use std::{array::IntoIter, convert::Infallible, iter::Chain};
 
-use atspi::State;
+use atspi::{Role, State};
 use either::Either;
 use enum_dispatch::enum_dispatch;
 use serde::{Deserialize, Serialize};
@@ -77,6 +77,48 @@ impl IntoCommands for Speak {
 		[self.into()].into_iter()
 	}
 }
+impl IntoCommands for Spell {
+	type Iter = IntoIter<OdiliaCommand, 1>;
+	fn into_commands(self) -> Self::Iter {
+		[self.into()].into_iter()
+	}
+}
+impl IntoCommands for SpeakChar {
+	type Iter = IntoIter<OdiliaCommand, 1>;
+	fn into_commands(self) -> Self::Iter {
+		[self.into()].into_iter()
+	}
+}
+impl IntoCommands for PlaySound {
+	type Iter = IntoIter<OdiliaCommand, 1>;
+	fn into_commands(self) -> Self::Iter {
+		[self.into()].into_iter()
+	}
+}
+impl IntoCommands for Braille {
+	type Iter = IntoIter<OdiliaCommand, 1>;
+	fn into_commands(self) -> Self::Iter {
+		[self.into()].into_iter()
+	}
+}
+impl IntoCommands for SetCaret {
+	type Iter = IntoIter<OdiliaCommand, 1>;
+	fn into_commands(self) -> Self::Iter {
+		[self.into()].into_iter()
+	}
+}
+impl IntoCommands for Activate {
+	type Iter = IntoIter<OdiliaCommand, 1>;
+	fn into_commands(self) -> Self::Iter {
+		[self.into()].into_iter()
+	}
+}
+impl IntoCommands for ControlSpeech {
+	type Iter = IntoIter<OdiliaCommand, 1>;
+	fn into_commands(self) -> Self::Iter {
+		[self.into()].into_iter()
+	}
+}
 impl IntoCommands for OdiliaCommand {
 	type Iter = IntoIter<OdiliaCommand, 1>;
 	fn into_commands(self) -> Self::Iter {
@@ -181,9 +223,62 @@ pub struct CaretPos(pub usize);
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
 pub struct Speak(pub String, pub Priority);
 
+/// Like [`Speak`], but the text is spelled out one character at a time.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct Spell(pub String, pub Priority);
+
+/// Speak a single character by itself, as when the caret moves over it.
+/// Unlike [`Speak`], symbols are always named, and capital letters are indicated.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct SpeakChar(pub char, pub Priority);
+
+/// Play a sound icon (an "earcon"), by the name speech-dispatcher knows it by.
+/// It is queued along with speech, so it is heard before anything spoken after it.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct PlaySound(pub String);
+
+/// Show an object on the braille display: its name, its role, and the line of its text the caret
+/// is on. Any of these may be left out, as when only the caret has moved.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct Braille {
+	pub name: Option<String>,
+	pub role: Option<Role>,
+	pub text: Option<String>,
+	/// The character offset of the caret within all of `text`.
+	pub caret: Option<usize>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
 pub struct Focus(pub AccessiblePrimitive);
 
+/// Move the text caret of an object, as when a braille routing key is pressed over its text.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct SetCaret {
+	pub item: AccessiblePrimitive,
+	/// The character offset to move the caret to.
+	pub offset: usize,
+}
+
+/// Do the default action of an object, like pressing a button or following a link.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct Activate(pub AccessiblePrimitive);
+
+/// An action to take on speech which is currently being spoken, or is queued to be spoken.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+pub enum SpeechControl {
+	/// Stop the message currently being spoken; queued messages will continue afterwards.
+	Stop,
+	/// Pause speech, it can be picked up again with [`SpeechControl::Resume`].
+	Pause,
+	/// Resume previously paused speech.
+	Resume,
+	/// Stop the current message and throw away all queued messages.
+	CancelAll,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct ControlSpeech(pub SpeechControl);
+
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
 pub struct SetState {
 	pub item: AccessiblePrimitive,
@@ -202,14 +297,28 @@ macro_rules! impl_command_type {
 impl_command_type!(Focus, Focus);
 impl_command_type!(SetState, SetState);
 impl_command_type!(Speak, Speak);
+impl_command_type!(Spell, Spell);
+impl_command_type!(SpeakChar, SpeakChar);
+impl_command_type!(PlaySound, PlaySound);
+impl_command_type!(Braille, Braille);
 impl_command_type!(CaretPos, CaretPos);
+impl_command_type!(SetCaret, SetCaret);
+impl_command_type!(Activate, Activate);
+impl_command_type!(ControlSpeech, ControlSpeech);
 
 #[derive(Debug, Clone, EnumDiscriminants, Serialize, Deserialize, Eq, PartialEq)]
 #[strum_discriminants(derive(Ord, PartialOrd, Display))]
 #[enum_dispatch(CommandTypeDynamic)]
 pub enum OdiliaCommand {
 	Speak(Speak),
+	Spell(Spell),
+	SpeakChar(SpeakChar),
+	PlaySound(PlaySound),
+	Braille(Braille),
 	Focus(Focus),
 	CaretPos(CaretPos),
 	SetState(SetState),
+	SetCaret(SetCaret),
+	Activate(Activate),
+	ControlSpeech(ControlSpeech),
 }
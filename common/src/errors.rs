@@ -1,6 +1,7 @@
 use std::{fmt, fmt::Debug, str::FromStr};
 
 use atspi::AtspiError;
+use serde::{Deserialize, Serialize};
 use serde_plain::Error as SerdePlainError;
 use thiserror::Error;
 
@@ -50,6 +51,8 @@ pub enum OdiliaError {
 	CommandLine(#[from] lexopt::Error),
 	#[error("SSIP: {0}")]
 	Ssip(#[from] ssip_client_async::ClientError),
+	#[error("Braille: {0}")]
+	Braille(#[from] BrailleError),
 }
 
 impl From<&'static str> for OdiliaError {
@@ -222,3 +225,47 @@ pub enum NotifyError {
 	#[error("zbus specification defined error")]
 	DbusSpec(#[from] zbus::fdo::Error),
 }
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum BrailleError {
+	#[error("brltty speaks BrlAPI protocol version {0}, which is not supported")]
+	UnsupportedVersion(u32),
+	#[error("none of the ways brltty offered to authenticate are supported")]
+	Authentication,
+	#[error("brltty refused a request, with error code {0}")]
+	Refused(u32),
+	#[error("unexpected packet of type {0:#x}")]
+	UnexpectedPacket(u32),
+	#[error("malformed packet")]
+	Malformed,
+	#[error("the connection to brltty has been closed")]
+	Closed,
+	#[error("brltty did not answer in time")]
+	TimedOut,
+	#[error("could not translate with braille table {0}")]
+	Table(String),
+	#[error("there is no braille command called {0}")]
+	UnknownCommand(String),
+}
+
+/// Something wrong with what an input server sent over Odilia's socket; sent back to it as
+/// [`crate::protocol::ServerMessage::Error`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, thiserror::Error)]
+pub enum ProtocolError {
+	#[error("frame is not valid UTF-8")]
+	InvalidUtf8,
+	#[error("invalid message: {0}")]
+	InvalidMessage(String),
+	#[error("frame is longer than the limit of {0} bytes")]
+	FrameTooLong(usize),
+	#[error("protocol version {0} is not supported")]
+	UnsupportedVersion(u32),
+	#[error("a hello must be sent before anything else")]
+	HelloExpected,
+	#[error("no update {0} was sent")]
+	UnknownUpdate(u64),
+	#[error("this program is not allowed to control Odilia")]
+	Unauthorized,
+	#[error("events are being sent too quickly; some were dropped")]
+	RateLimited,
+}
@@ -10,8 +10,8 @@ use crate::modes::ScreenReaderMode;
 pub enum Feature {
 	/// Unimplemented, but will eventually stop all speech until re-activated.
 	Speech,
-	/// Unimplemented.
-	Braille, // TODO
+	/// Output to a braille display, through brltty; turning it on and off is unimplemented.
+	Braille,
 }
 
 #[derive(Eq, PartialEq, Clone, Hash, Serialize, Deserialize, Debug)]
@@ -21,6 +21,14 @@ pub enum Direction {
 	Backward,
 }
 
+#[derive(Eq, PartialEq, Clone, Copy, Hash, Serialize, Deserialize, Debug)]
+#[serde(tag = "adjustment")]
+/// Which way to move a numeric setting, like the speech rate.
+pub enum Adjustment {
+	Increase,
+	Decrease,
+}
+
 pub trait EventType {
 	const ETYPE: ScreenReaderEventDiscriminants;
 }
@@ -46,6 +54,14 @@ macro_rules! impl_event_type {
 pub struct StopSpeech;
 impl_event_type!(StopSpeech, StopSpeech);
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PauseSpeech;
+impl_event_type!(PauseSpeech, PauseSpeech);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ResumeSpeech;
+impl_event_type!(ResumeSpeech, ResumeSpeech);
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct Enable(pub Feature);
 impl_event_type!(Enable, Enable);
@@ -66,6 +82,82 @@ impl_event_type!(StructuralNavigation, StructuralNavigation);
 pub struct Quit;
 impl_event_type!(Quit, Quit);
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AdjustRate(pub Adjustment);
+impl_event_type!(AdjustRate, AdjustRate);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AdjustPitch(pub Adjustment);
+impl_event_type!(AdjustPitch, AdjustPitch);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AdjustVolume(pub Adjustment);
+impl_event_type!(AdjustVolume, AdjustVolume);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CycleVoice(pub Direction);
+impl_event_type!(CycleVoice, CycleVoice);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CycleOutputModule(pub Direction);
+impl_event_type!(CycleOutputModule, CycleOutputModule);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CycleVerbosity;
+impl_event_type!(CycleVerbosity, CycleVerbosity);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ReviewSpeechHistory(pub Direction);
+impl_event_type!(ReviewSpeechHistory, ReviewSpeechHistory);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RepeatLastSpeech;
+impl_event_type!(RepeatLastSpeech, RepeatLastSpeech);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SpellSpeechHistory;
+impl_event_type!(SpellSpeechHistory, SpellSpeechHistory);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ReadCharacter;
+impl_event_type!(ReadCharacter, ReadCharacter);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SpellWord;
+impl_event_type!(SpellWord, SpellWord);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SpellWordPhonetically;
+impl_event_type!(SpellWordPhonetically, SpellWordPhonetically);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SayAll;
+impl_event_type!(SayAll, SayAll);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Interrupt;
+impl_event_type!(Interrupt, Interrupt);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PanBraille(pub Direction);
+impl_event_type!(PanBraille, PanBraille);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RouteBraille(pub usize);
+impl_event_type!(RouteBraille, RouteBraille);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PassNextKey;
+impl_event_type!(PassNextKey, PassNextKey);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ToggleLearningMode;
+impl_event_type!(ToggleLearningMode, ToggleLearningMode);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ListKeybindings;
+impl_event_type!(ListKeybindings, ListKeybindings);
+
 #[derive(Eq, PartialEq, Clone, Serialize, Deserialize, Debug, EnumDiscriminants)]
 /// Events which can be trigged through Odilia's external API.
 /// Subject to change without notice until v1.0, but we're [open to suggestions on our Github](https://github.com/odilia-app/odilia/); please reach out with features you'd like to see.
@@ -74,6 +166,10 @@ impl_event_type!(Quit, Quit);
 pub enum ScreenReaderEvent {
 	/// Stop all current speech.
 	StopSpeech(StopSpeech),
+	/// Pause speech where it is; it can be continued with [`ScreenReaderEvent::ResumeSpeech`].
+	PauseSpeech(PauseSpeech),
+	/// Continue speech which was paused.
+	ResumeSpeech(ResumeSpeech),
 	/// Enable a feature.
 	Enable(Enable),
 	/// Disable a feature.
@@ -82,6 +178,98 @@ pub enum ScreenReaderEvent {
 	ChangeMode(ChangeMode),
 	/// Navigate to the next [`Role`] in [`Direction`] by depth-first search.
 	StructuralNavigation(StructuralNavigation),
+	/// Make speech faster or slower.
+	AdjustRate(AdjustRate),
+	/// Make speech higher or lower pitched.
+	AdjustPitch(AdjustPitch),
+	/// Make speech louder or quieter.
+	AdjustVolume(AdjustVolume),
+	/// Switch to the next or previous voice of the current synthesizer.
+	CycleVoice(CycleVoice),
+	/// Switch to the next or previous synthesizer (speech-dispatcher output module).
+	CycleOutputModule(CycleOutputModule),
+	/// Switch to the next verbosity level, which decides how much is said about each object.
+	CycleVerbosity(CycleVerbosity),
+	/// Speak the previous ([`Direction::Backward`]) or next ([`Direction::Forward`]) entry in the history of things Odilia has said.
+	ReviewSpeechHistory(ReviewSpeechHistory),
+	/// Say the most recent utterance again.
+	RepeatLastSpeech(RepeatLastSpeech),
+	/// Spell out the speech history entry currently being reviewed.
+	SpellSpeechHistory(SpellSpeechHistory),
+	/// Say the character at the caret; pressing this twice quickly says it phonetically (e.g. "alpha").
+	ReadCharacter(ReadCharacter),
+	/// Spell out the word at the caret.
+	SpellWord(SpellWord),
+	/// Spell out the word at the caret using the phonetic alphabet.
+	SpellWordPhonetically(SpellWordPhonetically),
+	/// Read the focused document from the caret to the end.
+	SayAll(SayAll),
+	/// Show the previous ([`Direction::Backward`]) or next ([`Direction::Forward`]) part of a line which is too long for the braille display.
+	PanBraille(PanBraille),
+	/// A routing key was pressed above the given cell of the braille display, counted from 0; this moves the caret there, or activates the object shown there.
+	RouteBraille(RouteBraille),
+	/// The next key combination pressed goes to the application untouched, even if it is one of Odilia's.
+	PassNextKey(PassNextKey),
+	/// Turn keyboard learning on or off: while it is on, every key pressed is described, along with what it is bound to, instead of doing it.
+	ToggleLearningMode(ToggleLearningMode),
+	/// List every key binding which works in the current mode.
+	ListKeybindings(ListKeybindings),
+	/// A key was pressed which was not meant for Odilia; this stops anything long running, like say all.
+	Interrupt(Interrupt),
 	/// Quit the screen reader.
 	Quit(Quit),
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CapsLockToggled(pub Option<bool>);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct KeySequenceStarted(pub String);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct KeySequenceCancelled;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LearningModeToggled(pub bool);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct KeyDescribed(pub String);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct KeybindingsListed(pub Vec<String>);
+
+#[derive(Eq, PartialEq, Clone, Serialize, Deserialize, Debug)]
+/// What an input server tells Odilia about itself, so it can be announced to the user.
+/// Unlike a [`ScreenReaderEvent`], these do not ask Odilia to do anything: they can not be bound to keys, and are sent as [`crate::protocol::ClientMessage::Feedback`].
+pub enum InputFeedback {
+	/// Caps lock was turned on (`Some(true)`) or off (`Some(false)`); `None` if the input server could not tell which.
+	CapsLockToggled(CapsLockToggled),
+	/// The first combo of a key sequence was pressed, and the input server waits for the rest; the string is that combo, like `CapsLock+Ctrl+T`.
+	KeySequenceStarted(KeySequenceStarted),
+	/// A started key sequence was given up on, because it was not finished in time or a key which is not part of it was pressed.
+	KeySequenceCancelled(KeySequenceCancelled),
+	/// Keyboard learning was turned on (`true`) or off (`false`).
+	LearningModeToggled(LearningModeToggled),
+	/// A key was pressed in keyboard learning mode; the string is the keys held, and what they are bound to, like `CapsLock+H: next heading`.
+	KeyDescribed(KeyDescribed),
+	/// The key bindings which work in the current mode; each is like `CapsLock+H: next heading`.
+	KeybindingsListed(KeybindingsListed),
+}
+
+macro_rules! impl_from_feedback {
+	($($variant:ident),*) => {
+		$(impl From<$variant> for InputFeedback {
+			fn from(feedback: $variant) -> Self {
+				InputFeedback::$variant(feedback)
+			}
+		})*
+	};
+}
+impl_from_feedback!(
+	CapsLockToggled,
+	KeySequenceStarted,
+	KeySequenceCancelled,
+	LearningModeToggled,
+	KeyDescribed,
+	KeybindingsListed
+);
@@ -38,3 +38,8 @@ impl_try_from_state!(U1, U2,);
 impl_try_from_state!(U1, U2, U3,);
 impl_try_from_state!(U1, U2, U3, U4,);
 impl_try_from_state!(U1, U2, U3, U4, U5,);
+impl_try_from_state!(U1, U2, U3, U4, U5, U6,);
+impl_try_from_state!(U1, U2, U3, U4, U5, U6, U7,);
+impl_try_from_state!(U1, U2, U3, U4, U5, U6, U7, U8,);
+impl_try_from_state!(U1, U2, U3, U4, U5, U6, U7, U8, U9,);
+impl_try_from_state!(U1, U2, U3, U4, U5, U6, U7, U8, U9, U10,);
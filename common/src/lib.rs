@@ -16,6 +16,9 @@ pub mod errors;
 pub mod events;
 pub mod from_state;
 pub mod modes;
+// the protocol carries the keybinding settings
+#[cfg(feature = "tracing")]
+pub mod protocol;
 pub mod result;
 #[cfg(feature = "tracing")]
 pub mod settings;
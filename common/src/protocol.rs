@@ -0,0 +1,271 @@
+//! The protocol input servers speak to Odilia over its socket.
+//!
+//! Every message is a frame of one line of JSON, ended by a newline; JSON never has a raw newline
+//! inside it, so frames may be split or joined however the socket likes. A client first sends
+//! [`ClientMessage::Hello`] with the version of the protocol it speaks and its name, which Odilia
+//! answers with [`ServerMessage::Welcome`]; then it sends events, which ask Odilia to do
+//! something, and feedback about the client itself, which Odilia announces to the user. Odilia
+//! replies to anything it cannot understand with [`ServerMessage::Error`], and otherwise ignores
+//! it.
+//!
+//! Odilia also keeps each client up to date with the state input depends on, like the current
+//! mode, by sending [`ServerMessage::Update`]s, starting with the current state right after the
+//! welcome. The client answers each one with [`ClientMessage::Ack`] once it has been applied.
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+	errors::ProtocolError,
+	events::{Feature, InputFeedback, ScreenReaderEvent},
+	modes::ScreenReaderMode,
+	settings::KeybindingSettings,
+};
+
+/// The version of the protocol this crate speaks.
+pub const PROTOCOL_VERSION: u32 = 2;
+/// The longest frame, without its newline, which will be read; longer ones are skipped.
+pub const MAX_FRAME_SIZE: usize = 64 * 1024;
+
+/// The first message of a client, saying who it is.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Hello {
+	/// The version of the protocol the client speaks; see [`PROTOCOL_VERSION`].
+	pub version: u32,
+	/// The name of the client, for logs.
+	pub client: String,
+}
+
+impl Hello {
+	/// A hello for the protocol version of this crate.
+	#[must_use]
+	pub fn new(client: impl Into<String>) -> Self {
+		Self { version: PROTOCOL_VERSION, client: client.into() }
+	}
+}
+
+/// What an input server sends to Odilia.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ClientMessage {
+	Hello(Hello),
+	Event(ScreenReaderEvent),
+	/// Something about the client for Odilia to announce, like a key being described.
+	Feedback(InputFeedback),
+	/// The [`ServerMessage::Update`] with this id has been applied.
+	Ack(u64),
+}
+
+/// A change to the state of Odilia which input servers need to know about.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Update {
+	/// The mode Odilia is now in, whichever client or part of Odilia changed it.
+	Mode(ScreenReaderMode),
+	/// The features which are now on; any others are off.
+	Features(Vec<Feature>),
+	/// The `[keybindings]` settings, which replace any the client has.
+	Keybindings(KeybindingSettings),
+	/// Whether Odilia is reading on its own, like in a say all; only then do keys meant for
+	/// applications need to interrupt it.
+	Reading(bool),
+}
+
+/// What Odilia sends back to an input server.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ServerMessage {
+	/// The hello was accepted; events may follow.
+	Welcome {
+		version: u32,
+	},
+	/// A change the client should apply, then acknowledge with [`ClientMessage::Ack`] and `id`.
+	Update {
+		id: u64,
+		update: Update,
+	},
+	Error(ProtocolError),
+}
+
+/// Encode `message` as a frame, ready to be written to the socket.
+///
+/// # Errors
+///
+/// If `message` cannot be serialized, or is longer than [`MAX_FRAME_SIZE`].
+pub fn encode<T: Serialize>(message: &T) -> Result<Vec<u8>, ProtocolError> {
+	let mut frame = serde_json::to_vec(message)
+		.map_err(|e| ProtocolError::InvalidMessage(e.to_string()))?;
+	if frame.len() > MAX_FRAME_SIZE {
+		return Err(ProtocolError::FrameTooLong(MAX_FRAME_SIZE));
+	}
+	frame.push(b'\n');
+	Ok(frame)
+}
+
+/// Decode a message from `frame`, without its newline.
+///
+/// # Errors
+///
+/// If `frame` is not UTF-8, or not JSON of a `T`.
+pub fn decode<T: DeserializeOwned>(frame: &[u8]) -> Result<T, ProtocolError> {
+	let text = std::str::from_utf8(frame).map_err(|_| ProtocolError::InvalidUtf8)?;
+	serde_json::from_str(text).map_err(|e| ProtocolError::InvalidMessage(e.to_string()))
+}
+
+/// Splits the bytes read from a socket into frames.
+#[derive(Debug, Default)]
+pub struct FrameDecoder {
+	buffer: Vec<u8>,
+	/// Whether the start of the buffered frame was too long, and was thrown away.
+	discarding: bool,
+}
+
+impl FrameDecoder {
+	#[must_use]
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Add bytes read from the socket.
+	pub fn push(&mut self, bytes: &[u8]) {
+		self.buffer.extend_from_slice(bytes);
+	}
+
+	/// The next whole frame, without its newline, or `None` until more bytes are pushed.
+	/// A frame longer than [`MAX_FRAME_SIZE`] is reported once, as soon as it is too long,
+	/// and the rest of it is skipped.
+	pub fn next_frame(&mut self) -> Option<Result<Vec<u8>, ProtocolError>> {
+		loop {
+			let Some(end) = self.buffer.iter().position(|b| *b == b'\n') else {
+				if self.buffer.len() <= MAX_FRAME_SIZE {
+					return None;
+				}
+				self.buffer.clear();
+				if self.discarding {
+					return None;
+				}
+				self.discarding = true;
+				return Some(Err(ProtocolError::FrameTooLong(MAX_FRAME_SIZE)));
+			};
+			let mut frame: Vec<u8> = self.buffer.drain(..=end).collect();
+			frame.pop();
+			if std::mem::take(&mut self.discarding) {
+				continue;
+			}
+			if frame.len() > MAX_FRAME_SIZE {
+				return Some(Err(ProtocolError::FrameTooLong(MAX_FRAME_SIZE)));
+			}
+			return Some(Ok(frame));
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use proptest::prelude::*;
+
+	use super::*;
+	use crate::{
+		events::{ChangeMode, KeyDescribed, Quit, StopSpeech},
+		modes::ScreenReaderMode,
+	};
+
+	fn messages() -> Vec<ClientMessage> {
+		vec![
+			ClientMessage::Hello(Hello::new("test")),
+			ClientMessage::Event(ScreenReaderEvent::StopSpeech(StopSpeech)),
+			ClientMessage::Event(ScreenReaderEvent::ChangeMode(ChangeMode(
+				ScreenReaderMode::Browse,
+			))),
+			ClientMessage::Event(ScreenReaderEvent::Quit(Quit)),
+			ClientMessage::Feedback(InputFeedback::KeyDescribed(KeyDescribed(
+				"CapsLock+H: next heading".to_string(),
+			))),
+			ClientMessage::Ack(7),
+		]
+	}
+
+	fn frames(decoder: &mut FrameDecoder) -> Vec<Result<Vec<u8>, ProtocolError>> {
+		std::iter::from_fn(|| decoder.next_frame()).collect()
+	}
+
+	#[test]
+	fn frames_are_split_at_newlines() {
+		let mut decoder = FrameDecoder::new();
+		decoder.push(b"one\ntw");
+		assert_eq!(frames(&mut decoder), vec![Ok(b"one".to_vec())]);
+		decoder.push(b"o\n\nthree");
+		assert_eq!(frames(&mut decoder), vec![Ok(b"two".to_vec()), Ok(Vec::new())]);
+	}
+
+	#[test]
+	fn long_frames_are_reported_once_and_skipped() {
+		let mut decoder = FrameDecoder::new();
+		decoder.push(&vec![b'a'; MAX_FRAME_SIZE + 1]);
+		assert_eq!(
+			frames(&mut decoder),
+			vec![Err(ProtocolError::FrameTooLong(MAX_FRAME_SIZE))]
+		);
+		decoder.push(&vec![b'a'; MAX_FRAME_SIZE + 1]);
+		decoder.push(b"a\nnext\n");
+		assert_eq!(frames(&mut decoder), vec![Ok(b"next".to_vec())]);
+	}
+
+	#[test]
+	fn updates_round_trip() {
+		let updates = [
+			Update::Mode(ScreenReaderMode::Browse),
+			Update::Features(vec![Feature::Speech]),
+			Update::Keybindings(KeybindingSettings::default()),
+			Update::Reading(true),
+		];
+		for (id, update) in (0..).zip(updates) {
+			let message = ServerMessage::Update { id, update };
+			let frame = encode(&message).expect("Encodable update");
+			assert_eq!(frame.last(), Some(&b'\n'));
+			assert_eq!(decode(&frame[..frame.len() - 1]), Ok(message));
+		}
+	}
+
+	#[test]
+	fn malformed_frames_are_errors() {
+		assert_eq!(decode::<ClientMessage>(b"\xff\xfe"), Err(ProtocolError::InvalidUtf8));
+		assert!(matches!(
+			decode::<ClientMessage>(b"{\"Nope\":1}"),
+			Err(ProtocolError::InvalidMessage(_))
+		));
+	}
+
+	proptest! {
+		#[test]
+		fn messages_survive_any_split(splits in proptest::collection::vec(0usize..512, 0..16)) {
+			let bytes: Vec<u8> = messages()
+				.iter()
+				.flat_map(|message| encode(message).expect("Encodable message"))
+				.collect();
+			let mut decoder = FrameDecoder::new();
+			let mut decoded = Vec::new();
+			let mut rest = bytes.as_slice();
+			for split in splits {
+				let (chunk, tail) = rest.split_at(split.min(rest.len()));
+				decoder.push(chunk);
+				rest = tail;
+				decoded.extend(frames(&mut decoder));
+			}
+			decoder.push(rest);
+			decoded.extend(frames(&mut decoder));
+			let decoded: Vec<ClientMessage> = decoded
+				.into_iter()
+				.map(|frame| decode(&frame.expect("Short frame")).expect("Valid message"))
+				.collect();
+			prop_assert_eq!(decoded, messages());
+		}
+
+		#[test]
+		fn garbage_never_panics(chunks in proptest::collection::vec(proptest::collection::vec(any::<u8>(), 0..256), 0..16)) {
+			let mut decoder = FrameDecoder::new();
+			for chunk in chunks {
+				decoder.push(&chunk);
+				for frame in frames(&mut decoder).into_iter().flatten() {
+					let _ = decode::<ClientMessage>(&frame);
+				}
+			}
+		}
+	}
+}
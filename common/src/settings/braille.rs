@@ -0,0 +1,30 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::events::ScreenReaderEvent;
+
+///structure for all the configurable options related to braille output
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[allow(clippy::module_name_repetitions)]
+pub struct BrailleSettings {
+	///the liblouis table to translate text in the user's own language with, like "en-us-g2.ctb" for contracted (grade 2) English or "en-us-g1.ctb" for uncontracted (grade 1)
+	pub table: String,
+	///switch table for text the application says is in another language
+	pub follow_language: bool,
+	///the table to use for each language, keyed by language code like "de" or "pt-BR"; languages not listed use `table`
+	pub tables: HashMap<String, String>,
+	///what the keys of the display do, keyed by the brltty command its key table sends, like "FWINLT" for panning left; these are added to the defaults, and routing keys always move the caret
+	pub keys: HashMap<String, ScreenReaderEvent>,
+}
+
+impl Default for BrailleSettings {
+	fn default() -> Self {
+		Self {
+			table: "en-us-g2.ctb".into(),
+			follow_language: true,
+			tables: HashMap::new(),
+			keys: HashMap::new(),
+		}
+	}
+}
@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+///whether a piece of information is given in words, with a sound icon (an "earcon"), or both
+/// when there is no sound icon for something, it is always spoken, so nothing is ever lost
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum FeedbackMode {
+	///only speak it
+	#[default]
+	Speech,
+	///only play the sound icon
+	Sound,
+	///play the sound icon, then speak it
+	Both,
+}
+
+impl FeedbackMode {
+	#[must_use]
+	pub fn speaks(self) -> bool {
+		self != FeedbackMode::Sound
+	}
+	#[must_use]
+	pub fn plays(self) -> bool {
+		self != FeedbackMode::Speech
+	}
+}
+
+///short non-speech sounds played for roles and state changes
+/// the names are those of speech-dispatcher's sound icons: files in its sound icon directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[allow(clippy::module_name_repetitions)]
+pub struct EarconSettings {
+	///how the role of a newly focused object, like a link or a button, is given
+	pub roles: FeedbackMode,
+	///how a change to the focused object's state, like a check box being checked, is given
+	pub states: FeedbackMode,
+	///the sound icon for each role, keyed by the role's name, like "button" or "check box"
+	pub role_icons: HashMap<String, String>,
+	///the sound icon for each state change, keyed by what would otherwise be spoken, like "checked" or "collapsed"
+	pub state_icons: HashMap<String, String>,
+}
+
+impl Default for EarconSettings {
+	fn default() -> Self {
+		let icons = |pairs: &[(&str, &str)]| {
+			pairs.iter()
+				.map(|(key, icon)| ((*key).to_string(), (*icon).to_string()))
+				.collect()
+		};
+		Self {
+			roles: FeedbackMode::default(),
+			states: FeedbackMode::default(),
+			role_icons: icons(&[
+				("link", "link"),
+				("button", "button"),
+				("check box", "check-box"),
+				("radio button", "radio-button"),
+				("combo box", "combo-box"),
+				("entry", "entry"),
+				("heading", "heading"),
+			]),
+			state_icons: icons(&[
+				("checked", "checked"),
+				("not checked", "unchecked"),
+				("partially checked", "partially-checked"),
+				("expanded", "expanded"),
+				("collapsed", "collapsed"),
+				("pressed", "checked"),
+				("not pressed", "unchecked"),
+				("invalid", "error"),
+			]),
+		}
+	}
+}
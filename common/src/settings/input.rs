@@ -1,11 +1,29 @@
+use std::path::PathBuf;
+
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, Default)]
+///structure for the options related to input methods, and the socket they control Odilia through
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[allow(clippy::module_name_repetitions)]
 pub struct InputSettings {
 	pub method: InputMethod,
+	///the programs allowed to connect to Odilia's input socket, by the full path of their executable, like "/usr/bin/odilia-input-server-keyboard"; if empty, any program run by the same user may
+	pub allowed_clients: Vec<PathBuf>,
+	///how many events each input method may send a second; short bursts of up to this many at once are allowed, and any more are dropped
+	pub events_per_second: u32,
+}
+
+impl Default for InputSettings {
+	fn default() -> Self {
+		Self {
+			method: InputMethod::default(),
+			allowed_clients: Vec::new(),
+			events_per_second: 50,
+		}
+	}
 }
 
-#[derive(Debug, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub enum InputMethod {
 	#[default]
 	Keyboard,
@@ -0,0 +1,37 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::events::ScreenReaderEvent;
+
+///keybindings for the keyboard input server, keyed by combos like "CapsLock+Shift+H", or sequences of them separated by spaces; these are added to the defaults, replacing any default with the same keys in the same mode
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+#[allow(clippy::module_name_repetitions)]
+pub struct KeybindingSettings {
+	///the keys held down for every combo, like `CapsLock` or `Insert`; pressing one twice quickly passes it through, so caps lock can still be toggled
+	pub activation_keys: Vec<String>,
+	///how quickly, in milliseconds, an activation key has to be pressed again to pass it through
+	pub double_tap_ms: u64,
+	///how long, in milliseconds, to wait for the next combo of a sequence like "CapsLock+Ctrl+T R" before giving up on it
+	pub sequence_timeout_ms: u64,
+	///combos which work in every mode
+	pub global: HashMap<String, ScreenReaderEvent>,
+	///combos which only work in focus mode
+	pub focus: HashMap<String, ScreenReaderEvent>,
+	///combos which only work in browse mode
+	pub browse: HashMap<String, ScreenReaderEvent>,
+}
+
+impl Default for KeybindingSettings {
+	fn default() -> Self {
+		Self {
+			activation_keys: vec!["CapsLock".into()],
+			double_tap_ms: 400,
+			sequence_timeout_ms: 1500,
+			global: HashMap::new(),
+			focus: HashMap::new(),
+			browse: HashMap::new(),
+		}
+	}
+}
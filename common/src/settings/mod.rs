@@ -1,11 +1,21 @@
+pub mod braille;
+pub mod earcons;
 pub mod input;
+pub mod keybindings;
 pub mod log;
+pub mod pronunciation;
 pub mod speech;
+pub mod symbols;
 
+pub use braille::BrailleSettings;
+pub use earcons::EarconSettings;
 pub use input::{InputMethod, InputSettings};
+pub use keybindings::KeybindingSettings;
 pub use log::LogSettings;
+pub use pronunciation::PronunciationSettings;
 use serde::{Deserialize, Serialize};
 pub use speech::SpeechSettings;
+pub use symbols::SymbolSettings;
 
 ///type representing a *read-only* view of the odilia screenreader configuration
 /// this type should only be obtained as a result of parsing odilia's configuration files, as it containes types for each section responsible for controlling various parts of the screenreader
@@ -13,6 +23,8 @@ pub use speech::SpeechSettings;
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct ApplicationConfig {
 	pub speech: SpeechSettings,
+	pub braille: BrailleSettings,
 	pub log: LogSettings,
 	pub input: InputSettings,
+	pub keybindings: KeybindingSettings,
 }
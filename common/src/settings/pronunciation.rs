@@ -0,0 +1,39 @@
+use serde::{Deserialize, Serialize};
+
+///the user's pronunciation dictionary, read from `pronunciation.toml`, which lives next to `config.toml`
+/// rules are applied in the order they are written, so a later rule sees the text as changed by the earlier ones
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[allow(clippy::module_name_repetitions)]
+pub struct PronunciationSettings {
+	#[serde(default)]
+	pub rules: Vec<PronunciationRule>,
+}
+
+///a single substitution: every match of `pattern` is spoken as `replacement`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[allow(clippy::module_name_repetitions)]
+pub struct PronunciationRule {
+	pub pattern: String,
+	///for [`PatternKind::Regex`] rules, this may refer to capture groups, like `$1` or `${name}`
+	pub replacement: String,
+	#[serde(default)]
+	pub kind: PatternKind,
+	#[serde(default)]
+	pub case_sensitive: bool,
+	///only match the pattern when it is not part of a larger word
+	#[serde(default)]
+	pub whole_word: bool,
+	///the name of the application this rule applies to (compared case-insensitively); if this is not set, the rule applies everywhere
+	#[serde(default)]
+	pub app: Option<String>,
+}
+
+///how the `pattern` of a [`PronunciationRule`] is interpreted
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum PatternKind {
+	///the pattern is matched as plain text
+	#[default]
+	Literal,
+	///the pattern is a regular expression, using the syntax of the `regex` crate
+	Regex,
+}
@@ -1,6 +1,10 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
+
+use super::EarconSettings;
 ///structure for all the speech related configuration options available in odilia
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[allow(clippy::module_name_repetitions)]
 pub struct SpeechSettings {
 	pub rate: i8,
@@ -9,7 +13,30 @@ pub struct SpeechSettings {
 	pub module: String,
 	pub language: String,
 	pub person: String,
+	///switch language (and voice) for text the application says is in another language, like a quote on a web page
+	pub follow_language: bool,
+	///the voice to use for each language, keyed by language code like "de" or "pt-BR"; languages not listed use `person`
+	pub voices: HashMap<String, String>,
 	pub punctuation: PunctuationSpellingMode,
+	///the order in which information about an object is spoken when it is focused
+	/// any field left out of this list is not spoken at all
+	pub field_order: Vec<SpeechField>,
+	///how much is said about each object; see [`Verbosity`]
+	pub verbosity: Verbosity,
+	///verbosity overrides for specific roles, keyed by the role's name, like "button" or "check box"
+	pub role_verbosity: HashMap<String, Verbosity>,
+	///what is spoken while typing into a text field
+	pub echo: EchoMode,
+	///say "star" for each character typed into or deleted from a password field; when false, nothing is said at all
+	pub password_stars: bool,
+	///how a capital letter is indicated when it is read by itself
+	pub capitals: CapitalIndication,
+	///whether numbers are read as a whole ("one hundred twenty three") or one digit at a time ("one two three")
+	pub numbers: NumberReading,
+	///how much text is sent to the synthesizer at a time while reading a whole document
+	pub say_all_by: SayAllUnit,
+	///sound icons played for roles and state changes, in place of or as well as speaking them
+	pub earcons: EarconSettings,
 }
 impl Default for SpeechSettings {
 	fn default() -> Self {
@@ -20,15 +47,187 @@ impl Default for SpeechSettings {
 			module: "espeak-ng".into(),
 			language: "en-US".into(),
 			person: "English (America)+Max".into(),
+			follow_language: true,
+			voices: HashMap::new(),
 			punctuation: PunctuationSpellingMode::Some,
+			field_order: vec![
+				SpeechField::Name,
+				SpeechField::Value,
+				SpeechField::Role,
+				SpeechField::States,
+				SpeechField::Position,
+				SpeechField::Description,
+				SpeechField::Shortcut,
+				SpeechField::HelpText,
+				SpeechField::Tutorial,
+			],
+			verbosity: Verbosity::default(),
+			role_verbosity: HashMap::new(),
+			echo: EchoMode::default(),
+			password_stars: true,
+			capitals: CapitalIndication::default(),
+			numbers: NumberReading::default(),
+			say_all_by: SayAllUnit::default(),
+			earcons: EarconSettings::default(),
 		}
 	}
 }
 
-#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub enum PunctuationSpellingMode {
 	Some,
 	Most,
 	None,
 	All,
 }
+
+impl PunctuationSpellingMode {
+	///whether a symbol with the given level is spoken when this is the current punctuation mode
+	/// levels go from [`None`](PunctuationSpellingMode::None), through `Some` and `Most`, up to `All`
+	#[must_use]
+	pub fn includes(self, level: PunctuationSpellingMode) -> bool {
+		fn rank(mode: PunctuationSpellingMode) -> u8 {
+			match mode {
+				PunctuationSpellingMode::None => 0,
+				PunctuationSpellingMode::Some => 1,
+				PunctuationSpellingMode::Most => 2,
+				PunctuationSpellingMode::All => 3,
+			}
+		}
+		rank(level) <= rank(self)
+	}
+}
+
+///how a capital letter is indicated when it is read by itself
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum CapitalIndication {
+	///capital letters sound exactly like lower case ones
+	None,
+	///say "cap" before the letter
+	#[default]
+	Say,
+	///read the letter at a higher pitch
+	Pitch,
+	///play a sound before the letter
+	Icon,
+}
+
+///how numbers are read
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum NumberReading {
+	///as whole numbers, leaving it up to the synthesizer
+	#[default]
+	Whole,
+	///one digit at a time
+	Digits,
+}
+
+///the pieces a document is split into while it is read from the caret onwards
+/// reading can only be followed (and stopped) at the start of a piece
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum SayAllUnit {
+	///one sentence at a time, ending at a full stop, question mark, exclamation mark or line break
+	#[default]
+	Sentence,
+	///one line at a time
+	Line,
+}
+
+///a piece of information which can be spoken about an object
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum SpeechField {
+	///the name or label of the object
+	Name,
+	///the current contents of an editable object, like a text entry or spin button
+	Value,
+	///what kind of object it is, like "push button"
+	Role,
+	///states the user can change, like "checked" or "expanded"
+	States,
+	///where the object is among its siblings, like "3 of 5"
+	Position,
+	///the longer description of the object, if it has a name as well
+	Description,
+	///the keyboard shortcut which activates the object
+	Shortcut,
+	///extra help provided by the application, usually shown as a tooltip
+	HelpText,
+	///a hint on how to use the object, like "press space to toggle"
+	Tutorial,
+}
+
+///how much Odilia says about each object
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum Verbosity {
+	///everything, including help text and hints on how to use each kind of object
+	Beginner,
+	///roles, descriptions and state changes, but no help text or hints
+	#[default]
+	Normal,
+	///only names, values, and states at the time an object is focused
+	Terse,
+}
+
+impl Verbosity {
+	///the next level, from most to least verbose, wrapping around
+	#[must_use]
+	pub fn next(self) -> Self {
+		match self {
+			Verbosity::Beginner => Verbosity::Normal,
+			Verbosity::Normal => Verbosity::Terse,
+			Verbosity::Terse => Verbosity::Beginner,
+		}
+	}
+	///whether the role of an object is spoken
+	#[must_use]
+	pub fn speaks_role(self) -> bool {
+		self != Verbosity::Terse
+	}
+	///whether the description of an object is spoken
+	#[must_use]
+	pub fn speaks_description(self) -> bool {
+		self != Verbosity::Terse
+	}
+	///whether the help text of an object is spoken
+	#[must_use]
+	pub fn speaks_help_text(self) -> bool {
+		self == Verbosity::Beginner
+	}
+	///whether hints on how to use an object are spoken
+	#[must_use]
+	pub fn speaks_tutorial(self) -> bool {
+		self == Verbosity::Beginner
+	}
+	///whether changes to the state of the focused object (like being checked) are spoken
+	#[must_use]
+	pub fn speaks_state_changes(self) -> bool {
+		self != Verbosity::Terse
+	}
+}
+
+///what is spoken while typing; deleted characters are always spoken
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum EchoMode {
+	///nothing is said while typing
+	None,
+	///each character is spoken as it is typed
+	#[default]
+	Character,
+	///each word is spoken once it is finished, by typing a space or punctuation
+	Word,
+	///both characters and finished words are spoken
+	Both,
+}
+
+impl EchoMode {
+	///whether each character is spoken as it is typed
+	#[must_use]
+	pub fn echoes_characters(self) -> bool {
+		matches!(self, EchoMode::Character | EchoMode::Both)
+	}
+	///whether words are spoken once they are finished
+	#[must_use]
+	pub fn echoes_words(self) -> bool {
+		matches!(self, EchoMode::Word | EchoMode::Both)
+	}
+}
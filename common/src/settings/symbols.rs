@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+
+use super::speech::PunctuationSpellingMode;
+
+///the user's symbol names, read from `symbols.toml`, which lives next to `config.toml`
+/// these are used before Odilia's built in (English) names, so they can both add symbols and translate existing ones
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[allow(clippy::module_name_repetitions)]
+pub struct SymbolSettings {
+	#[serde(default)]
+	pub symbols: Vec<SymbolEntry>,
+}
+
+///the spoken name of a single symbol
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[allow(clippy::module_name_repetitions)]
+pub struct SymbolEntry {
+	pub symbol: char,
+	pub name: String,
+	///the lowest punctuation level at which the name is spoken in running text; a symbol read by itself is always named
+	#[serde(default = "default_level")]
+	pub level: PunctuationSpellingMode,
+	///the language this name is for, like "de" or "pt-BR" (only the part before the `-` is compared); if this is not set, the name is used for every language
+	#[serde(default)]
+	pub language: Option<String>,
+}
+
+fn default_level() -> PunctuationSpellingMode {
+	PunctuationSpellingMode::Most
+}
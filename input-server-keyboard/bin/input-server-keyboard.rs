@@ -15,16 +15,25 @@
 
 use std::{
 	env,
-	io::Write,
+	io::{BufRead, BufReader, Write},
 	os::unix::net::UnixStream,
 	path::PathBuf,
-	sync::mpsc::{sync_channel, Receiver},
+	sync::{
+		mpsc::{channel, sync_channel, Receiver, Sender},
+		Arc, Mutex, PoisonError,
+	},
 	thread,
+	time::Duration,
 };
 
 use nix::unistd::Uid;
-use odilia_common::{events::ScreenReaderEvent as OdiliaEvent, modes::ScreenReaderMode as Mode};
-use odilia_input_server_keyboard::{callback, ComboSets, State};
+use odilia_common::{
+	events::{InputFeedback, ScreenReaderEvent as OdiliaEvent},
+	modes::ScreenReaderMode as Mode,
+	protocol::{decode, encode, ClientMessage, Hello, ServerMessage, Update},
+	settings::KeybindingSettings,
+};
+use odilia_input_server_keyboard::{callback, ComboSets, State, ACTIVATION_KEY};
 use rdev::grab;
 
 /// Finds PID and Socket files and returns their respective [`PathBuf`]s.
@@ -38,7 +47,7 @@ fn get_file_paths() -> (PathBuf, PathBuf) {
             );
 
 			let pid_file_path = format!("{val}/odilias.pid");
-			let sock_file_path = format!("{val}/odilia.sock");
+			let sock_file_path = format!("{val}/odilia/odilia.sock");
 
 			(pid_file_path.into(), sock_file_path.into())
 		}
@@ -46,23 +55,126 @@ fn get_file_paths() -> (PathBuf, PathBuf) {
 			tracing::warn!(error=%e, "XDG_RUNTIME_DIR Variable is not set, falling back to hardcoded path");
 
 			let pid_file_path = format!("/run/user/{}/odilias.pid", Uid::current());
-			let sock_file_path = format!("/run/user/{}/odilia.sock", Uid::current());
+			let sock_file_path =
+				format!("/run/user/{}/odilia/odilia.sock", Uid::current());
 
 			(pid_file_path.into(), sock_file_path.into())
 		}
 	}
 }
 
+/// The next frame from Odilia, without its newline, or `None` if it has hung up.
+fn read_frame(reader: &mut impl BufRead) -> Result<Option<Vec<u8>>, std::io::Error> {
+	let mut frame = Vec::new();
+	reader.read_until(b'\n', &mut frame)?;
+	if frame.pop() != Some(b'\n') {
+		return Ok(None);
+	}
+	Ok(Some(frame))
+}
+
+/// Says hello to Odilia over `stream`, and waits for it to be welcomed.
+/// Returns the reader the rest of Odilia's messages come from.
+fn handshake(stream: &mut UnixStream) -> Result<BufReader<UnixStream>, std::io::Error> {
+	let hello = ClientMessage::Hello(Hello::new(env!("CARGO_PKG_NAME")));
+	stream.write_all(&encode(&hello).map_err(std::io::Error::other)?)?;
+	let mut reader = BufReader::new(stream.try_clone()?);
+	let frame = read_frame(&mut reader)?.ok_or(std::io::ErrorKind::UnexpectedEof)?;
+	match decode(&frame).map_err(std::io::Error::other)? {
+		ServerMessage::Welcome { version } => {
+			tracing::debug!(version, "Odilia welcomed us");
+			Ok(reader)
+		}
+		ServerMessage::Error(e) => Err(std::io::Error::other(e)),
+		ServerMessage::Update { .. } => {
+			Err(std::io::Error::other("Odilia sent an update before welcoming us"))
+		}
+	}
+}
+
+/// Reads messages from Odilia until it hangs up, passing its updates on to the keyboard [`State`]
+/// and acknowledging them over `writer`.
+fn handle_messages_from_socket(
+	mut reader: BufReader<UnixStream>,
+	writer: &Mutex<UnixStream>,
+	updates: &Sender<Update>,
+) -> Result<(), std::io::Error> {
+	while let Some(frame) = read_frame(&mut reader)? {
+		match decode(&frame) {
+			Ok(ServerMessage::Update { id, update }) => {
+				tracing::debug!(id, ?update, "Update from Odilia");
+				if updates.send(update).is_err() {
+					tracing::error!(
+						"The keyboard is no longer being listened to"
+					);
+					break;
+				}
+				let ack = encode(&ClientMessage::Ack(id))
+					.map_err(std::io::Error::other)?;
+				writer.lock()
+					.unwrap_or_else(PoisonError::into_inner)
+					.write_all(&ack)?;
+			}
+			Ok(ServerMessage::Error(error)) => {
+				tracing::error!(%error, "Odilia could not understand a message");
+			}
+			Ok(ServerMessage::Welcome { .. }) => {
+				tracing::warn!("Odilia welcomed us again");
+			}
+			Err(error) => tracing::error!(%error, "Invalid message from Odilia"),
+		}
+	}
+	Ok(())
+}
+
+/// Sends each piece of `feedback` over `writer`, for Odilia to announce, until the keyboard is no
+/// longer listened to or Odilia hangs up.
+fn send_feedback_to_socket(
+	feedback: &Receiver<InputFeedback>,
+	writer: &Mutex<UnixStream>,
+) -> Result<(), std::io::Error> {
+	for feedback in feedback {
+		let frame = encode(&ClientMessage::Feedback(feedback))
+			.expect("Should be able to serialize any feedback!");
+		writer.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.write_all(&frame)?;
+	}
+	Ok(())
+}
+
 /// Takes a [`Receiver`] and blocks forever waiting on results from it.
-/// When it receives an event, it sends it over the unix socket to notify Odilia.
-fn handle_events_to_socket(rx: &Receiver<OdiliaEvent>) -> Result<(), std::io::Error> {
+/// When it receives an event, it sends it over the unix socket to notify Odilia; `feedback` is
+/// sent the same way.
+/// Updates from Odilia are passed on over `updates`.
+fn handle_events_to_socket(
+	rx: &Receiver<OdiliaEvent>,
+	feedback: Receiver<InputFeedback>,
+	updates: Sender<Update>,
+) -> Result<(), std::io::Error> {
 	let (_pid_path, sock_path) = get_file_paths();
 	tracing::debug!(?sock_path, "This is the socket path we recieved");
 	let mut stream = UnixStream::connect(&sock_path)?;
+	let reader = handshake(&mut stream)?;
+	let writer = Arc::new(Mutex::new(stream));
+	let acks = Arc::clone(&writer);
+	let _ = thread::spawn(move || {
+		if let Err(error) = handle_messages_from_socket(reader, &acks, &updates) {
+			tracing::error!(%error, "Error reading messages from Odilia");
+		}
+	});
+	let announcements = Arc::clone(&writer);
+	let _ = thread::spawn(move || {
+		if let Err(error) = send_feedback_to_socket(&feedback, &announcements) {
+			tracing::error!(%error, "Error sending feedback to Odilia");
+		}
+	});
 	for event in rx {
-		let val = serde_json::to_string(&event)
+		let frame = encode(&ClientMessage::Event(event))
 			.expect("Should be able to serialize any event!");
-		stream.write_all(val.as_bytes())?;
+		writer.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.write_all(&frame)?;
 	}
 	Ok(())
 }
@@ -72,14 +184,29 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 	// NOTE: this will _block the input thread_ if events are not removed from it often.
 	// This _should_ never be a problem, because two threads are running, but you never know.
 	let (ev_tx, ev_rx) = sync_channel::<OdiliaEvent>(255);
-	let combos = ComboSets::default();
+	let (feedback_tx, feedback_rx) = sync_channel::<InputFeedback>(255);
+	let (update_tx, update_rx) = channel();
+	// Odilia sends its `[keybindings]` configuration as soon as it has welcomed us; until then,
+	// the defaults are used, so that the screen reader can still be controlled
+	let settings = KeybindingSettings::default();
 	let state = State {
 		mode: Mode::Focus,
 		activation_key_pressed: false,
+		activation_keys: vec![ACTIVATION_KEY],
+		double_tap: Duration::from_millis(settings.double_tap_ms),
+		last_tap: None,
+		pass_next: false,
+		passing: Vec::new(),
+		pending: None,
+		learning: false,
+		reading: false,
+		sequence_timeout: Duration::from_millis(settings.sequence_timeout_ms),
 		// no allocations below 10-key rollover
 		pressed: Vec::with_capacity(10),
-		combos,
+		combos: ComboSets::default(),
 		tx: ev_tx,
+		feedback: feedback_tx,
+		updates: update_rx,
 	};
 	let _ = thread::spawn(move || {
 		// This will block.
@@ -87,6 +214,6 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 			tracing::error!("Error grabbing keyboard: {error:?}");
 		}
 	});
-	handle_events_to_socket(&ev_rx)?;
+	handle_events_to_socket(&ev_rx, feedback_rx, update_tx)?;
 	Ok(())
 }
@@ -0,0 +1,104 @@
+//! What keys do, in words: for keyboard learning mode, where each key pressed is described
+//! instead of doing anything, and for listing every binding in a mode.
+
+use odilia_common::events::{
+	Adjustment, ChangeMode, Direction, Disable, Enable, Feature,
+	ScreenReaderEvent as OdiliaEvent,
+};
+use rdev::Key;
+
+use crate::{keybindings::mode_name, ComboSets, KeySequence, Mode};
+
+/// `forward` or `backward`, whichever way `direction` goes.
+fn towards(direction: &Direction, forward: &'static str, backward: &'static str) -> &'static str {
+	match direction {
+		Direction::Forward => forward,
+		Direction::Backward => backward,
+	}
+}
+
+/// `increase` or `decrease`, whichever way `adjustment` goes.
+fn adjust(adjustment: Adjustment, increase: &'static str, decrease: &'static str) -> &'static str {
+	match adjustment {
+		Adjustment::Increase => increase,
+		Adjustment::Decrease => decrease,
+	}
+}
+
+fn feature_name(feature: &Feature) -> &'static str {
+	match feature {
+		Feature::Speech => "speech",
+		Feature::Braille => "braille",
+	}
+}
+
+/// What `ev` does, in a few words, like `next heading`.
+#[must_use]
+pub fn describe_event(ev: &OdiliaEvent) -> String {
+	match ev {
+		OdiliaEvent::StopSpeech(_) => "stop speech".to_string(),
+		OdiliaEvent::PauseSpeech(_) => "pause speech".to_string(),
+		OdiliaEvent::ResumeSpeech(_) => "resume speech".to_string(),
+		OdiliaEvent::Enable(Enable(feature)) => {
+			format!("turn on {}", feature_name(feature))
+		}
+		OdiliaEvent::Disable(Disable(feature)) => {
+			format!("turn off {}", feature_name(feature))
+		}
+		OdiliaEvent::ChangeMode(ChangeMode(mode)) => mode_name(Some(*mode)).to_string(),
+		OdiliaEvent::StructuralNavigation(nav) => {
+			format!("{} {}", towards(&nav.0, "next", "previous"), nav.1.name())
+		}
+		OdiliaEvent::AdjustRate(rate) => {
+			adjust(rate.0, "speak faster", "speak slower").to_string()
+		}
+		OdiliaEvent::AdjustPitch(pitch) => {
+			adjust(pitch.0, "raise pitch", "lower pitch").to_string()
+		}
+		OdiliaEvent::AdjustVolume(volume) => {
+			adjust(volume.0, "louder", "quieter").to_string()
+		}
+		OdiliaEvent::CycleVoice(voice) => {
+			format!("{} voice", towards(&voice.0, "next", "previous"))
+		}
+		OdiliaEvent::CycleOutputModule(module) => {
+			format!("{} synthesizer", towards(&module.0, "next", "previous"))
+		}
+		OdiliaEvent::CycleVerbosity(_) => "next verbosity level".to_string(),
+		OdiliaEvent::ReviewSpeechHistory(review) => {
+			format!("{} in speech history", towards(&review.0, "next", "previous"))
+		}
+		OdiliaEvent::RepeatLastSpeech(_) => "repeat last speech".to_string(),
+		OdiliaEvent::SpellSpeechHistory(_) => "spell speech history".to_string(),
+		OdiliaEvent::ReadCharacter(_) => "read character".to_string(),
+		OdiliaEvent::SpellWord(_) => "spell word".to_string(),
+		OdiliaEvent::SpellWordPhonetically(_) => "spell word phonetically".to_string(),
+		OdiliaEvent::SayAll(_) => "say all".to_string(),
+		OdiliaEvent::PanBraille(pan) => {
+			format!("pan braille {}", towards(&pan.0, "forward", "back"))
+		}
+		OdiliaEvent::RouteBraille(route) => format!("route to braille cell {}", route.0),
+		OdiliaEvent::PassNextKey(_) => "pass next key".to_string(),
+		OdiliaEvent::ToggleLearningMode(_) => "keyboard learning".to_string(),
+		OdiliaEvent::ListKeybindings(_) => "list keybindings".to_string(),
+		OdiliaEvent::Interrupt(_) => "interrupt".to_string(),
+		OdiliaEvent::Quit(_) => "quit Odilia".to_string(),
+	}
+}
+
+impl ComboSets {
+	/// Every combo and sequence which can be pressed in `mode`, with what it does, like
+	/// `CapsLock+H: next heading` when `activation` is `CapsLock`; those for every mode come
+	/// first.
+	#[must_use]
+	pub fn help(&self, mode: Mode, activation: Key) -> Vec<String> {
+		self.iter()
+			.filter(|(set_mode, _)| set_mode.is_none() || *set_mode == Some(mode))
+			.flat_map(|(_, combos)| combos.entries())
+			.map(|(strokes, ev)| {
+				let sequence = KeySequence::from(strokes.to_vec());
+				format!("{}: {}", sequence.combo(activation), describe_event(ev))
+			})
+			.collect()
+	}
+}
@@ -0,0 +1,556 @@
+//! Keybindings written by people: combos like `CapsLock+Shift+H`, read from Odilia's
+//! `[keybindings]` configuration and merged with the defaults.
+
+use std::{fmt, str::FromStr};
+
+use odilia_common::settings::KeybindingSettings;
+use rdev::Key;
+
+use crate::{
+	ComboError, ComboSet, ComboSets, KeySequence, KeySet, KeySetError, Mode, SetError,
+	ACTIVATION_KEY,
+};
+
+/// The names keys are written with in combos, matched ignoring case.
+/// Where a key has more than one name, the first is the one it is shown with.
+const KEY_NAMES: [(&str, Key); 122] = [
+	("CapsLock", Key::CapsLock),
+	("Shift", Key::ShiftLeft),
+	("RightShift", Key::ShiftRight),
+	("Ctrl", Key::ControlLeft),
+	("Control", Key::ControlLeft),
+	("RightCtrl", Key::ControlRight),
+	("RightControl", Key::ControlRight),
+	("Alt", Key::Alt),
+	("AltGr", Key::AltGr),
+	("Super", Key::MetaLeft),
+	("Meta", Key::MetaLeft),
+	("RightSuper", Key::MetaRight),
+	("RightMeta", Key::MetaRight),
+	("Fn", Key::Function),
+	("Backspace", Key::Backspace),
+	("Delete", Key::Delete),
+	("Insert", Key::Insert),
+	("Enter", Key::Return),
+	("Return", Key::Return),
+	("Escape", Key::Escape),
+	("Esc", Key::Escape),
+	("Space", Key::Space),
+	("Tab", Key::Tab),
+	("Up", Key::UpArrow),
+	("Down", Key::DownArrow),
+	("Left", Key::LeftArrow),
+	("Right", Key::RightArrow),
+	("Home", Key::Home),
+	("End", Key::End),
+	("PageUp", Key::PageUp),
+	("PageDown", Key::PageDown),
+	("PrintScreen", Key::PrintScreen),
+	("ScrollLock", Key::ScrollLock),
+	("Pause", Key::Pause),
+	("NumLock", Key::NumLock),
+	("F1", Key::F1),
+	("F2", Key::F2),
+	("F3", Key::F3),
+	("F4", Key::F4),
+	("F5", Key::F5),
+	("F6", Key::F6),
+	("F7", Key::F7),
+	("F8", Key::F8),
+	("F9", Key::F9),
+	("F10", Key::F10),
+	("F11", Key::F11),
+	("F12", Key::F12),
+	("A", Key::KeyA),
+	("B", Key::KeyB),
+	("C", Key::KeyC),
+	("D", Key::KeyD),
+	("E", Key::KeyE),
+	("F", Key::KeyF),
+	("G", Key::KeyG),
+	("H", Key::KeyH),
+	("I", Key::KeyI),
+	("J", Key::KeyJ),
+	("K", Key::KeyK),
+	("L", Key::KeyL),
+	("M", Key::KeyM),
+	("N", Key::KeyN),
+	("O", Key::KeyO),
+	("P", Key::KeyP),
+	("Q", Key::KeyQ),
+	("R", Key::KeyR),
+	("S", Key::KeyS),
+	("T", Key::KeyT),
+	("U", Key::KeyU),
+	("V", Key::KeyV),
+	("W", Key::KeyW),
+	("X", Key::KeyX),
+	("Y", Key::KeyY),
+	("Z", Key::KeyZ),
+	("1", Key::Num1),
+	("2", Key::Num2),
+	("3", Key::Num3),
+	("4", Key::Num4),
+	("5", Key::Num5),
+	("6", Key::Num6),
+	("7", Key::Num7),
+	("8", Key::Num8),
+	("9", Key::Num9),
+	("0", Key::Num0),
+	("`", Key::BackQuote),
+	("Grave", Key::BackQuote),
+	("-", Key::Minus),
+	("Minus", Key::Minus),
+	("=", Key::Equal),
+	("Equal", Key::Equal),
+	("[", Key::LeftBracket),
+	("LeftBracket", Key::LeftBracket),
+	("]", Key::RightBracket),
+	("RightBracket", Key::RightBracket),
+	(";", Key::SemiColon),
+	("Semicolon", Key::SemiColon),
+	("'", Key::Quote),
+	("Quote", Key::Quote),
+	("\\", Key::BackSlash),
+	("Backslash", Key::BackSlash),
+	("IntlBackslash", Key::IntlBackslash),
+	(",", Key::Comma),
+	("Comma", Key::Comma),
+	(".", Key::Dot),
+	("Period", Key::Dot),
+	("/", Key::Slash),
+	("Slash", Key::Slash),
+	("KpEnter", Key::KpReturn),
+	("KpMinus", Key::KpMinus),
+	("KpPlus", Key::KpPlus),
+	("KpMultiply", Key::KpMultiply),
+	("KpDivide", Key::KpDivide),
+	("KpDelete", Key::KpDelete),
+	("Kp0", Key::Kp0),
+	("Kp1", Key::Kp1),
+	("Kp2", Key::Kp2),
+	("Kp3", Key::Kp3),
+	("Kp4", Key::Kp4),
+	("Kp5", Key::Kp5),
+	("Kp6", Key::Kp6),
+	("Kp7", Key::Kp7),
+	("Kp8", Key::Kp8),
+	("Kp9", Key::Kp9),
+];
+
+/// The key called `name`, ignoring case.
+#[must_use]
+pub fn key_from_name(name: &str) -> Option<Key> {
+	KEY_NAMES
+		.iter()
+		.find(|(known, _)| known.eq_ignore_ascii_case(name))
+		.map(|(_, key)| *key)
+}
+
+/// The name `key` is written with in combos.
+#[must_use]
+pub fn key_name(key: Key) -> String {
+	KEY_NAMES.iter().find(|(_, known)| *known == key).map_or_else(
+		|| match key {
+			Key::Unknown(code) => format!("Unknown({code})"),
+			other => format!("{other:?}"),
+		},
+		|(name, _)| (*name).to_string(),
+	)
+}
+
+/// How a mode is named in errors and help.
+pub(crate) fn mode_name(mode: Option<Mode>) -> &'static str {
+	match mode {
+		None => "every mode",
+		Some(Mode::Focus) => "focus mode",
+		Some(Mode::Browse) => "browse mode",
+	}
+}
+
+impl KeySet {
+	/// Read a combo like `CapsLock+Shift+H`: key names joined with `+`, in the order they are
+	/// pressed.
+	/// Every combo is pressed while holding one of the `activation_keys`, so writing them is
+	/// optional; they are left out of the set.
+	///
+	/// # Errors
+	///
+	/// If a key has no name Odilia knows, or can not be added: see [`Self::insert`].
+	pub fn from_combo(combo: &str, activation_keys: &[Key]) -> Result<Self, KeySetError> {
+		let mut keys = KeySet::new();
+		for name in combo.split('+').map(str::trim) {
+			let key = key_from_name(name)
+				.ok_or_else(|| KeySetError::UnknownKey(name.to_string()))?;
+			if !activation_keys.contains(&key) {
+				keys.insert(key)?;
+			}
+		}
+		Ok(keys)
+	}
+	/// The combo as it is pressed while holding `activation`, like `CapsLock+Shift+H`.
+	///
+	/// ```
+	/// use rdev::Key;
+	/// use odilia_input_server_keyboard::KeySet;
+	/// let keys = KeySet::try_from([Key::ShiftLeft, Key::KeyH]).unwrap();
+	/// assert_eq!(keys.combo(Key::Insert), "Insert+Shift+H");
+	/// ```
+	#[must_use]
+	pub fn combo(&self, activation: Key) -> String {
+		format!("{}+{self}", key_name(activation))
+	}
+}
+
+impl FromStr for KeySet {
+	type Err = KeySetError;
+	/// Read a combo held with the default [`ACTIVATION_KEY`]; see [`KeySet::from_combo`].
+	///
+	/// ```
+	/// use rdev::Key;
+	/// use odilia_input_server_keyboard::KeySet;
+	/// let combo: KeySet = "CapsLock+Shift+H".parse().unwrap();
+	/// assert_eq!(combo, KeySet::try_from([Key::ShiftLeft, Key::KeyH]).unwrap());
+	/// assert_eq!(combo.to_string(), "Shift+H");
+	/// assert_eq!(combo.combo(Key::CapsLock), "CapsLock+Shift+H");
+	/// assert!("CapsLock+Hyper+H".parse::<KeySet>().is_err());
+	/// ```
+	fn from_str(combo: &str) -> Result<Self, Self::Err> {
+		KeySet::from_combo(combo, &[ACTIVATION_KEY])
+	}
+}
+
+/// Written without an activation key, which [`KeySet::from_combo`] accepts; use
+/// [`KeySet::combo`] to name the key that is held.
+impl fmt::Display for KeySet {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let mut keys = self.inner.iter();
+		if let Some(key) = keys.next() {
+			f.write_str(&key_name(*key))?;
+		}
+		for key in keys {
+			write!(f, "+{}", key_name(*key))?;
+		}
+		Ok(())
+	}
+}
+
+impl KeySequence {
+	/// Read a sequence like `CapsLock+Ctrl+T R`: combos separated by spaces, each read as in
+	/// [`KeySet::from_combo`]; a sequence of one combo is just that combo.
+	///
+	/// # Errors
+	///
+	/// If any of the combos can not be read.
+	pub fn from_combo(sequence: &str, activation_keys: &[Key]) -> Result<Self, KeySetError> {
+		// spaces around a `+` are part of a combo, not between two of them
+		let sequence = sequence.split('+').map(str::trim).collect::<Vec<_>>().join("+");
+		let strokes = sequence
+			.split_whitespace()
+			.map(|combo| KeySet::from_combo(combo, activation_keys))
+			.collect::<Result<Vec<_>, _>>()?;
+		Ok(strokes.into())
+	}
+	/// The sequence as it is pressed, with `activation` held for its first combo only, like
+	/// `CapsLock+Ctrl+T R`.
+	#[must_use]
+	pub fn combo(&self, activation: Key) -> String {
+		let Some((first, rest)) = self.strokes().split_first() else {
+			return key_name(activation);
+		};
+		std::iter::once(first.combo(activation))
+			.chain(rest.iter().map(ToString::to_string))
+			.collect::<Vec<_>>()
+			.join(" ")
+	}
+}
+
+impl FromStr for KeySequence {
+	type Err = KeySetError;
+	/// Read a sequence started with the default [`ACTIVATION_KEY`]; see
+	/// [`KeySequence::from_combo`].
+	///
+	/// ```
+	/// use rdev::Key;
+	/// use odilia_input_server_keyboard::{KeySequence, KeySet};
+	/// let sequence: KeySequence = "CapsLock+Ctrl+T R".parse().unwrap();
+	/// let ctrl_t = KeySet::try_from([Key::ControlLeft, Key::KeyT]).unwrap();
+	/// let r = KeySet::try_from([Key::KeyR]).unwrap();
+	/// assert_eq!(sequence, [ctrl_t, r].into());
+	/// assert_eq!(sequence.to_string(), "Ctrl+T R");
+	/// assert_eq!(sequence.combo(Key::CapsLock), "CapsLock+Ctrl+T R");
+	/// ```
+	fn from_str(sequence: &str) -> Result<Self, Self::Err> {
+		KeySequence::from_combo(sequence, &[ACTIVATION_KEY])
+	}
+}
+
+/// Written without an activation key, as for a [`KeySet`]; use [`KeySequence::combo`] to name
+/// the key held for the first combo.
+impl fmt::Display for KeySequence {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let strokes = self.strokes().iter().map(ToString::to_string).collect::<Vec<_>>();
+		f.write_str(&strokes.join(" "))
+	}
+}
+
+impl fmt::Display for KeySetError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			KeySetError::ActivationKey(key) => {
+				write!(
+					f,
+					"{} is held for every combo, and can not be part of one",
+					key_name(*key)
+				)
+			}
+			KeySetError::AlreadyContains(key) => {
+				write!(f, "{} is in it more than once", key_name(*key))
+			}
+			KeySetError::UnknownKey(name) => {
+				write!(f, "there is no key called \"{name}\"")
+			}
+		}
+	}
+}
+
+/// Write `keys` as pressed with `activation`, or without an activation key when there is none.
+pub(crate) fn combo_name(keys: &KeySet, activation: Option<Key>) -> String {
+	activation.map_or_else(|| keys.to_string(), |key| keys.combo(key))
+}
+
+/// Write `sequence` as pressed with `activation`, as in [`combo_name`].
+fn sequence_name(sequence: &KeySequence, activation: Option<Key>) -> String {
+	activation.map_or_else(|| sequence.to_string(), |key| sequence.combo(key))
+}
+
+impl ComboError {
+	/// What is wrong, naming the combos as they are pressed while holding `activation`.
+	#[must_use]
+	pub fn describe(&self, activation: Key) -> String {
+		self.message(Some(activation))
+	}
+	fn message(&self, activation: Option<Key>) -> String {
+		match self {
+			ComboError::SamePrefix { original, new } => format!(
+				"{} and {} start with the same keys, so one of them could never be pressed",
+				combo_name(new, activation),
+				combo_name(original, activation)
+			),
+			ComboError::Identical(keys) => {
+				format!("{} is bound more than once", combo_name(keys, activation))
+			}
+			ComboError::SequencePrefix { original, new } => format!(
+				"{} and {} start the same way, so one of them could never be pressed",
+				sequence_name(new, activation),
+				sequence_name(original, activation)
+			),
+		}
+	}
+}
+
+impl fmt::Display for ComboError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(&self.message(None))
+	}
+}
+
+impl SetError {
+	/// What is wrong, naming the combos as they are pressed while holding `activation`.
+	#[must_use]
+	pub fn describe(&self, activation: Key) -> String {
+		self.message(Some(activation))
+	}
+	fn message(&self, activation: Option<Key>) -> String {
+		match self {
+			SetError::IdenticalCombo { mode, set } => format!(
+				"{} is bound in {}, where it already has a binding",
+				combo_name(set, activation),
+				mode_name(*mode)
+			),
+			SetError::SamePrefixCombo { original, attempted } => format!(
+				"{} in {} and {} in {} start with the same keys, so one of them could never be pressed",
+				combo_name(&attempted.1, activation),
+				mode_name(attempted.0),
+				combo_name(&original.1, activation),
+				mode_name(original.0)
+			),
+			SetError::UnpressableKey => format!(
+				"a combo has no keys besides {}",
+				activation
+					.map_or_else(|| "the activation key".to_string(), key_name)
+			),
+			SetError::UnreachableMode(mode) => format!(
+				"no combo changes to {}, so its combos could never be pressed",
+				mode_name(Some(*mode))
+			),
+			SetError::SequencePrefixCombo { original, attempted } => format!(
+				"{} in {} and {} in {} start the same way, so one of them could never be pressed",
+				sequence_name(&attempted.1, activation),
+				mode_name(attempted.0),
+				sequence_name(&original.1, activation),
+				mode_name(original.0)
+			),
+		}
+	}
+}
+
+impl fmt::Display for SetError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(&self.message(None))
+	}
+}
+
+impl std::error::Error for KeySetError {}
+impl std::error::Error for ComboError {}
+impl std::error::Error for SetError {}
+
+/// An error in reading keybindings from the configuration.
+#[derive(Debug, PartialEq, Eq)]
+pub enum BindingError {
+	/// A combo could not be read.
+	Combo {
+		/// The combo, as it was written.
+		combo: String,
+		/// What is wrong with it.
+		error: KeySetError,
+	},
+	/// Two combos in the same mode conflict.
+	Conflict {
+		/// The mode both combos are in; `None` is every mode.
+		mode: Option<Mode>,
+		/// How they conflict.
+		error: ComboError,
+		/// The first activation key, to name the combos with.
+		activation: Key,
+	},
+	/// A combo conflicts with one in another mode, or its mode can not be reached.
+	Set {
+		/// How it conflicts, or which mode can not be reached.
+		error: SetError,
+		/// The first activation key, to name the combos with.
+		activation: Key,
+	},
+	/// An activation key has no name Odilia knows.
+	ActivationKey(String),
+	/// No activation keys were given, so no combo could be pressed.
+	NoActivationKeys,
+}
+
+impl fmt::Display for BindingError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			BindingError::Combo { combo, error } => {
+				write!(f, "can not read the combo \"{combo}\": {error}")
+			}
+			BindingError::Conflict { mode, error, activation } => {
+				write!(
+					f,
+					"in {}, {}",
+					mode_name(*mode),
+					error.describe(*activation)
+				)
+			}
+			BindingError::Set { error, activation } => {
+				f.write_str(&error.describe(*activation))
+			}
+			BindingError::ActivationKey(name) => {
+				write!(f, "there is no key called \"{name}\" to use as an activation key")
+			}
+			BindingError::NoActivationKeys => {
+				write!(f, "there are no activation keys, so no combo could be pressed")
+			}
+		}
+	}
+}
+
+impl std::error::Error for BindingError {}
+
+/// The keys held down for combos, from their names in `settings`.
+///
+/// # Errors
+///
+/// If a key has no name Odilia knows, or there are none.
+pub fn activation_keys(settings: &KeybindingSettings) -> Result<Vec<Key>, BindingError> {
+	let keys = settings
+		.activation_keys
+		.iter()
+		.map(|name| {
+			key_from_name(name).ok_or_else(|| BindingError::ActivationKey(name.clone()))
+		})
+		.collect::<Result<Vec<_>, _>>()?;
+	if keys.is_empty() {
+		return Err(BindingError::NoActivationKeys);
+	}
+	Ok(keys)
+}
+
+impl ComboSets {
+	/// The default combos, with the combos and sequences in `settings` added to them.
+	/// A combo or sequence with the same keys as a default one in the same mode replaces it.
+	///
+	/// # Errors
+	///
+	/// If a combo or activation key can not be read, or a combo conflicts with another: see
+	/// [`ComboSet::insert_sequence`] and [`ComboSets::insert`].
+	pub fn with_overrides(settings: &KeybindingSettings) -> Result<Self, BindingError> {
+		let activation_keys = activation_keys(settings)?;
+		let mut merged = ComboSets::default()
+			.into_iter()
+			.map(|(mode, combos)| {
+				let sequences = combos
+					.inner
+					.into_iter()
+					.map(|(keys, ev)| (KeySequence::from(keys), ev))
+					.chain(combos.sequences)
+					.collect::<Vec<_>>();
+				(mode, sequences)
+			})
+			.collect::<Vec<_>>();
+		for (mode, bindings) in [
+			(None, &settings.global),
+			(Some(Mode::Focus), &settings.focus),
+			(Some(Mode::Browse), &settings.browse),
+		] {
+			// sorted, so that errors are the same from one run to the next
+			let mut bindings = bindings.iter().collect::<Vec<_>>();
+			bindings.sort_by(|a, b| a.0.cmp(b.0));
+			let overrides = bindings
+				.into_iter()
+				.map(|(combo, event)| {
+					let keys = KeySequence::from_combo(combo, &activation_keys)
+						.map_err(|error| BindingError::Combo {
+							combo: combo.clone(),
+							error,
+						})?;
+					Ok((keys, event.clone()))
+				})
+				.collect::<Result<Vec<_>, BindingError>>()?;
+			if overrides.is_empty() {
+				continue;
+			}
+			let index = merged
+				.iter()
+				.position(|(existing, _)| *existing == mode)
+				.unwrap_or_else(|| {
+					merged.push((mode, Vec::new()));
+					merged.len() - 1
+				});
+			let combos = &mut merged[index].1;
+			combos.retain(|(keys, _)| !overrides.iter().any(|(new, _)| new == keys));
+			combos.extend(overrides);
+		}
+		// errors name combos by the first activation key, which is the one held by default
+		let activation = activation_keys[0];
+		let mut sets = ComboSets::new();
+		for (mode, sequences) in merged {
+			let combos =
+				ComboSet::new().with_sequences(sequences).map_err(|error| {
+					BindingError::Conflict { mode, error, activation }
+				})?;
+			sets.insert(mode, combos)
+				.map_err(|error| BindingError::Set { error, activation })?;
+		}
+		Ok(sets)
+	}
+}
@@ -22,21 +22,44 @@ mod tests;
 #[cfg(all(test, feature = "proptest"))]
 mod proptests;
 
-use std::{cmp::Ordering, sync::mpsc::SyncSender};
+mod help;
+mod keybindings;
+
+use std::{
+	cmp::Ordering,
+	sync::mpsc::{Receiver, SyncSender},
+	time::{Duration, SystemTime},
+};
 
 use atspi::Role;
+pub use help::describe_event;
+pub use keybindings::{activation_keys, key_from_name, key_name, BindingError};
 use odilia_common::{
 	events::{
-		ChangeMode, Direction, Quit, ScreenReaderEvent as OdiliaEvent, StopSpeech,
-		StructuralNavigation,
+		AdjustPitch, AdjustRate, AdjustVolume, Adjustment, CapsLockToggled, ChangeMode,
+		CycleOutputModule, CycleVerbosity, CycleVoice, Direction, InputFeedback, Interrupt,
+		KeyDescribed, KeySequenceCancelled, KeySequenceStarted, KeybindingsListed,
+		LearningModeToggled, ListKeybindings, PassNextKey, PauseSpeech, Quit,
+		ReadCharacter, RepeatLastSpeech, ResumeSpeech, ReviewSpeechHistory, SayAll,
+		ScreenReaderEvent as OdiliaEvent, SpellSpeechHistory, SpellWord,
+		SpellWordPhonetically, StopSpeech, StructuralNavigation, ToggleLearningMode,
 	},
 	modes::ScreenReaderMode as Mode,
+	protocol::Update,
 };
 use rdev::{Event, EventType, Key};
 
-/// The fixed activation key for all keybindings.
+/// The default activation key for all keybindings.
+/// Other keys can be used instead (see [`State::activation_keys`]), but this one can never be part
+/// of a combo.
 pub const ACTIVATION_KEY: Key = Key::CapsLock;
 
+/// How quickly an activation key has to be pressed again to pass it through, by default.
+pub const DOUBLE_TAP: Duration = Duration::from_millis(400);
+
+/// How long to wait for the next combo of a [`KeySequence`], by default.
+pub const SEQUENCE_TIMEOUT: Duration = Duration::from_millis(1500);
+
 /// A set of keys to be used as the combination for a binding.
 #[derive(Eq, PartialEq, Clone, Default)]
 #[repr(transparent)]
@@ -159,10 +182,12 @@ fn val_key(k1: &Key) -> u64 {
 /// An error in creating (or modifying) a [`KeySet`].
 #[derive(Debug, PartialEq, Eq)]
 pub enum KeySetError {
-	/// Attempted to add the [`ACTIVATION_KEY`].
-	ActivationKey,
+	/// Attempted to add an activation key, like the [`ACTIVATION_KEY`].
+	ActivationKey(rdev::Key),
 	/// Attempted to add a key twice.
 	AlreadyContains(rdev::Key),
+	/// A combo named a key which does not exist.
+	UnknownKey(String),
 }
 
 impl PartialOrd for KeySet {
@@ -204,7 +229,7 @@ impl KeySet {
 	/// ```
 	pub fn insert(&mut self, key: Key) -> Result<(), KeySetError> {
 		if key == ACTIVATION_KEY {
-			Err(KeySetError::ActivationKey)
+			Err(KeySetError::ActivationKey(key))
 		} else if self.inner.contains(&key) {
 			Err(KeySetError::AlreadyContains(key))
 		} else {
@@ -266,6 +291,55 @@ impl IntoIterator for KeySet {
 	}
 }
 
+/// A sequence of key combos, pressed one after the other, like `CapsLock+Ctrl+T` then `R`.
+/// The activation key is only held for the first one.
+#[derive(Eq, PartialEq, Clone, Default)]
+#[repr(transparent)]
+pub struct KeySequence {
+	strokes: Vec<KeySet>,
+}
+impl std::fmt::Debug for KeySequence {
+	fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+		self.strokes.fmt(fmt)
+	}
+}
+impl From<KeySet> for KeySequence {
+	fn from(keys: KeySet) -> Self {
+		KeySequence { strokes: vec![keys] }
+	}
+}
+impl From<Vec<KeySet>> for KeySequence {
+	fn from(strokes: Vec<KeySet>) -> Self {
+		KeySequence { strokes }
+	}
+}
+impl<const N: usize> From<[KeySet; N]> for KeySequence {
+	fn from(strokes: [KeySet; N]) -> Self {
+		KeySequence { strokes: strokes.into() }
+	}
+}
+impl KeySequence {
+	/// The combos to press, in order.
+	#[must_use]
+	pub fn strokes(&self) -> &[KeySet] {
+		&self.strokes
+	}
+}
+
+/// Whether pressing one of `a` and `b` would get in the way of pressing the other.
+/// The strokes are compared in order: the sequences are told apart at the first which differ,
+/// unless one of those strokes starts with the other; if one runs out first, it is pressed before
+/// the other can be.
+fn sequences_conflict(a: &[KeySet], b: &[KeySet]) -> bool {
+	for (stroke_a, stroke_b) in a.iter().zip(b) {
+		if stroke_a != stroke_b {
+			return stroke_a.inner.starts_with(&stroke_b.inner)
+				|| stroke_b.inner.starts_with(&stroke_a.inner);
+		}
+	}
+	true
+}
+
 /// An error in creating a set of key combos.
 #[derive(Debug, PartialEq, Eq)]
 pub enum ComboError {
@@ -278,17 +352,29 @@ pub enum ComboError {
 	},
 	/// An existing combo has the same set of keys assigned to it.
 	Identical(KeySet),
+	/// An existing combo or sequence is pressed the same way as the start of a new sequence, or
+	/// the other way around; this includes identical sequences.
+	SequencePrefix {
+		/// Existing combo or sequence which conflicts.
+		original: KeySequence,
+		/// The combo or sequence which was attempted to be added, but failed.
+		new: KeySequence,
+	},
 }
 
 /// A set of key combos and their associated action.
 #[derive(Clone, Eq, PartialEq, Default)]
-#[repr(transparent)]
 pub struct ComboSet {
 	inner: Vec<(KeySet, OdiliaEvent)>,
+	/// Sequences of more than one combo.
+	sequences: Vec<(KeySequence, OdiliaEvent)>,
 }
 impl std::fmt::Debug for ComboSet {
 	fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
-		self.inner.fmt(fmt)
+		fmt.debug_list()
+			.entries(&self.inner)
+			.entries(&self.sequences)
+			.finish()
 	}
 }
 impl TryFrom<Vec<(KeySet, OdiliaEvent)>> for ComboSet {
@@ -379,13 +465,89 @@ impl ComboSet {
 				});
 			}
 		}
+		for (sequence, _) in &self.sequences {
+			if sequences_conflict(&sequence.strokes, std::slice::from_ref(&keys)) {
+				return Err(ComboError::SequencePrefix {
+					original: sequence.clone(),
+					new: keys.into(),
+				});
+			}
+		}
 		self.inner.push((keys, ev));
 		Ok(())
 	}
+	/// Insert a new [`KeySequence`], [`OdiliaEvent`] combination.
+	/// A sequence of one combo is the same as that combo on its own: see [`Self::insert`].
+	///
+	/// # Errors
+	///
+	/// If pressing the sequence would get in the way of pressing an existing combo or sequence,
+	/// or the other way around: they are identical, one is pressed the same way as the start of
+	/// the other, or they differ at a combo which starts with the same keys as the other.
+	///
+	/// ```
+	/// use rdev::Key;
+	/// use odilia_input_server_keyboard::{ComboSet, KeySet, KeySequence};
+	/// use odilia_common::events::{SayAll, StopSpeech};
+	/// let t: KeySet = [Key::KeyT].try_into().unwrap();
+	/// let r: KeySet = [Key::KeyR].try_into().unwrap();
+	/// let mut cs = ComboSet::new();
+	/// assert!(cs.insert_sequence([t.clone(), r.clone()].into(), SayAll.into()).is_ok());
+	/// // T is the start of the sequence, so it can not be bound on its own
+	/// assert!(cs.insert(t.clone(), StopSpeech.into()).is_err());
+	/// assert!(cs.insert_sequence([t, r.clone(), r].into(), StopSpeech.into()).is_err());
+	/// ```
+	pub fn insert_sequence(
+		&mut self,
+		sequence: KeySequence,
+		ev: OdiliaEvent,
+	) -> Result<(), ComboError> {
+		if sequence.strokes.len() <= 1 {
+			let keys = sequence.strokes.into_iter().next().unwrap_or_default();
+			return self.insert(keys, ev);
+		}
+		let existing = self
+			.inner
+			.iter()
+			.map(|(keys, _)| KeySequence::from(keys.clone()))
+			.chain(self.sequences.iter().map(|(existing, _)| existing.clone()));
+		for original in existing {
+			if sequences_conflict(&original.strokes, &sequence.strokes) {
+				return Err(ComboError::SequencePrefix { original, new: sequence });
+			}
+		}
+		self.sequences.push((sequence, ev));
+		Ok(())
+	}
+	/// Add each of `sequences`, as with [`Self::insert_sequence`].
+	///
+	/// # Errors
+	///
+	/// See [`Self::insert_sequence`].
+	pub fn with_sequences<I>(mut self, sequences: I) -> Result<Self, ComboError>
+	where
+		I: IntoIterator<Item = (KeySequence, OdiliaEvent)>,
+	{
+		sequences
+			.into_iter()
+			.try_for_each(|(sequence, ev)| self.insert_sequence(sequence, ev))?;
+		Ok(self)
+	}
+	/// [`Iterator`] through each [`KeySequence`] of more than one combo, and its event.
+	pub fn sequences(&self) -> impl Iterator<Item = &'_ (KeySequence, OdiliaEvent)> {
+		self.sequences.iter()
+	}
+	/// Every combo and sequence, and its event; combos are sequences of one.
+	fn entries(&self) -> impl Iterator<Item = (&'_ [KeySet], &'_ OdiliaEvent)> {
+		self.inner
+			.iter()
+			.map(|(keys, ev)| (std::slice::from_ref(keys), ev))
+			.chain(self.sequences.iter().map(|(sequence, ev)| (sequence.strokes(), ev)))
+	}
 	/// Create a new, empty [`ComboSet`].
 	#[must_use]
 	pub fn new() -> Self {
-		Self { inner: Vec::new() }
+		Self { inner: Vec::new(), sequences: Vec::new() }
 	}
 	/// Create a [`ComboSet`] from an iterator.
 	/// # Errors
@@ -445,6 +607,16 @@ pub enum SetError {
 		/// The attempted combo to add.
 		attempted: (Option<Mode>, KeySet),
 	},
+	/// A sequence with the same start as a combo or sequence has already been set, or the other
+	/// way around.
+	/// This happens if either the two have the same mode, or the mode of the original is `None`
+	/// (global).
+	SequencePrefixCombo {
+		/// The mode and combo or sequence which conflicts.
+		original: (Option<Mode>, KeySequence),
+		/// The attempted combo or sequence to add.
+		attempted: (Option<Mode>, KeySequence),
+	},
 	/// Attempted to add a combo with an empty set of keys.
 	UnpressableKey,
 	/// Attempted to add a keybinding with a mode that is not accessible via pressing other keys.
@@ -521,9 +693,9 @@ impl ComboSets {
 		if let Some(some_mode) = mode {
 			if !self.inner
 				.iter()
-				.flat_map(|x| x.1.inner.iter())
+				.flat_map(|x| x.1.entries())
 				.filter_map(|ev| match ev.1 {
-					OdiliaEvent::ChangeMode(ChangeMode(mode)) => Some(mode),
+					OdiliaEvent::ChangeMode(ChangeMode(mode)) => Some(*mode),
 					_ => None,
 				})
 				.any(|m| m == some_mode)
@@ -531,10 +703,9 @@ impl ComboSets {
 				return Err(SetError::UnreachableMode(some_mode));
 			}
 		}
-		if cs.inner
-			.iter()
-			.map(|set| set.0.inner.iter())
-			.any(|combo| combo.count() == 0)
+		if cs.entries()
+			.flat_map(|(strokes, _)| strokes)
+			.any(|combo| combo.inner.is_empty())
 		{
 			return Err(SetError::UnpressableKey);
 		}
@@ -565,6 +736,29 @@ impl ComboSets {
 						}
 					}
 				}
+				for (original, _) in combo_sets.entries() {
+					for (attempted, _) in cs.entries() {
+						if (original.len() > 1 || attempted.len() > 1)
+							&& sequences_conflict(original, attempted)
+						{
+							return Err(
+								SetError::SequencePrefixCombo {
+									original: (
+										*combo_mode,
+										original.to_vec()
+											.into(),
+									),
+									attempted: (
+										mode,
+										attempted
+											.to_vec()
+											.into(),
+									),
+								},
+							);
+						}
+					}
+				}
 			}
 		}
 		self.inner.push((mode, cs));
@@ -606,6 +800,8 @@ impl IntoIterator for ComboSets {
 }
 
 impl Default for ComboSets {
+	// a table of every default combo; splitting it up would only make it harder to read
+	#[allow(clippy::too_many_lines)]
 	fn default() -> Self {
 		ComboSets::try_from([
 			(
@@ -616,6 +812,81 @@ impl Default for ComboSets {
 						ChangeMode(Mode::Focus).into(),
 					),
 					([Key::KeyG].try_into().unwrap(), StopSpeech.into()),
+					([Key::KeyP].try_into().unwrap(), PauseSpeech.into()),
+					(
+						[Key::ShiftLeft, Key::KeyP].try_into().unwrap(),
+						ResumeSpeech.into(),
+					),
+					(
+						[Key::Equal].try_into().unwrap(),
+						AdjustRate(Adjustment::Increase).into(),
+					),
+					(
+						[Key::Minus].try_into().unwrap(),
+						AdjustRate(Adjustment::Decrease).into(),
+					),
+					(
+						[Key::ShiftLeft, Key::Equal].try_into().unwrap(),
+						AdjustPitch(Adjustment::Increase).into(),
+					),
+					(
+						[Key::ShiftLeft, Key::Minus].try_into().unwrap(),
+						AdjustPitch(Adjustment::Decrease).into(),
+					),
+					(
+						[Key::ControlLeft, Key::Equal].try_into().unwrap(),
+						AdjustVolume(Adjustment::Increase).into(),
+					),
+					(
+						[Key::ControlLeft, Key::Minus].try_into().unwrap(),
+						AdjustVolume(Adjustment::Decrease).into(),
+					),
+					(
+						[Key::KeyV].try_into().unwrap(),
+						CycleVoice(Direction::Forward).into(),
+					),
+					(
+						[Key::ShiftLeft, Key::KeyV].try_into().unwrap(),
+						CycleVoice(Direction::Backward).into(),
+					),
+					(
+						[Key::KeyM].try_into().unwrap(),
+						CycleOutputModule(Direction::Forward).into(),
+					),
+					(
+						[Key::ShiftLeft, Key::KeyM].try_into().unwrap(),
+						CycleOutputModule(Direction::Backward).into(),
+					),
+					(
+						[Key::ControlLeft, Key::KeyV].try_into().unwrap(),
+						CycleVerbosity.into(),
+					),
+					(
+						[Key::LeftBracket].try_into().unwrap(),
+						ReviewSpeechHistory(Direction::Backward).into(),
+					),
+					(
+						[Key::RightBracket].try_into().unwrap(),
+						ReviewSpeechHistory(Direction::Forward).into(),
+					),
+					([Key::KeyR].try_into().unwrap(), RepeatLastSpeech.into()),
+					(
+						[Key::ShiftLeft, Key::KeyR].try_into().unwrap(),
+						SpellSpeechHistory.into(),
+					),
+					([Key::KeyC].try_into().unwrap(), ReadCharacter.into()),
+					([Key::KeyW].try_into().unwrap(), SpellWord.into()),
+					(
+						[Key::ShiftLeft, Key::KeyW].try_into().unwrap(),
+						SpellWordPhonetically.into(),
+					),
+					([Key::KeyA].try_into().unwrap(), SayAll.into()),
+					([Key::F2].try_into().unwrap(), PassNextKey.into()),
+					(
+						[Key::Num1].try_into().unwrap(),
+						ToggleLearningMode.into(),
+					),
+					([Key::F1].try_into().unwrap(), ListKeybindings.into()),
 					(
 						[Key::KeyB].try_into().unwrap(),
 						ChangeMode(Mode::Browse).into(),
@@ -695,6 +966,40 @@ impl Default for ComboSets {
 						.into(),
 					),
 				])
+				.and_then(|set| {
+					set.with_sequences([
+						(
+							[
+								[Key::ControlLeft, Key::KeyT]
+									.try_into()
+									.unwrap(),
+								[Key::KeyR].try_into().unwrap(),
+							]
+							.into(),
+							StructuralNavigation(
+								Direction::Forward,
+								Role::TableRow,
+							)
+							.into(),
+						),
+						(
+							[
+								[Key::ControlLeft, Key::KeyT]
+									.try_into()
+									.unwrap(),
+								[Key::ShiftLeft, Key::KeyR]
+									.try_into()
+									.unwrap(),
+							]
+							.into(),
+							StructuralNavigation(
+								Direction::Backward,
+								Role::TableRow,
+							)
+							.into(),
+						),
+					])
+				})
 				.unwrap(),
 			),
 		])
@@ -718,10 +1023,32 @@ impl<'a> IntoIterator for &'a ComboSets {
 }
 
 /// The primary holder of state for all keybindings in the daemon.
+// each flag is independent of the others, so they do not make sense as one enum
+#[allow(clippy::struct_excessive_bools)]
 #[derive(Debug)]
 pub struct State {
-	/// If the activation key ([`crate::ACTIVATION_KEY`]) is pressed.
+	/// If an activation key is pressed.
 	pub activation_key_pressed: bool,
+	/// The keys held down for combos; [`crate::ACTIVATION_KEY`] by default.
+	pub activation_keys: Vec<Key>,
+	/// Two presses of the same activation key this close together pass the second one through,
+	/// so that caps lock can still be toggled.
+	pub double_tap: Duration,
+	/// The activation key last pressed, and when, if no other key has been pressed since.
+	pub last_tap: Option<(Key, SystemTime)>,
+	/// If a [`PassNextKey`] combo was pressed, and the next combo should go to the application.
+	pub pass_next: bool,
+	/// The keys held in the combo being passed to the application untouched.
+	pub passing: Vec<Key>,
+	/// The [`KeySequence`] which has been started, if any.
+	pub pending: Option<Pending>,
+	/// If keyboard learning is on: keys pressed are described, instead of doing anything.
+	pub learning: bool,
+	/// If Odilia is reading on its own, like in a say all, which keys meant for the application
+	/// interrupt.
+	pub reading: bool,
+	/// How long to wait for the next combo of a [`KeySequence`] before giving up on it.
+	pub sequence_timeout: Duration,
 	/// Which mode the screen reader is in.
 	pub mode: Mode,
 	/// All pressed keys _after_ activation is pressed.
@@ -731,6 +1058,283 @@ pub struct State {
 	/// A synchronous channel to send events to.
 	/// The receiver will send them over a socket to the main Odilia process.
 	pub tx: SyncSender<OdiliaEvent>,
+	/// A synchronous channel for what the input server has to say about itself, like a key
+	/// described in learning mode; it is sent to Odilia the same way, to be announced.
+	pub feedback: SyncSender<InputFeedback>,
+	/// Changes Odilia has sent over the socket, like a new mode; they are applied before the next
+	/// key is handled.
+	pub updates: Receiver<Update>,
+}
+
+/// A [`KeySequence`] which has been started, but not finished.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pending {
+	/// The combos of the sequence pressed so far.
+	pub strokes: Vec<KeySet>,
+	/// The keys of the next combo pressed so far.
+	pub keys: Vec<Key>,
+	/// When the last combo was pressed.
+	pub at: SystemTime,
+}
+
+impl State {
+	/// Every combo and sequence which can be pressed in the current mode, and its event.
+	fn bindings(&self) -> impl Iterator<Item = (&'_ [KeySet], &'_ OdiliaEvent)> {
+		self.combos
+			.iter()
+			.filter(|(mode, _)| mode.is_none() || *mode == Some(self.mode))
+			.flat_map(|(_, combos)| combos.entries())
+	}
+	/// Send `ev` to Odilia, after acting on the events which change how keys are handled.
+	fn trigger(&mut self, ev: OdiliaEvent) {
+		tracing::debug!("Combo found for {ev:?}");
+		match ev {
+			// if it's a change mode event, update the mode
+			OdiliaEvent::ChangeMode(ChangeMode(new_mode)) => self.mode = new_mode,
+			// the next combo, once this one is let go of, is not for Odilia
+			OdiliaEvent::PassNextKey(_) => self.pass_next = true,
+			// only the input server knows whether learning is on, and what the combos are
+			OdiliaEvent::ToggleLearningMode(_) => {
+				self.learning = !self.learning;
+				return self.announce(LearningModeToggled(self.learning));
+			}
+			OdiliaEvent::ListKeybindings(_) => {
+				let help = self.combos.help(self.mode, self.activation_key());
+				return self.announce(KeybindingsListed(help));
+			}
+			_ => {}
+		}
+		self.tx.send(ev)
+			.expect("To be able to send the combo over the channel");
+	}
+	/// The activation key combos are announced with: the first of the
+	/// [`State::activation_keys`].
+	fn activation_key(&self) -> Key {
+		self.activation_keys.first().copied().unwrap_or(ACTIVATION_KEY)
+	}
+	/// Have Odilia announce `feedback`; this must never block typing, so if the channel is full,
+	/// it is dropped.
+	fn announce(&self, feedback: impl Into<InputFeedback>) {
+		let feedback = feedback.into();
+		if self.feedback.try_send(feedback).is_err() {
+			tracing::debug!("Could not send feedback; the channel is full");
+		}
+	}
+	/// Apply every [`Update`] Odilia has sent since the last key.
+	fn apply_updates(&mut self) {
+		while let Ok(update) = self.updates.try_recv() {
+			tracing::debug!("Applying {update:?}");
+			match update {
+				Update::Mode(mode) => self.mode = mode,
+				// no combos depend on which features are on
+				Update::Features(_) => {}
+				Update::Reading(reading) => self.reading = reading,
+				Update::Keybindings(settings) => {
+					let loaded = ComboSets::with_overrides(&settings).and_then(
+						|combos| Ok((combos, activation_keys(&settings)?)),
+					);
+					match loaded {
+						Ok((combos, activation_keys)) => {
+							self.combos = combos;
+							self.activation_keys = activation_keys;
+							self.double_tap = Duration::from_millis(
+								settings.double_tap_ms,
+							);
+							self.sequence_timeout = Duration::from_millis(
+								settings.sequence_timeout_ms,
+							);
+							self.pending = None;
+						}
+						Err(error) => {
+							tracing::error!(%error, "Invalid keybindings; keeping the current ones");
+						}
+					}
+				}
+			}
+		}
+	}
+	/// If pressing `key` at `time` is the second press of a double tap.
+	fn is_double_tap(&self, key: Key, time: SystemTime) -> bool {
+		self.last_tap.is_some_and(|(last, at)| {
+			last == key
+				&& time.duration_since(at)
+					.is_ok_and(|since| since < self.double_tap)
+		})
+	}
+}
+
+/// Whether `event` is part of the combo after a [`PassNextKey`], which goes to the application
+/// untouched, activation keys and all.
+/// Passing starts with the first key pressed once the keys of the previous combo are let go of,
+/// and ends when all the keys pressed since are let go of.
+fn pass_through(event: &Event, state: &mut State) -> bool {
+	match event.event_type {
+		EventType::KeyPress(key)
+			if !state.passing.is_empty()
+				|| (state.pass_next
+					&& state.pressed.is_empty() && !state
+					.activation_key_pressed) =>
+		{
+			state.pass_next = false;
+			if !state.passing.contains(&key) {
+				state.passing.push(key);
+			}
+			true
+		}
+		EventType::KeyRelease(key) if !state.passing.is_empty() => {
+			state.passing.retain(|held| *held != key);
+			true
+		}
+		_ => false,
+	}
+}
+
+/// In keyboard learning mode, describe the keys held, and what they are bound to, instead of
+/// doing it.
+/// Returns false if `event` is not for learning mode, and should be handled as usual: activation
+/// keys, and the combo which turns learning off, still work.
+fn learn(event: &Event, state: &mut State) -> bool {
+	if !state.learning {
+		return false;
+	}
+	match event.event_type {
+		EventType::KeyPress(key) if !state.activation_keys.contains(&key) => {
+			// held down
+			if state.pressed.contains(&key) {
+				return true;
+			}
+			state.pressed.push(key);
+			let keys = KeySet { inner: state.pressed.clone() };
+			let activation =
+				state.activation_key_pressed.then(|| state.activation_key());
+			let bound = state
+				.bindings()
+				.find(|(strokes, _)| activation.is_some() && strokes[0] == keys)
+				.map(|(strokes, ev)| (strokes.len(), ev.clone()));
+			let description = match bound {
+				Some((1, ev @ OdiliaEvent::ToggleLearningMode(_))) => {
+					state.trigger(ev);
+					return true;
+				}
+				Some((1, ev)) => format!(
+					"{}: {}",
+					keybindings::combo_name(&keys, activation),
+					describe_event(&ev)
+				),
+				Some(_) => format!(
+					"{}: start a key sequence",
+					keybindings::combo_name(&keys, activation)
+				),
+				None => keybindings::combo_name(&keys, activation),
+			};
+			state.announce(KeyDescribed(description));
+			true
+		}
+		EventType::KeyRelease(key) if state.pressed.contains(&key) => {
+			state.pressed.retain(|held| *held != key);
+			true
+		}
+		_ => false,
+	}
+}
+
+/// Handle a key being pressed while a [`KeySequence`] is pending, which swallows it if it is part
+/// of the next combo of the sequence.
+/// Returns false if it is not, or the sequence was not finished in time; the sequence is then
+/// abandoned (see [`abandon_sequence`]), and the key is handled as usual.
+fn continue_sequence(event: &Event, state: &mut State) -> bool {
+	let EventType::KeyPress(key) = event.event_type else {
+		return false;
+	};
+	if state.activation_keys.contains(&key) {
+		return false;
+	}
+	let Some(mut pending) = state.pending.take() else {
+		return false;
+	};
+	let time = event.time;
+	if time.duration_since(pending.at)
+		.map_or(true, |since| since >= state.sequence_timeout)
+	{
+		tracing::trace!("Sequence timed out");
+		abandon_sequence(&pending, state);
+		return false;
+	}
+	// held down since it was pressed for the sequence
+	if state.pressed.contains(&key) {
+		state.pending = Some(pending);
+		return true;
+	}
+	pending.keys.push(key);
+	let done = pending.strokes.len();
+	let mut finished = None;
+	let mut stroke_finished = false;
+	let mut continues = false;
+	for (strokes, ev) in state.bindings() {
+		if strokes.len() <= done || strokes[..done] != pending.strokes[..] {
+			continue;
+		}
+		let next = &strokes[done].inner;
+		if *next == pending.keys && strokes.len() == done + 1 {
+			finished = Some(ev.clone());
+		} else if *next == pending.keys {
+			stroke_finished = true;
+		} else if next.starts_with(&pending.keys) {
+			continues = true;
+		}
+	}
+	if finished.is_none() && !stroke_finished && !continues {
+		tracing::trace!("{key:?} is not part of the sequence");
+		pending.keys.pop();
+		abandon_sequence(&pending, state);
+		return false;
+	}
+	// its release is swallowed too
+	state.pressed.push(key);
+	if let Some(ev) = finished {
+		state.trigger(ev);
+		return true;
+	}
+	if stroke_finished {
+		pending.strokes
+			.push(KeySet { inner: std::mem::take(&mut pending.keys) });
+		pending.at = time;
+	}
+	state.pending = Some(pending);
+	true
+}
+
+/// Start waiting for the rest of the sequence whose first combo is held, pressed at `time`, and
+/// let Odilia know.
+fn start_sequence(time: SystemTime, state: &mut State) {
+	let first = KeySet { inner: state.pressed.clone() };
+	tracing::debug!("Sequence started with {first:?}");
+	let combo = first.combo(state.activation_key());
+	state.announce(KeySequenceStarted(combo));
+	state.pending = Some(Pending { strokes: vec![first], keys: Vec::new(), at: time });
+}
+
+/// Give up on the `pending` sequence, and let Odilia know.
+/// The keys of its unfinished combo were swallowed when pressed, but may have been meant for the
+/// application, like a shift before a capital letter; they are no longer tracked, so their
+/// releases go through rather than being swallowed as well.
+fn abandon_sequence(pending: &Pending, state: &mut State) {
+	state.pressed.retain(|key| !pending.keys.contains(key));
+	state.announce(KeySequenceCancelled);
+}
+
+/// Whether caps lock is on, from the keyboard LEDs the kernel exposes; `None` if there are none.
+fn caps_lock_on() -> Option<bool> {
+	let mut on = None;
+	for led in std::fs::read_dir("/sys/class/leds").ok()?.flatten() {
+		if !led.file_name().to_string_lossy().ends_with("::capslock") {
+			continue;
+		}
+		if let Ok(brightness) = std::fs::read_to_string(led.path().join("brightness")) {
+			on = Some(on.unwrap_or(false) || brightness.trim() != "0");
+		}
+	}
+	on
 }
 
 /// The callback function to call in a tight loop.
@@ -742,30 +1346,52 @@ pub struct State {
 /// If the [`State`]'s [`SyncSender`] for the [`OdiliaEvent`] is unable to be sent to.
 pub fn callback(event: Event, state: &mut State) -> Option<Event> {
 	tracing::debug!("Callback called for {event:?}");
+	state.apply_updates();
+	if pass_through(&event, state) {
+		tracing::trace!("Passing {event:?} through");
+		return Some(event);
+	}
+	if learn(&event, state) || continue_sequence(&event, state) {
+		return None;
+	}
 	match (event.event_type, state.activation_key_pressed) {
-		// if capslock is pressed while activation is disabled
-		(EventType::KeyPress(ACTIVATION_KEY), false) => {
+		// if an activation key is pressed while activation is disabled
+		(EventType::KeyPress(key), false) if state.activation_keys.contains(&key) => {
+			// pressed twice in quick succession: let the second press through, so that caps lock
+			// can still be toggled
+			if state.is_double_tap(key, event.time) {
+				state.last_tap = None;
+				tracing::trace!("Double tap; passing {key:?} through");
+				if key == Key::CapsLock {
+					// the toggle has not happened yet, so the LEDs still show the old state
+					let on = caps_lock_on().map(|on| !on);
+					state.announce(CapsLockToggled(on));
+				}
+				return Some(event);
+			}
 			// enable it
 			state.activation_key_pressed = true;
+			state.last_tap = Some((key, event.time));
 			tracing::trace!("Activation enabled!");
 			// swallow the event
 			None
 		}
-		// if capslock is released while activation is disabled (happens if capslock was pressed before
-		// start, but released after the daemon began intercepting keys)
-		(EventType::KeyRelease(ACTIVATION_KEY), false) => {
+		// if an activation key is released while activation is disabled (happens if it was
+		// pressed before start, but released after the daemon began intercepting keys, or if it
+		// was passed through by a double tap)
+		(EventType::KeyRelease(key), false) if state.activation_keys.contains(&key) => {
 			// passthrough the event; if you don't, the application the user was focused on will act like
 			// capslock is perpetually held
 			Some(event)
 		}
-		// if capslock is pressed while activation is enabled (usually the result of holding down
-		// the key)
-		(EventType::KeyPress(ACTIVATION_KEY), true) => {
+		// if an activation key is pressed while activation is enabled (usually the result of
+		// holding down the key)
+		(EventType::KeyPress(key), true) if state.activation_keys.contains(&key) => {
 			// swallow the event
 			None
 		}
-		// if capslock is released while activate is enabled
-		(EventType::KeyRelease(ACTIVATION_KEY), true) => {
+		// if an activation key is released while activate is enabled
+		(EventType::KeyRelease(key), true) if state.activation_keys.contains(&key) => {
 			// disable activate state
 			state.activation_key_pressed = false;
 			tracing::trace!("Activation disabled!");
@@ -774,6 +1400,8 @@ pub fn callback(event: Event, state: &mut State) -> Option<Event> {
 		}
 		// if a key press is made while activation is enabled
 		(EventType::KeyPress(other), true) => {
+			// a combo is being pressed; the next activation key press is not a double tap
+			state.last_tap = None;
 			// if the key is already pressed (i.e., it's been held down)
 			let None = state.pressed.iter().position(|key| *key == other) else {
 				// swallow the event immediately, do not pass through
@@ -781,36 +1409,45 @@ pub fn callback(event: Event, state: &mut State) -> Option<Event> {
 			};
 			// otherwise, add it to the list of held keys
 			state.pressed.push(other);
-			// look in the combos
-			for (mode, combos) in &state.combos {
-				if *mode != Some(state.mode) && mode.is_some() {
-					continue;
-				}
-				for combo in combos {
-					// if a combo matches the held keys (must be in right order)
-					if combo.0 == *state.pressed {
-						// print out the command
-						tracing::debug!("Combo found for {:?}", combo.1);
-						// if it's a change mode event, update the mode
-						if let OdiliaEvent::ChangeMode(ChangeMode(
-							new_mode,
-						)) = combo.1
-						{
-							state.mode = new_mode;
-						}
-						state.tx.send(combo.1.clone()).expect(
-                "To be able to send the combo over the channel",
-              );
-						// exit early; found combo!
-						return None;
-					}
-				}
+			// look in the combos; a combo must match the held keys in the right order
+			let found = state
+				.bindings()
+				.find(|(strokes, _)| {
+					strokes.len() == 1 && strokes[0].inner == state.pressed
+				})
+				.map(|(_, ev)| ev.clone());
+			if let Some(ev) = found {
+				state.trigger(ev);
+				// exit early; found combo!
+				return None;
+			}
+			// or it may be the first combo of a sequence, which waits for the rest
+			if state.bindings().any(|(strokes, _)| {
+				strokes.len() > 1 && strokes[0].inner == state.pressed
+			}) {
+				start_sequence(event.time, state);
 			}
 			// swallow the event
 			None
 		}
+		// if a key press is made while activation is disabled, it is meant for the application
+		(EventType::KeyPress(_), false) => {
+			state.last_tap = None;
+			// let Odilia know, so it can stop reading a whole document; only while it is, so that
+			// typing does not use up the events Odilia lets through. This must never block typing,
+			// so if the channel is full, the interruption is dropped
+			if state.reading && state.tx.try_send(Interrupt.into()).is_err() {
+				tracing::debug!(
+					"Could not send an interruption; the channel is full"
+				);
+			}
+			Some(event)
+		}
 		// if a key release is made while activation mode is on
 		(EventType::KeyRelease(other), _) => {
+			if let Some(pending) = &mut state.pending {
+				pending.keys.retain(|key| *key != other);
+			}
 			// if it's previously been pressed
 			if let Some(idx) = state.pressed.iter().position(|key| *key == other) {
 				// remove it from the list of held keys
@@ -1,6 +1,6 @@
 use std::{
 	sync::mpsc::{Receiver, TryRecvError},
-	time::SystemTime,
+	time::{Duration, SystemTime},
 };
 
 use atspi::Role;
@@ -9,8 +9,8 @@ use proptest::prelude::*;
 use rdev::{Button, Event, EventType, Key};
 
 use crate::{
-	callback, tests::EventFromEventType, ComboSet, ComboSets, KeySet, Mode, OdiliaEvent, State,
-	ACTIVATION_KEY,
+	callback, tests::EventFromEventType, ComboSet, ComboSets, KeySequence, KeySet, Mode,
+	OdiliaEvent, State, ACTIVATION_KEY,
 };
 
 impl ComboSets {
@@ -373,6 +373,10 @@ fn direction() -> impl Strategy<Value = Direction> {
 	prop_oneof![Just(Direction::Forward), Just(Direction::Backward),]
 }
 
+fn adjustment() -> impl Strategy<Value = Adjustment> {
+	prop_oneof![Just(Adjustment::Increase), Just(Adjustment::Decrease),]
+}
+
 fn feature() -> impl Strategy<Value = Feature> {
 	prop_oneof![Just(Feature::Speech), Just(Feature::Braille),]
 }
@@ -380,12 +384,31 @@ fn feature() -> impl Strategy<Value = Feature> {
 fn odilia_event() -> impl Strategy<Value = OdiliaEvent> {
 	prop_oneof![
 		Just(OdiliaEvent::StopSpeech(StopSpeech)),
+		Just(OdiliaEvent::PauseSpeech(PauseSpeech)),
+		Just(OdiliaEvent::ResumeSpeech(ResumeSpeech)),
 		feature().prop_map(|feat| OdiliaEvent::Enable(Enable(feat))),
 		feature().prop_map(|feat| OdiliaEvent::Disable(Disable(feat))),
 		mode().prop_map(|mode| OdiliaEvent::ChangeMode(ChangeMode(mode))),
 		(direction(), role()).prop_map(|(dir, rle)| OdiliaEvent::StructuralNavigation(
 			StructuralNavigation(dir, rle)
 		)),
+		adjustment().prop_map(|adj| OdiliaEvent::AdjustRate(AdjustRate(adj))),
+		adjustment().prop_map(|adj| OdiliaEvent::AdjustPitch(AdjustPitch(adj))),
+		adjustment().prop_map(|adj| OdiliaEvent::AdjustVolume(AdjustVolume(adj))),
+		direction().prop_map(|dir| OdiliaEvent::CycleVoice(CycleVoice(dir))),
+		direction().prop_map(|dir| OdiliaEvent::CycleOutputModule(CycleOutputModule(dir))),
+		Just(OdiliaEvent::CycleVerbosity(CycleVerbosity)),
+		direction()
+			.prop_map(|dir| OdiliaEvent::ReviewSpeechHistory(ReviewSpeechHistory(dir))),
+		Just(OdiliaEvent::RepeatLastSpeech(RepeatLastSpeech)),
+		Just(OdiliaEvent::SpellSpeechHistory(SpellSpeechHistory)),
+		Just(OdiliaEvent::ReadCharacter(ReadCharacter)),
+		Just(OdiliaEvent::SpellWord(SpellWord)),
+		Just(OdiliaEvent::SpellWordPhonetically(SpellWordPhonetically)),
+		Just(OdiliaEvent::SayAll(SayAll)),
+		direction().prop_map(|dir| OdiliaEvent::PanBraille(PanBraille(dir))),
+		(0..80usize).prop_map(|cell| OdiliaEvent::RouteBraille(RouteBraille(cell))),
+		Just(OdiliaEvent::Interrupt(Interrupt)),
 	]
 }
 
@@ -393,9 +416,27 @@ fn combo() -> impl Strategy<Value = (KeySet, OdiliaEvent)> {
 	(prop::collection::vec(key(), 1..20).prop_map(KeySet::from_dedup), odilia_event())
 }
 
+fn sequence() -> impl Strategy<Value = (KeySequence, OdiliaEvent)> {
+	(
+		prop::collection::vec(
+			prop::collection::vec(key(), 1..5).prop_map(KeySet::from_dedup),
+			2..4,
+		)
+		.prop_map(KeySequence::from),
+		odilia_event(),
+	)
+}
+
 fn combo_set() -> impl Strategy<Value = ComboSet> {
-	prop::collection::vec(combo(), 1..20)
-		.prop_map(|v| ComboSet::from_iter_ignore_errors(v.into_iter()))
+	(prop::collection::vec(combo(), 1..20), prop::collection::vec(sequence(), 0..5)).prop_map(
+		|(combos, sequences)| {
+			let mut set = ComboSet::from_iter_ignore_errors(combos.into_iter());
+			for (sequence, ev) in sequences {
+				let _ = set.insert_sequence(sequence, ev);
+			}
+			set
+		},
+	)
 }
 
 fn combo_sets() -> impl Strategy<Value = ComboSets> {
@@ -405,17 +446,20 @@ fn combo_sets() -> impl Strategy<Value = ComboSets> {
 
 prop_compose! {
     fn state()
-	(cmbs in combo_sets()) -> (State, Receiver<OdiliaEvent>) {
-	let (mut state, rx) = State::new_unbounded();
+	(cmbs in combo_sets()) -> (State, Receiver<OdiliaEvent>, Receiver<InputFeedback>) {
+	let (mut state, rx, feedback) = State::new_unbounded();
 	state.combos = cmbs;
-	(state, rx)
+	// events are generated back to back, so any two presses of CapsLock would be a double tap;
+	// double taps are tested on their own in `tests.rs`
+	state.double_tap = Duration::ZERO;
+	(state, rx, feedback)
     }
 }
 
 proptest! {
     #[test]
     fn test_all_keybindings_capture(
-	(mut state, rx) in state(),
+	(mut state, rx, _feedback) in state(),
     ) {
 	let combo_sets = state.combos.clone();
 	let caps_press = Event::from_event_type(EventType::KeyPress(ACTIVATION_KEY));
@@ -439,9 +483,40 @@ proptest! {
 	}
     }
     #[test]
+    fn test_all_sequences_trigger(
+	(mut state, rx, feedback) in state(),
+    ) {
+	let combo_sets = state.combos.clone();
+	let caps_press = Event::from_event_type(EventType::KeyPress(ACTIVATION_KEY));
+	callback(caps_press, &mut state);
+	for (mode, combos) in combo_sets {
+	    for (sequence, odilia_trigger) in combos.sequences() {
+	    // directly set mode required to trigger the given sequence
+	    if let Some(mode) = mode {
+		state.mode = mode;
+	    }
+		for (i, stroke) in sequence.strokes().iter().enumerate() {
+		    for key in stroke.clone() {
+			assert_eq!(rx.try_recv(), Err(TryRecvError::Empty), "An OdiliaCommand was sent before a full sequence has been pressed!");
+			callback(Event::from_event_type(EventType::KeyPress(key)), &mut state);
+		    }
+		    if i == 0 {
+			assert_eq!(feedback.try_recv(), Ok(KeySequenceStarted(stroke.combo(ACTIVATION_KEY)).into()), "The start of the sequence was not announced!");
+		    }
+		    for key in stroke.clone() {
+			callback(Event::from_event_type(EventType::KeyRelease(key)), &mut state);
+		    }
+		}
+		assert_eq!(rx.try_recv(), Ok(odilia_trigger.clone()), "All combos of the sequence were pressed to produce an event; either the wrong event was sent, or there was some error sending it!");
+		assert_eq!(state.pressed.len(), 0, "Pressed keys is not 0-length after releasing them!");
+		assert!(state.pending.is_none(), "The sequence is still pending after it was finished!");
+	    }
+	}
+    }
+    #[test]
     fn all_release_all_passthrough(
 	events in events_all_release(),
-	(mut state, _rx) in state(),
+	(mut state, _rx, _feedback) in state(),
     ) {
 	for event in events {
 	    let ev1 = event.clone();
@@ -452,15 +527,26 @@ proptest! {
     #[test]
     fn all_unused_keys_are_passed_through_and_capslock_always_consumed(
 	(events, _size) in events(),
-	(mut state, _rx) in state(),
+	(mut state, _rx, _feedback) in state(),
     ) {
 	let mut caps_held = false;
 	let all_grabbable_keys: Vec<Key> = state.combos.inner.iter()
-	    .flat_map(|combos| combos.1.keys().map(|combo_set| combo_set.inner.clone()))
-	    .flatten()
+	    .flat_map(|combos| combos.1.entries())
+	    .flat_map(|(strokes, _)| strokes)
+	    .flat_map(|stroke| stroke.inner.clone())
 	    .collect();
 	for event in events {
 	    let ev1 = event.clone();
+	    // after a `PassNextKey` combo, the next combo goes through untouched, CapsLock included
+	    let passing = !state.passing.is_empty()
+		|| (state.pass_next
+		    && state.pressed.is_empty()
+		    && !state.activation_key_pressed
+		    && matches!(ev1.event_type, EventType::KeyPress(_)));
+	    if passing {
+		assert_eq!(callback(event, &mut state), Some(ev1), "The combo after PassNextKey was not passed through!");
+		continue;
+	    }
 	    match ev1.event_type {
 		EventType::KeyPress(ACTIVATION_KEY) => {
 		    caps_held = true;
@@ -490,7 +576,7 @@ proptest! {
     #[test]
     fn new_event_is_not_constructed(
 	(events, _size) in events(),
-	(mut state, _rx) in state(),
+	(mut state, _rx, _feedback) in state(),
     ) {
 	for event in events {
 	    let ev1 = event.clone();
@@ -500,10 +586,34 @@ proptest! {
 	}
     }
 
+    #[test]
+    fn pass_next_key_passes_the_next_combo(
+	keys in prop::collection::vec(key(), 1..10),
+	(mut state, rx, _feedback) in state(),
+    ) {
+	let mut combo: Vec<Key> = Vec::new();
+	for key in keys {
+	    if !combo.contains(&key) {
+		combo.push(key);
+	    }
+	}
+	state.pass_next = true;
+	for key in &combo {
+	    let press = Event::from_event_type(EventType::KeyPress(*key));
+	    assert_eq!(callback(press.clone(), &mut state), Some(press), "A key of the combo after PassNextKey was captured!");
+	}
+	for key in combo.iter().rev() {
+	    let release = Event::from_event_type(EventType::KeyRelease(*key));
+	    assert_eq!(callback(release.clone(), &mut state), Some(release), "A key of the combo after PassNextKey was captured!");
+	}
+	assert_eq!(rx.try_recv(), Err(TryRecvError::Empty), "The combo after PassNextKey was sent to Odilia!");
+	assert!(!state.pass_next && state.passing.is_empty(), "Passing through did not end with the combo!");
+    }
+
 		#[test]
 		fn doesnt_panic(
 	(events, _size) in events(),
-	(mut state, _rx) in state(),
+	(mut state, _rx, _feedback) in state(),
     ) {
 			for ev in events {
 				callback(ev, &mut state);
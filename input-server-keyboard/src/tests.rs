@@ -1,10 +1,17 @@
-use std::sync::mpsc::{sync_channel, Receiver};
+use std::{
+	collections::HashMap,
+	sync::mpsc::{channel, sync_channel, Receiver},
+	time::{Duration, SystemTime},
+};
 
-use odilia_common::events::*;
+use atspi::Role;
+use odilia_common::{events::*, protocol::Update, settings::KeybindingSettings};
 use rdev::{Event, EventType, Key};
 
 use crate::{
-	callback, ComboError, ComboSet, ComboSets, KeySet, Mode, OdiliaEvent, SetError, State,
+	activation_keys, callback, BindingError, ComboError, ComboSet, ComboSets, KeySequence,
+	KeySet, KeySetError, Mode, OdiliaEvent, SetError, State, ACTIVATION_KEY, DOUBLE_TAP,
+	SEQUENCE_TIMEOUT,
 };
 
 pub(crate) trait EventFromEventType {
@@ -16,20 +23,33 @@ pub(crate) trait EventFromEventType {
 impl EventFromEventType for Event {}
 
 impl State {
-	/// For testing purposes only: create an "unbounded" (100,000-sized) buffer for accepting the
-	/// `OdiliaEvents` that may be triggered.
-	pub(crate) fn new_unbounded() -> (Self, Receiver<OdiliaEvent>) {
+	/// For testing purposes only: create "unbounded" (100,000-sized) buffers for accepting the
+	/// `OdiliaEvents` and `InputFeedback` that may be triggered.
+	pub(crate) fn new_unbounded() -> (Self, Receiver<OdiliaEvent>, Receiver<InputFeedback>) {
 		let (tx, rx) = sync_channel(100_000);
+		let (feedback_tx, feedback) = sync_channel(100_000);
 		(
 			Self {
 				activation_key_pressed: false,
+				activation_keys: vec![ACTIVATION_KEY],
+				double_tap: DOUBLE_TAP,
+				last_tap: None,
+				pass_next: false,
+				passing: Vec::new(),
+				pending: None,
+				learning: false,
+				reading: false,
+				sequence_timeout: SEQUENCE_TIMEOUT,
 				mode: Mode::Focus,
 				// handle up to 10 key presses without allocation
 				pressed: Vec::with_capacity(10),
 				combos: ComboSets::new(),
 				tx,
+				feedback: feedback_tx,
+				updates: channel().1,
 			},
 			rx,
+			feedback,
 		)
 	}
 }
@@ -117,7 +137,7 @@ fn forever_repeating_key_problem() {
 	let core_combos =
 		ComboSet::try_from(vec![(g, StopSpeech.into())]).expect("Valid comboset!");
 	let cs = ComboSets::try_from([(None, core_combos)]).expect("Valid combosets!");
-	let (mut state, _rx) = State::new_unbounded();
+	let (mut state, _rx, _feedback) = State::new_unbounded();
 	state.combos = cs;
 	for (i, (ev, correct)) in
 		events.into_iter().zip(correct_return_values.into_iter()).enumerate()
@@ -131,3 +151,548 @@ fn forever_repeating_key_problem() {
 fn default_combosets_no_panic() {
 	let _ = ComboSets::default();
 }
+
+#[test]
+fn passthrough_key_press_interrupts_reading() {
+	let (mut state, rx, _feedback) = State::new_unbounded();
+	let (updates, receiver) = channel();
+	state.updates = receiver;
+	let press_g = Event::from_event_type(EventType::KeyPress(Key::KeyG));
+	let release_g = Event::from_event_type(EventType::KeyRelease(Key::KeyG));
+	// nothing is being read, so there is nothing to interrupt
+	assert_eq!(callback(press_g.clone(), &mut state), Some(press_g.clone()));
+	assert_eq!(callback(release_g.clone(), &mut state), Some(release_g.clone()));
+	assert!(rx.try_recv().is_err());
+	updates.send(Update::Reading(true)).expect("Sent");
+	assert_eq!(callback(press_g.clone(), &mut state), Some(press_g));
+	assert_eq!(callback(release_g.clone(), &mut state), Some(release_g));
+	assert_eq!(rx.try_recv(), Ok(Interrupt.into()));
+	// only the press interrupts
+	assert!(rx.try_recv().is_err());
+}
+
+/// The event bound to `combo` in exactly `mode`, if there is one.
+fn bound(sets: &ComboSets, mode: Option<Mode>, combo: &str) -> Option<OdiliaEvent> {
+	let keys: KeySet = combo.parse().expect("Valid combo!");
+	sets.iter()
+		.filter(|(set_mode, _)| *set_mode == mode)
+		.flat_map(|(_, combos)| combos)
+		.find(|(set, _)| *set == keys)
+		.map(|(_, ev)| ev.clone())
+}
+
+#[test]
+fn parse_combos() {
+	let combo: KeySet = "CapsLock+Shift+H".parse().unwrap();
+	assert_eq!(combo, KeySet::try_from([Key::ShiftLeft, Key::KeyH]).unwrap());
+	// the activation key is implied, and names are not case sensitive
+	assert_eq!("shift + h".parse::<KeySet>(), Ok(combo.clone()));
+	assert_eq!(combo.to_string(), "Shift+H");
+	assert_eq!(combo.combo(ACTIVATION_KEY), "CapsLock+Shift+H");
+	assert_eq!("CapsLock+[".parse::<KeySet>().unwrap().combo(Key::Insert), "Insert+[");
+	assert_eq!(
+		"CapsLock+Hyper+H".parse::<KeySet>(),
+		Err(KeySetError::UnknownKey("Hyper".to_string()))
+	);
+	assert_eq!("H+h".parse::<KeySet>(), Err(KeySetError::AlreadyContains(Key::KeyH)));
+}
+
+#[test]
+fn keybindings_merge_with_defaults() {
+	let settings = KeybindingSettings {
+		global: HashMap::from([
+			("CapsLock+G".to_string(), Quit.into()),
+			("CapsLock+Ctrl+Q".to_string(), Quit.into()),
+		]),
+		focus: HashMap::from([("CapsLock+Ctrl+H".to_string(), SayAll.into())]),
+		..KeybindingSettings::default()
+	};
+	let sets = ComboSets::with_overrides(&settings).expect("Valid keybindings!");
+	assert_eq!(bound(&sets, None, "CapsLock+G"), Some(Quit.into()));
+	assert_eq!(bound(&sets, None, "CapsLock+Ctrl+Q"), Some(Quit.into()));
+	assert_eq!(bound(&sets, Some(Mode::Focus), "CapsLock+Ctrl+H"), Some(SayAll.into()));
+	// the rest of the defaults are kept
+	assert_eq!(bound(&sets, None, "CapsLock+P"), Some(PauseSpeech.into()));
+	assert_eq!(
+		ComboSets::with_overrides(&KeybindingSettings::default()),
+		Ok(ComboSets::default())
+	);
+}
+
+#[test]
+fn keybinding_errors_name_the_combos() {
+	let settings = KeybindingSettings {
+		global: HashMap::from([("CapsLock+Shift".to_string(), SayAll.into())]),
+		..KeybindingSettings::default()
+	};
+	let error = ComboSets::with_overrides(&settings).unwrap_err();
+	assert!(matches!(
+		error,
+		BindingError::Conflict { mode: None, error: ComboError::SamePrefix { .. }, .. }
+	));
+	assert_eq!(
+		error.to_string(),
+		"in every mode, CapsLock+Shift and CapsLock+Shift+P start with the same keys, so one of them could never be pressed"
+	);
+	let settings = KeybindingSettings {
+		browse: HashMap::from([("CapsLock+G".to_string(), SayAll.into())]),
+		..KeybindingSettings::default()
+	};
+	assert_eq!(
+		ComboSets::with_overrides(&settings).unwrap_err().to_string(),
+		"CapsLock+G is bound in browse mode, where it already has a binding"
+	);
+	let settings = KeybindingSettings {
+		focus: HashMap::from([("CapsLock+Hyper".to_string(), SayAll.into())]),
+		..KeybindingSettings::default()
+	};
+	assert_eq!(
+		ComboSets::with_overrides(&settings).unwrap_err().to_string(),
+		"can not read the combo \"CapsLock+Hyper\": there is no key called \"Hyper\""
+	);
+	// combos are named by the configured activation key
+	let settings = KeybindingSettings {
+		activation_keys: vec!["Insert".to_string(), "CapsLock".to_string()],
+		browse: HashMap::from([("Insert+G".to_string(), SayAll.into())]),
+		..KeybindingSettings::default()
+	};
+	assert_eq!(
+		ComboSets::with_overrides(&settings).unwrap_err().to_string(),
+		"Insert+G is bound in browse mode, where it already has a binding"
+	);
+}
+
+/// `event_type`, happening `millis` milliseconds after `start`.
+fn event_at(event_type: EventType, start: SystemTime, millis: u64) -> Event {
+	Event { event_type, time: start + Duration::from_millis(millis), name: None }
+}
+
+#[test]
+fn double_tap_passes_caps_lock_through() {
+	let (mut state, _rx, feedback) = State::new_unbounded();
+	let start = SystemTime::now();
+	let press = |millis| event_at(EventType::KeyPress(Key::CapsLock), start, millis);
+	let release = |millis| event_at(EventType::KeyRelease(Key::CapsLock), start, millis);
+	assert_eq!(callback(press(0), &mut state), None);
+	assert_eq!(callback(release(50), &mut state), None);
+	assert_eq!(callback(press(150), &mut state), Some(press(150)));
+	assert!(!state.activation_key_pressed);
+	assert_eq!(callback(release(200), &mut state), Some(release(200)));
+	assert!(matches!(feedback.try_recv(), Ok(InputFeedback::CapsLockToggled(_))));
+	// a third press starts over, rather than being another double tap
+	assert_eq!(callback(press(300), &mut state), None);
+	assert!(state.activation_key_pressed);
+}
+
+#[test]
+fn slow_or_interrupted_taps_are_not_double_taps() {
+	let (mut state, rx, _feedback) = State::new_unbounded();
+	let start = SystemTime::now();
+	let press = |millis| event_at(EventType::KeyPress(Key::CapsLock), start, millis);
+	let release = |millis| event_at(EventType::KeyRelease(Key::CapsLock), start, millis);
+	let slow = u64::try_from(DOUBLE_TAP.as_millis()).unwrap() + 100;
+	assert_eq!(callback(press(0), &mut state), None);
+	assert_eq!(callback(release(50), &mut state), None);
+	assert_eq!(callback(press(slow), &mut state), None);
+	// a combo pressed in between
+	assert_eq!(
+		callback(event_at(EventType::KeyPress(Key::KeyG), start, slow + 10), &mut state),
+		None
+	);
+	assert_eq!(callback(release(slow + 20), &mut state), None);
+	assert_eq!(callback(press(slow + 50), &mut state), None);
+	assert!(rx.try_recv().is_err());
+}
+
+#[test]
+fn other_activation_keys() {
+	let settings = KeybindingSettings {
+		activation_keys: vec!["Insert".to_string(), "CapsLock".to_string()],
+		global: HashMap::from([("Insert+Ctrl+Q".to_string(), Quit.into())]),
+		..KeybindingSettings::default()
+	};
+	let keys = activation_keys(&settings).expect("Known keys!");
+	assert_eq!(keys, [Key::Insert, Key::CapsLock]);
+	let (mut state, rx, _feedback) = State::new_unbounded();
+	state.combos = ComboSets::with_overrides(&settings).expect("Valid keybindings!");
+	state.activation_keys = keys;
+	let insert = Event::from_event_type(EventType::KeyPress(Key::Insert));
+	assert_eq!(callback(insert, &mut state), None);
+	for key in [Key::ControlLeft, Key::KeyQ] {
+		assert_eq!(
+			callback(Event::from_event_type(EventType::KeyPress(key)), &mut state),
+			None
+		);
+	}
+	assert_eq!(rx.try_recv(), Ok(Quit.into()));
+	let settings = KeybindingSettings {
+		activation_keys: vec!["Hyper".to_string()],
+		..KeybindingSettings::default()
+	};
+	assert_eq!(
+		activation_keys(&settings),
+		Err(BindingError::ActivationKey("Hyper".to_string()))
+	);
+	let settings =
+		KeybindingSettings { activation_keys: Vec::new(), ..KeybindingSettings::default() };
+	assert_eq!(ComboSets::with_overrides(&settings), Err(BindingError::NoActivationKeys));
+}
+
+#[test]
+fn pass_next_key_passes_one_combo() {
+	let key = |event_type| Event::from_event_type(event_type);
+	let press = |k| key(EventType::KeyPress(k));
+	let release = |k| key(EventType::KeyRelease(k));
+	let core_combos = ComboSet::try_from(vec![
+		(vec![Key::KeyG].try_into().unwrap(), StopSpeech.into()),
+		(vec![Key::KeyX].try_into().unwrap(), PassNextKey.into()),
+	])
+	.expect("Valid comboset!");
+	let (mut state, rx, _feedback) = State::new_unbounded();
+	state.combos = ComboSets::try_from([(None, core_combos)]).expect("Valid combosets!");
+	// the combo for passing the next key is swallowed, as usual
+	for event in [press(Key::CapsLock), press(Key::KeyX), release(Key::KeyX)] {
+		assert_eq!(callback(event, &mut state), None);
+	}
+	assert_eq!(rx.try_recv(), Ok(PassNextKey.into()));
+	assert!(state.pass_next);
+	assert_eq!(callback(release(Key::CapsLock), &mut state), None);
+	// the next one, activation key and all, goes to the application
+	for event in
+		[press(Key::CapsLock), press(Key::KeyG), release(Key::CapsLock), release(Key::KeyG)]
+	{
+		assert_eq!(callback(event.clone(), &mut state), Some(event));
+	}
+	assert!(!state.pass_next);
+	assert!(rx.try_recv().is_err());
+	// and the one after that is Odilia's again
+	assert_eq!(callback(press(Key::CapsLock), &mut state), None);
+	assert_eq!(callback(press(Key::KeyG), &mut state), None);
+	assert_eq!(rx.try_recv(), Ok(StopSpeech.into()));
+}
+
+#[test]
+fn pass_next_key_waits_for_the_combo_to_be_let_go() {
+	let press = |k| Event::from_event_type(EventType::KeyPress(k));
+	let core_combos =
+		ComboSet::try_from(vec![(vec![Key::KeyX].try_into().unwrap(), PassNextKey.into())])
+			.expect("Valid comboset!");
+	let (mut state, _rx, _feedback) = State::new_unbounded();
+	state.combos = ComboSets::try_from([(None, core_combos)]).expect("Valid combosets!");
+	assert_eq!(callback(press(Key::CapsLock), &mut state), None);
+	assert_eq!(callback(press(Key::KeyX), &mut state), None);
+	// still holding the activation key; this is not the next combo yet
+	assert_eq!(callback(press(Key::KeyG), &mut state), None);
+	assert!(state.pass_next);
+}
+
+#[test]
+fn parse_sequences() {
+	let sequence: KeySequence = "CapsLock+Ctrl+T R".parse().unwrap();
+	let ctrl_t = KeySet::try_from([Key::ControlLeft, Key::KeyT]).unwrap();
+	let r = KeySet::try_from([Key::KeyR]).unwrap();
+	assert_eq!(sequence, KeySequence::from([ctrl_t.clone(), r]));
+	// spaces around a `+` do not split the combo
+	assert_eq!("CapsLock + Ctrl + T   r".parse::<KeySequence>(), Ok(sequence.clone()));
+	assert_eq!(sequence.to_string(), "Ctrl+T R");
+	assert_eq!(sequence.combo(Key::Insert), "Insert+Ctrl+T R");
+	assert_eq!("CapsLock+Ctrl+T".parse::<KeySequence>(), Ok(ctrl_t.into()));
+	assert_eq!(
+		"CapsLock+Ctrl+T Hyper".parse::<KeySequence>(),
+		Err(KeySetError::UnknownKey("Hyper".to_string()))
+	);
+}
+
+#[test]
+fn sequences_fire_after_their_last_combo() {
+	let (mut state, rx, feedback) = State::new_unbounded();
+	state.combos = ComboSets::default();
+	state.mode = Mode::Browse;
+	let start = SystemTime::now();
+	let press = |key, millis| event_at(EventType::KeyPress(key), start, millis);
+	let release = |key, millis| event_at(EventType::KeyRelease(key), start, millis);
+	for event in [
+		press(Key::CapsLock, 0),
+		press(Key::ControlLeft, 10),
+		press(Key::KeyT, 20),
+		release(Key::KeyT, 30),
+		release(Key::ControlLeft, 40),
+		release(Key::CapsLock, 50),
+	] {
+		assert_eq!(callback(event, &mut state), None);
+	}
+	assert_eq!(
+		feedback.try_recv(),
+		Ok(KeySequenceStarted("CapsLock+Ctrl+T".to_string()).into())
+	);
+	assert!(state.pending.is_some());
+	// the rest is pressed without the activation key
+	assert_eq!(callback(press(Key::ShiftLeft, 500), &mut state), None);
+	assert_eq!(callback(press(Key::KeyR, 510), &mut state), None);
+	assert_eq!(
+		rx.try_recv(),
+		Ok(StructuralNavigation(Direction::Backward, Role::TableRow).into())
+	);
+	assert_eq!(callback(release(Key::KeyR, 520), &mut state), None);
+	assert_eq!(callback(release(Key::ShiftLeft, 530), &mut state), None);
+	assert!(state.pending.is_none());
+	assert!(state.pressed.is_empty());
+	assert!(rx.try_recv().is_err());
+}
+
+#[test]
+fn unfinished_sequences_are_abandoned() {
+	let (mut state, rx, feedback) = State::new_unbounded();
+	state.combos = ComboSets::default();
+	state.mode = Mode::Browse;
+	let start = SystemTime::now();
+	let press = |key, millis| event_at(EventType::KeyPress(key), start, millis);
+	let release = |key, millis| event_at(EventType::KeyRelease(key), start, millis);
+	let start_sequence = |state: &mut State, at| {
+		for event in [
+			press(Key::CapsLock, at),
+			press(Key::ControlLeft, at + 10),
+			press(Key::KeyT, at + 20),
+			release(Key::KeyT, at + 30),
+			release(Key::ControlLeft, at + 40),
+			release(Key::CapsLock, at + 50),
+		] {
+			assert_eq!(callback(event, state), None);
+		}
+	};
+	// too slowly
+	start_sequence(&mut state, 0);
+	let late = u64::try_from(SEQUENCE_TIMEOUT.as_millis()).unwrap() + 100;
+	assert_eq!(callback(press(Key::KeyR, late), &mut state), Some(press(Key::KeyR, late)));
+	assert!(state.pending.is_none());
+	assert_eq!(
+		callback(release(Key::KeyR, late + 10), &mut state),
+		Some(release(Key::KeyR, late + 10))
+	);
+	// with a key which is not part of any sequence, which goes to the application
+	start_sequence(&mut state, 10_000);
+	assert_eq!(callback(press(Key::KeyQ, 10_100), &mut state), Some(press(Key::KeyQ, 10_100)));
+	assert!(state.pending.is_none());
+	let sent = rx.try_iter().collect::<Vec<_>>();
+	assert!(!sent
+		.iter()
+		.any(|ev| matches!(ev, OdiliaEvent::StructuralNavigation(_))));
+	// both times, the cancellation is announced
+	assert_eq!(
+		feedback.try_iter()
+			.filter(|feedback| matches!(
+				feedback,
+				InputFeedback::KeySequenceCancelled(_)
+			))
+			.count(),
+		2
+	);
+}
+
+#[test]
+fn partial_combo_then_unrelated_key() {
+	let (mut state, rx, feedback) = State::new_unbounded();
+	state.combos = ComboSets::default();
+	state.mode = Mode::Browse;
+	let start = SystemTime::now();
+	let press = |key, millis| event_at(EventType::KeyPress(key), start, millis);
+	let release = |key, millis| event_at(EventType::KeyRelease(key), start, millis);
+	for event in [
+		press(Key::CapsLock, 0),
+		press(Key::ControlLeft, 10),
+		press(Key::KeyT, 20),
+		release(Key::KeyT, 30),
+		release(Key::ControlLeft, 40),
+		release(Key::CapsLock, 50),
+	] {
+		assert_eq!(callback(event, &mut state), None);
+	}
+	// shift could start Shift+R, the rest of the sequence, so it is swallowed
+	assert_eq!(callback(press(Key::ShiftLeft, 500), &mut state), None);
+	assert!(state.pending.is_some());
+	// but A is not part of it; it goes to the application, and so does shift once let go of
+	assert_eq!(callback(press(Key::KeyA, 510), &mut state), Some(press(Key::KeyA, 510)));
+	assert!(state.pending.is_none());
+	assert_eq!(callback(release(Key::KeyA, 520), &mut state), Some(release(Key::KeyA, 520)));
+	assert_eq!(
+		callback(release(Key::ShiftLeft, 530), &mut state),
+		Some(release(Key::ShiftLeft, 530))
+	);
+	assert!(state.pressed.is_empty());
+	assert_eq!(
+		feedback.try_iter().collect::<Vec<_>>(),
+		vec![
+			KeySequenceStarted("CapsLock+Ctrl+T".to_string()).into(),
+			KeySequenceCancelled.into(),
+		]
+	);
+	// and no combo fired; nothing is being read, so A does not interrupt either
+	assert!(rx.try_recv().is_err());
+	// the next key is handled as usual
+	assert_eq!(callback(press(Key::KeyR, 600), &mut state), Some(press(Key::KeyR, 600)));
+}
+
+#[test]
+fn sequence_conflicts_name_the_sequences() {
+	let settings = KeybindingSettings {
+		browse: HashMap::from([("CapsLock+Ctrl+T".to_string(), SayAll.into())]),
+		..KeybindingSettings::default()
+	};
+	let error = ComboSets::with_overrides(&settings).unwrap_err();
+	assert!(matches!(
+		error,
+		BindingError::Conflict {
+			mode: Some(Mode::Browse),
+			error: ComboError::SequencePrefix { .. },
+			..
+		}
+	));
+	assert_eq!(
+		error.to_string(),
+		"in browse mode, CapsLock+Ctrl+T and CapsLock+Ctrl+T R start the same way, so one of them could never be pressed"
+	);
+	let settings = KeybindingSettings {
+		global: HashMap::from([("CapsLock+Ctrl+T".to_string(), SayAll.into())]),
+		..KeybindingSettings::default()
+	};
+	let error = ComboSets::with_overrides(&settings).unwrap_err();
+	assert!(matches!(
+		error,
+		BindingError::Set { error: SetError::SequencePrefixCombo { .. }, .. }
+	));
+	assert_eq!(
+		error.to_string(),
+		"CapsLock+Ctrl+T R in browse mode and CapsLock+Ctrl+T in every mode start the same way, so one of them could never be pressed"
+	);
+	// sequences which differ at a later combo are fine, and can replace the defaults
+	let settings = KeybindingSettings {
+		browse: HashMap::from([
+			("CapsLock+Ctrl+T X".to_string(), SayAll.into()),
+			("CapsLock+Ctrl+T R".to_string(), Quit.into()),
+		]),
+		..KeybindingSettings::default()
+	};
+	let sets = ComboSets::with_overrides(&settings).expect("Valid keybindings!");
+	let browse = sets
+		.iter()
+		.filter(|(mode, _)| *mode == Some(Mode::Browse))
+		.flat_map(|(_, combos)| combos.sequences())
+		.map(|(sequence, ev)| (sequence.combo(ACTIVATION_KEY), ev.clone()))
+		.collect::<Vec<_>>();
+	assert!(browse.contains(&("CapsLock+Ctrl+T X".to_string(), SayAll.into())));
+	assert!(browse.contains(&("CapsLock+Ctrl+T R".to_string(), Quit.into())));
+	assert_eq!(browse.len(), 3);
+}
+
+#[test]
+fn learning_mode_describes_keys_instead_of_pressing_them() {
+	let press = |k| Event::from_event_type(EventType::KeyPress(k));
+	let release = |k| Event::from_event_type(EventType::KeyRelease(k));
+	let (mut state, rx, feedback) = State::new_unbounded();
+	state.combos = ComboSets::default();
+	for event in [press(Key::CapsLock), press(Key::Num1), release(Key::Num1)] {
+		assert_eq!(callback(event, &mut state), None);
+	}
+	assert_eq!(feedback.try_recv(), Ok(LearningModeToggled(true).into()));
+	assert!(state.learning);
+	for event in [press(Key::KeyG), release(Key::KeyG), press(Key::KeyQ), release(Key::KeyQ)] {
+		assert_eq!(callback(event, &mut state), None);
+	}
+	assert_eq!(
+		feedback.try_recv(),
+		Ok(KeyDescribed("CapsLock+G: stop speech".to_string()).into())
+	);
+	assert_eq!(feedback.try_recv(), Ok(KeyDescribed("CapsLock+Q".to_string()).into()));
+	assert_eq!(callback(release(Key::CapsLock), &mut state), None);
+	// keys meant for the application are described, and kept from it, too
+	for event in [press(Key::ControlLeft), press(Key::KeyC), release(Key::KeyC)] {
+		assert_eq!(callback(event, &mut state), None);
+	}
+	assert_eq!(feedback.try_recv(), Ok(KeyDescribed("Ctrl".to_string()).into()));
+	assert_eq!(feedback.try_recv(), Ok(KeyDescribed("Ctrl+C".to_string()).into()));
+	assert_eq!(callback(release(Key::ControlLeft), &mut state), None);
+	// the first combo of a sequence, in browse mode
+	state.mode = Mode::Browse;
+	for event in [press(Key::CapsLock), press(Key::ControlLeft), press(Key::KeyT)] {
+		assert_eq!(callback(event, &mut state), None);
+	}
+	assert_eq!(feedback.try_recv(), Ok(KeyDescribed("CapsLock+Ctrl".to_string()).into()));
+	assert_eq!(
+		feedback.try_recv(),
+		Ok(KeyDescribed("CapsLock+Ctrl+T: start a key sequence".to_string()).into())
+	);
+	assert!(state.pending.is_none());
+	for event in [release(Key::KeyT), release(Key::ControlLeft)] {
+		assert_eq!(callback(event, &mut state), None);
+	}
+	// the combo which turns learning off still works
+	assert_eq!(callback(press(Key::Num1), &mut state), None);
+	assert_eq!(feedback.try_recv(), Ok(LearningModeToggled(false).into()));
+	assert_eq!(callback(release(Key::Num1), &mut state), None);
+	assert_eq!(callback(press(Key::KeyG), &mut state), None);
+	assert_eq!(rx.try_recv(), Ok(StopSpeech.into()));
+}
+
+#[test]
+fn help_lists_the_bindings_of_the_current_mode() {
+	let combos = ComboSets::default();
+	let focus = combos.help(Mode::Focus, Key::CapsLock);
+	assert!(focus.contains(&"CapsLock+G: stop speech".to_string()));
+	assert!(focus.contains(&"CapsLock+1: keyboard learning".to_string()));
+	assert!(!focus.iter().any(|line| line.contains("table row")));
+	let browse = combos.help(Mode::Browse, Key::CapsLock);
+	assert!(browse.contains(&"CapsLock+G: stop speech".to_string()));
+	assert!(browse.contains(&"CapsLock+H: next header".to_string()));
+	assert!(browse.contains(&"CapsLock+Ctrl+T Shift+R: previous table row".to_string()));
+	// combos are listed with the activation key the user chose
+	let insert = combos.help(Mode::Browse, Key::Insert);
+	assert!(insert.contains(&"Insert+Ctrl+T Shift+R: previous table row".to_string()));
+	assert!(!insert.iter().any(|line| line.contains("CapsLock")));
+	let (mut state, _rx, feedback) = State::new_unbounded();
+	state.combos = combos;
+	let press = |k| Event::from_event_type(EventType::KeyPress(k));
+	for event in [press(Key::CapsLock), press(Key::F1)] {
+		assert_eq!(callback(event, &mut state), None);
+	}
+	assert_eq!(feedback.try_recv(), Ok(KeybindingsListed(focus).into()));
+}
+
+#[test]
+fn updates_from_odilia_are_applied_before_the_next_key() {
+	let (mut state, rx, _feedback) = State::new_unbounded();
+	let (updates, receiver) = channel();
+	state.updates = receiver;
+	state.combos = ComboSets::default();
+	let press = |k| Event::from_event_type(EventType::KeyPress(k));
+	let release = |k| Event::from_event_type(EventType::KeyRelease(k));
+	// another client switched to browse mode, so its combos work
+	updates.send(Update::Mode(Mode::Browse)).unwrap();
+	for event in [press(Key::CapsLock), press(Key::KeyH)] {
+		assert_eq!(callback(event, &mut state), None);
+	}
+	assert_eq!(state.mode, Mode::Browse);
+	assert_eq!(
+		rx.try_recv(),
+		Ok(StructuralNavigation(Direction::Forward, Role::Header).into())
+	);
+	for event in [release(Key::KeyH), release(Key::CapsLock)] {
+		assert_eq!(callback(event, &mut state), None);
+	}
+	let settings = KeybindingSettings {
+		activation_keys: vec!["Insert".to_string()],
+		global: HashMap::from([("Insert+Ctrl+Q".to_string(), Quit.into())]),
+		..KeybindingSettings::default()
+	};
+	updates.send(Update::Keybindings(settings)).unwrap();
+	for key in [Key::Insert, Key::ControlLeft, Key::KeyQ] {
+		assert_eq!(callback(press(key), &mut state), None);
+	}
+	assert_eq!(state.activation_keys, [Key::Insert]);
+	assert_eq!(rx.try_recv(), Ok(Quit.into()));
+	// invalid keybindings are ignored
+	let settings =
+		KeybindingSettings { activation_keys: Vec::new(), ..KeybindingSettings::default() };
+	updates.send(Update::Keybindings(settings)).unwrap();
+	assert_eq!(callback(release(Key::KeyQ), &mut state), None);
+	assert_eq!(state.activation_keys, [Key::Insert]);
+}
@@ -2,7 +2,7 @@
 
 use std::{
 	process::Command,
-	sync::mpsc::{sync_channel, Receiver},
+	sync::mpsc::{channel, sync_channel, Receiver},
 	thread,
 	time::Duration,
 };
@@ -11,7 +11,9 @@ use odilia_common::{
 	events::{ScreenReaderEvent as OdiliaEvent, StopSpeech},
 	modes::ScreenReaderMode as Mode,
 };
-use odilia_input_server_keyboard::{callback, ComboSets, State};
+use odilia_input_server_keyboard::{
+	callback, ComboSets, State, ACTIVATION_KEY, DOUBLE_TAP, SEQUENCE_TIMEOUT,
+};
 use rdev::grab;
 
 /// Arguments to [`ydotool`]:
@@ -38,10 +40,21 @@ fn test_key_capture() {
 	let state = State {
 		mode: Mode::Focus,
 		activation_key_pressed: false,
+		activation_keys: vec![ACTIVATION_KEY],
+		double_tap: DOUBLE_TAP,
+		last_tap: None,
+		pass_next: false,
+		passing: Vec::new(),
+		pending: None,
+		learning: false,
+		reading: false,
+		sequence_timeout: SEQUENCE_TIMEOUT,
 		// no allocations below 10-key rollover
 		pressed: Vec::with_capacity(10),
 		combos,
 		tx: ev_tx,
+		feedback: sync_channel(5).0,
+		updates: channel().1,
 	};
 	let _ = thread::spawn(move || {
 		// This will block.
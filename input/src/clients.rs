@@ -0,0 +1,194 @@
+//! The input servers connected to Odilia: who they are, whether they may connect at all, and the
+//! updates they are kept up to date with.
+
+use std::{
+	mem::discriminant,
+	path::PathBuf,
+	sync::{Arc, Mutex, PoisonError},
+};
+
+use async_channel::{unbounded, Receiver, Sender};
+use async_net::unix::UnixStream;
+use nix::{
+	sys::socket::{getsockopt, sockopt::PeerCredentials},
+	unistd::Uid,
+};
+use odilia_common::{
+	errors::ProtocolError,
+	events::Feature,
+	protocol::{ServerMessage, Update},
+	settings::InputSettings,
+};
+
+/// Who is on the other end of the input socket, as far as the kernel and the client itself say.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientInfo {
+	/// Unique among the clients which have connected since Odilia started.
+	pub id: u64,
+	pub pid: i32,
+	pub uid: u32,
+	/// The executable of the process, if it could be found.
+	pub exe: Option<PathBuf>,
+	/// The name the client gave in its hello, once it has said hello.
+	pub name: Option<String>,
+}
+
+/// A connected client, and where to send its updates once it has been welcomed.
+#[derive(Debug)]
+struct Client {
+	info: ClientInfo,
+	updates: Option<Sender<ServerMessage>>,
+}
+
+/// The input servers connected to Odilia, which are kept up to date with the state input depends
+/// on, like the current mode.
+/// Cloning it gives another handle to the same clients.
+#[derive(Debug, Clone)]
+pub struct Clients {
+	inner: Arc<Mutex<ClientsInner>>,
+}
+
+#[derive(Debug)]
+struct ClientsInner {
+	/// The executables allowed to connect; any may, if this is empty.
+	allowed: Vec<PathBuf>,
+	/// How many events each client may send a second.
+	events_per_second: u32,
+	/// The id of the last client.
+	last_client: u64,
+	/// The id of the last update.
+	last_id: u64,
+	/// The latest update of each kind, with its id, for clients which connect later.
+	latest: Vec<(u64, Update)>,
+	clients: Vec<Client>,
+}
+
+impl Default for Clients {
+	fn default() -> Self {
+		Self::new(&InputSettings::default())
+	}
+}
+
+impl Clients {
+	/// No clients yet, who will be let in and limited as `settings` say.
+	#[must_use]
+	pub fn new(settings: &InputSettings) -> Self {
+		let inner = ClientsInner {
+			allowed: settings.allowed_clients.clone(),
+			events_per_second: settings.events_per_second,
+			last_client: 0,
+			last_id: 0,
+			latest: Vec::new(),
+			clients: Vec::new(),
+		};
+		Self { inner: Arc::new(Mutex::new(inner)) }
+	}
+
+	/// Send `update` to every connected client, and to any which connect later.
+	pub fn update(&self, update: Update) {
+		let mut inner = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+		inner.last_id += 1;
+		let id = inner.last_id;
+		for client in &inner.clients {
+			if let Some(updates) = &client.updates {
+				let _ = updates.try_send(ServerMessage::Update {
+					id,
+					update: update.clone(),
+				});
+			}
+		}
+		inner.latest
+			.retain(|(_, latest)| discriminant(latest) != discriminant(&update));
+		inner.latest.push((id, update));
+	}
+
+	/// Turn `feature` on or off, and tell every client which features are now on.
+	/// Every feature is on until it is turned off.
+	pub fn set_feature(&self, feature: Feature, on: bool) {
+		let mut features = self.features();
+		features.retain(|f| *f != feature);
+		if on {
+			features.push(feature);
+		}
+		self.update(Update::Features(features));
+	}
+
+	/// The features which are on.
+	#[must_use]
+	pub fn features(&self) -> Vec<Feature> {
+		let inner = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+		inner.latest
+			.iter()
+			.find_map(|(_, update)| match update {
+				Update::Features(features) => Some(features.clone()),
+				_ => None,
+			})
+			.unwrap_or_else(|| vec![Feature::Speech, Feature::Braille])
+	}
+
+	/// Every client which is connected.
+	#[must_use]
+	pub fn connected(&self) -> Vec<ClientInfo> {
+		let inner = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+		inner.clients.iter().map(|client| client.info.clone()).collect()
+	}
+
+	/// How many events each client may send a second.
+	pub(crate) fn events_per_second(&self) -> u32 {
+		self.inner
+			.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.events_per_second
+	}
+
+	/// Let in the process on the other end of `socket`, if it is run by the same user as Odilia,
+	/// and is one of the allowed executables.
+	pub(crate) fn admit(&self, socket: &UnixStream) -> Result<ClientInfo, ProtocolError> {
+		let credentials = getsockopt(socket, PeerCredentials).map_err(|error| {
+			tracing::warn!(%error, "Unable to find out who connected");
+			ProtocolError::Unauthorized
+		})?;
+		let pid = credentials.pid();
+		let uid = credentials.uid();
+		let exe = std::fs::read_link(format!("/proc/{pid}/exe")).ok();
+		if uid != Uid::current().as_raw() {
+			tracing::warn!(pid, uid, ?exe, "Refused input client run by another user");
+			return Err(ProtocolError::Unauthorized);
+		}
+		let mut inner = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+		if !inner.allowed.is_empty()
+			&& !exe.as_ref().is_some_and(|exe| inner.allowed.contains(exe))
+		{
+			tracing::warn!(pid, uid, ?exe, "Refused input client which is not allowed");
+			return Err(ProtocolError::Unauthorized);
+		}
+		inner.last_client += 1;
+		let info = ClientInfo { id: inner.last_client, pid, uid, exe, name: None };
+		inner.clients.push(Client { info: info.clone(), updates: None });
+		Ok(info)
+	}
+
+	/// Note the name client `id` gave in its hello, and start sending it updates, beginning
+	/// with the latest of each kind.
+	pub(crate) fn welcome(&self, id: u64, name: &str) -> Receiver<ServerMessage> {
+		let (tx, rx) = unbounded();
+		let mut inner = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+		for (id, update) in &inner.latest {
+			let _ = tx.try_send(ServerMessage::Update {
+				id: *id,
+				update: update.clone(),
+			});
+		}
+		if let Some(client) = inner.clients.iter_mut().find(|client| client.info.id == id) {
+			client.info.name = Some(name.to_string());
+			client.updates = Some(tx);
+		}
+		rx
+	}
+
+	/// Forget client `id`, which has disconnected.
+	pub(crate) fn disconnect(&self, id: u64) {
+		let mut inner = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+		inner.clients.retain(|client| client.info.id != id);
+	}
+}
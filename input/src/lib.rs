@@ -10,26 +10,35 @@
 )]
 #![allow(clippy::multiple_crate_versions)]
 
+mod clients;
 mod proxy;
 
 use std::{
 	env,
+	fs::{DirBuilder, Permissions},
 	ops::ControlFlow,
-	os::unix::net::SocketAddr,
+	os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt},
 	path::Path,
 	process::{exit, id},
-	time::{SystemTime, UNIX_EPOCH},
+	time::{Instant, SystemTime, UNIX_EPOCH},
 };
 
-use async_channel::Sender;
+use async_channel::{Receiver, Sender};
 use async_fs as fs;
 use async_net::unix::{UnixListener, UnixStream};
-use futures_lite::{future::or, stream::Stream, AsyncReadExt};
+use futures_lite::{future::or, stream::Stream, AsyncReadExt, AsyncWriteExt};
 use futures_util::{future::BoxFuture, FutureExt};
 use nix::unistd::Uid;
-use odilia_common::{errors::OdiliaError, events::ScreenReaderEvent};
+use odilia_common::{
+	errors::{OdiliaError, ProtocolError},
+	events::{InputFeedback, ScreenReaderEvent},
+	protocol::{decode, encode, ClientMessage, FrameDecoder, ServerMessage, PROTOCOL_VERSION},
+};
 use smol_cancellation_token::CancellationToken;
 use sysinfo::{ProcessExt, System, SystemExt};
+use tracing::Instrument;
+
+pub use crate::clients::{ClientInfo, Clients};
 
 async fn or_cancel<F>(f: F, token: &CancellationToken) -> Result<F::Output, std::io::Error>
 where
@@ -131,12 +140,35 @@ pub async fn setup_input_server() -> Result<UnixListener, OdiliaError> {
 		}
 	}
 
-	let listener = UnixListener::bind(sock_file_path)?;
+	// only the user running Odilia may connect; peer credentials are checked on top of this
+	let listener = bind_private(&sock_file_path)?;
 	tracing::debug!("Listener activated");
 	Ok(listener)
 }
 
-/// Receives [`odilia_common::events::ScreenReaderEvent`] structs, then creates a stream of futures that _need_ to be awaited/spawned by the caller onto the executor.
+/// Bind a socket at `path` which only its owner may connect to.
+/// Its directory is made private to the owner first, so that nobody else can reach the socket,
+/// not even between it being created and its own permissions being narrowed.
+fn bind_private(path: impl AsRef<Path>) -> std::io::Result<UnixListener> {
+	let path = path.as_ref();
+	if let Some(dir) = path.parent() {
+		DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+		// it may have been made before, with looser permissions or by somebody else
+		if std::fs::metadata(dir)?.uid() != Uid::current().as_raw() {
+			return Err(std::io::Error::new(
+				std::io::ErrorKind::PermissionDenied,
+				format!("{} belongs to another user", dir.display()),
+			));
+		}
+		std::fs::set_permissions(dir, Permissions::from_mode(0o700))?;
+	}
+	let listener = UnixListener::bind(path)?;
+	std::fs::set_permissions(path, Permissions::from_mode(0o600))?;
+	Ok(listener)
+}
+
+/// Receives [`odilia_common::events::ScreenReaderEvent`] structs, and the
+/// [`odilia_common::events::InputFeedback`] to announce, then creates a stream of futures that _need_ to be awaited/spawned by the caller onto the executor.
 /// Normally, the best way to do this is like so:
 ///
 /// ```rust,no_run
@@ -144,28 +176,33 @@ pub async fn setup_input_server() -> Result<UnixListener, OdiliaError> {
 /// # use smol_cancellation_token::CancellationToken;
 /// # use async_channel::bounded;
 /// # use async_net::unix::UnixListener;
-/// use odilia_input::sr_event_receiver;
+/// use odilia_input::{sr_event_receiver, Clients};
 /// // use smol::spawn or tokio::spawn
 /// # fn spawn<F>(_f: F) {}
 /// let listener = UnixListener::bind("/some/path/here")
 ///     .expect("Valid listener");
 /// let (sender, _receiver) = bounded(128);
+/// let (feedback_sender, _feedback_receiver) = bounded(128);
 /// let ct = CancellationToken::new();
 /// // For tokio; for async-io based executors, remember to call .detach()
-/// let stream = sr_event_receiver(listener, sender, ct)
+/// let stream = sr_event_receiver(listener, sender, feedback_sender, Clients::default(), ct)
 ///     .for_each(|fut| spawn(fut));
 /// ```
 ///
+/// Each client is checked against `clients` before it is listened to, and sent its updates once
+/// it has said hello.
 /// If the cancellation token is triggered, this stream will finish.
 #[tracing::instrument(skip_all)]
 pub fn sr_event_receiver(
 	listener: UnixListener,
 	event_sender: Sender<ScreenReaderEvent>,
+	feedback_sender: Sender<InputFeedback>,
+	clients: Clients,
 	shutdown: CancellationToken,
 ) -> impl Stream<Item = BoxFuture<'static, ()>> {
 	async_stream::stream! {
 	  loop {
-	      match sr_event_receiver_inner(&listener, &event_sender, &shutdown).await {
+	      match sr_event_receiver_inner(&listener, &event_sender, &feedback_sender, &clients, &shutdown).await {
 		Ok(box_fut) => yield box_fut,
 		Err(ControlFlow::Break(())) => break,
 		Err(ControlFlow::Continue(())) => {},
@@ -180,6 +217,8 @@ pub fn sr_event_receiver(
 async fn sr_event_receiver_inner(
 	listener: &UnixListener,
 	event_sender: &Sender<ScreenReaderEvent>,
+	feedback_sender: &Sender<InputFeedback>,
+	clients: &Clients,
 	shutdown: &CancellationToken,
 ) -> Result<BoxFuture<'static, ()>, ControlFlow<()>> {
 	let maybe_msg = or_cancel(listener.accept(), shutdown).await;
@@ -188,14 +227,33 @@ async fn sr_event_receiver_inner(
 		return Err(ControlFlow::Break(()));
 	};
 	match msg {
-		Ok((socket, address)) => {
+		Ok((socket, _)) => {
 			tracing::debug!("Ok from socket");
-			return Ok(handle_event(
+			let client = match clients.admit(&socket) {
+				Ok(client) => client,
+				Err(e) => return Ok(refuse(socket, e).boxed()),
+			};
+			let span = tracing::info_span!(
+				"input client",
+				id = client.id,
+				pid = client.pid,
+				exe = ?client.exe,
+				client = tracing::field::Empty,
+			);
+			let clients = clients.clone();
+			let session = handle_event(
 				socket,
-				address,
+				client.id,
 				event_sender.clone(),
+				feedback_sender.clone(),
+				clients.clone(),
 				shutdown.clone(),
-			)
+			);
+			return Ok(async move {
+				session.await;
+				clients.disconnect(client.id);
+			}
+			.instrument(span)
 			.boxed());
 		}
 		Err(e) => {
@@ -205,52 +263,272 @@ async fn sr_event_receiver_inner(
 	Err(ControlFlow::Continue(()))
 }
 
+/// What to do about one frame from a client.
+#[derive(Debug, PartialEq, Eq)]
+enum Received {
+	/// Pass the event on to Odilia.
+	Event(ScreenReaderEvent),
+	/// Pass the feedback on to Odilia, to be announced.
+	Feedback(InputFeedback),
+	/// Answer the client, and carry on.
+	Reply(ServerMessage),
+	/// Answer the client, and hang up.
+	Close(ServerMessage),
+	/// Nothing more to do.
+	Nothing,
+}
+
+/// How many events a client may still send: each second refills it by `capacity`, up to
+/// `capacity`, and each event takes one.
+#[derive(Debug)]
+struct RateLimit {
+	/// No limit at all, if zero.
+	capacity: u32,
+	tokens: f64,
+	last: Instant,
+	/// Whether events are being dropped, so that the client is only told once per burst.
+	limited: bool,
+}
+
+impl RateLimit {
+	fn new(events_per_second: u32) -> Self {
+		Self {
+			capacity: events_per_second,
+			tokens: f64::from(events_per_second),
+			last: Instant::now(),
+			limited: false,
+		}
+	}
+
+	/// Whether an event sent at `now` may go through.
+	fn allow(&mut self, now: Instant) -> bool {
+		if self.capacity == 0 {
+			return true;
+		}
+		let capacity = f64::from(self.capacity);
+		let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+		self.tokens = (self.tokens + elapsed * capacity).min(capacity);
+		self.last = now;
+		if self.tokens < 1.0 {
+			return false;
+		}
+		self.tokens -= 1.0;
+		self.limited = false;
+		true
+	}
+}
+
+/// One client's connection: whether it has said hello, and as whom, which updates it has not
+/// acknowledged yet, and how many more events it may send.
+#[derive(Debug)]
+struct Session {
+	client: Option<String>,
+	unacknowledged: Vec<u64>,
+	limit: RateLimit,
+}
+
+impl Session {
+	fn new(events_per_second: u32) -> Self {
+		Self {
+			client: None,
+			unacknowledged: Vec::new(),
+			limit: RateLimit::new(events_per_second),
+		}
+	}
+
+	fn receive(&mut self, frame: Result<Vec<u8>, ProtocolError>) -> Received {
+		let message = match frame.and_then(|frame| decode::<ClientMessage>(&frame)) {
+			Ok(message) => message,
+			Err(e) => return Received::Reply(ServerMessage::Error(e)),
+		};
+		match message {
+			ClientMessage::Hello(hello) if hello.version != PROTOCOL_VERSION => {
+				Received::Close(ServerMessage::Error(
+					ProtocolError::UnsupportedVersion(hello.version),
+				))
+			}
+			ClientMessage::Hello(hello) => {
+				tracing::info!(client = %hello.client, "Input client said hello");
+				self.client = Some(hello.client);
+				Received::Reply(ServerMessage::Welcome {
+					version: PROTOCOL_VERSION,
+				})
+			}
+			ClientMessage::Event(_)
+			| ClientMessage::Feedback(_)
+			| ClientMessage::Ack(_)
+				if self.client.is_none() =>
+			{
+				Received::Reply(ServerMessage::Error(ProtocolError::HelloExpected))
+			}
+			ClientMessage::Event(sre) => self.limited(Received::Event(sre)),
+			ClientMessage::Feedback(feedback) => {
+				self.limited(Received::Feedback(feedback))
+			}
+			ClientMessage::Ack(id) => {
+				let Some(index) =
+					self.unacknowledged.iter().position(|sent| *sent == id)
+				else {
+					return Received::Reply(ServerMessage::Error(
+						ProtocolError::UnknownUpdate(id),
+					));
+				};
+				self.unacknowledged.remove(index);
+				tracing::debug!(id, "Update acknowledged");
+				Received::Nothing
+			}
+		}
+	}
+
+	/// Let `received` through, unless the client has sent more than its rate limit allows;
+	/// events and feedback share the same limit.
+	fn limited(&mut self, received: Received) -> Received {
+		if self.limit.allow(Instant::now()) {
+			return received;
+		}
+		if self.limit.limited {
+			return Received::Nothing;
+		}
+		tracing::warn!(
+			?received,
+			"Input client is sending events too quickly; dropping them"
+		);
+		self.limit.limited = true;
+		Received::Reply(ServerMessage::Error(ProtocolError::RateLimited))
+	}
+
+	/// Note that `message` is about to be sent, so that it can be acknowledged.
+	fn send(&mut self, message: &ServerMessage) {
+		if let ServerMessage::Update { id, .. } = message {
+			self.unacknowledged.push(*id);
+		}
+	}
+}
+
+async fn reply(socket: &mut UnixStream, message: &ServerMessage) -> std::io::Result<()> {
+	let frame = encode(message).map_err(std::io::Error::other)?;
+	socket.write_all(&frame).await
+}
+
+/// Tell a client which was not let in why, and hang up.
+async fn refuse(mut socket: UnixStream, error: ProtocolError) {
+	if let Err(e) = reply(&mut socket, &ServerMessage::Error(error)).await {
+		tracing::debug!(error = ?e, "Error refusing input client");
+	}
+}
+
+/// The next update for a client, once it has said hello; until then, or if Odilia stops sending
+/// them, this never finishes.
+async fn next_update(updates: Option<&Receiver<ServerMessage>>) -> ServerMessage {
+	match updates {
+		Some(updates) => match updates.recv().await {
+			Ok(message) => message,
+			Err(_) => std::future::pending().await,
+		},
+		None => std::future::pending().await,
+	}
+}
+
+/// Bytes read from a client, or an update to send to it.
+enum Incoming {
+	Read(std::io::Result<usize>),
+	Update(ServerMessage),
+}
+
+/// Listen to client `id`, which has been let in by `clients`, until it hangs up.
 async fn handle_event(
 	mut socket: UnixStream,
-	address: SocketAddr,
+	id: u64,
 	event_sender: Sender<ScreenReaderEvent>,
+	feedback_sender: Sender<InputFeedback>,
+	clients: Clients,
 	shutdown: CancellationToken,
 ) {
+	let mut decoder = FrameDecoder::new();
+	let mut session = Session::new(clients.events_per_second());
+	let mut updates = None;
 	loop {
 		let mut buf = [0; 4096];
-		let maybe_reader = or_cancel(socket.read(&mut buf), &shutdown).await;
-		let Ok(reader) = maybe_reader else {
+		let read = socket.read(&mut buf).map(Incoming::Read);
+		let update = next_update(updates.as_ref()).map(Incoming::Update);
+		let Ok(incoming) = or_cancel(or(read, update), &shutdown).await else {
 			tracing::debug!("Shutting down listening on input socket at path '{:?}' due to cancellation token", socket.local_addr());
 			break;
 		};
-		let bytes = match reader {
-			Ok(0) => {
+		let bytes = match incoming {
+			Incoming::Update(message) => {
+				session.send(&message);
+				if let Err(e) = reply(&mut socket, &message).await {
+					tracing::error!(error = ?e, "Error sending update over socket");
+					break;
+				}
+				continue;
+			}
+			Incoming::Read(Ok(0)) => {
 				tracing::debug!(
 					"Socket '{:?}' was disconnected",
 					socket.local_addr()
 				);
 				break;
 			}
-			Ok(b) => b,
-			Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+			Incoming::Read(Ok(b)) => b,
+			Incoming::Read(Err(ref e))
+				if e.kind() == std::io::ErrorKind::WouldBlock =>
+			{
 				continue;
 			}
-			Err(e) => {
+			Incoming::Read(Err(e)) => {
 				tracing::error!(error = ?e, "Error reading from socket");
 				continue;
 			}
 		};
-		let response = std::str::from_utf8(&buf[..bytes]).expect("Valid UTF-8");
-		// if valid screen reader event
-
-		let sre = match serde_json::from_str::<ScreenReaderEvent>(response) {
-			Ok(sre) => sre,
-			Err(e) => {
-				tracing::error!(error = ?e, "Invalid odilia event");
-				continue;
+		decoder.push(&buf[..bytes]);
+		while let Some(frame) = decoder.next_frame() {
+			let message = match session.receive(frame) {
+				Received::Event(sre) => {
+					tracing::debug!(?sre);
+					if let Err(e) = event_sender.send(sre).await {
+						tracing::error!(error = ?e, "Error sending ScreenReaderEvent over socket");
+					} else {
+						tracing::debug!("Sent SR event");
+					}
+					continue;
+				}
+				Received::Feedback(feedback) => {
+					tracing::debug!(?feedback);
+					if let Err(e) = feedback_sender.send(feedback).await {
+						tracing::error!(error = ?e, "Error sending InputFeedback over socket");
+					}
+					continue;
+				}
+				Received::Nothing => continue,
+				Received::Reply(message) => message,
+				Received::Close(message) => {
+					tracing::error!(?message, "Closing input socket");
+					if let Err(e) = reply(&mut socket, &message).await {
+						tracing::error!(error = ?e, "Error replying over socket");
+					}
+					return;
+				}
+			};
+			if let ServerMessage::Error(ref e) = message {
+				tracing::error!(error = %e, "Invalid odilia event");
+			}
+			if let Err(e) = reply(&mut socket, &message).await {
+				tracing::error!(error = ?e, "Error replying over socket");
+				return;
+			}
+			// updates start after the welcome, so that they are not mistaken for it
+			if matches!(message, ServerMessage::Welcome { .. }) && updates.is_none() {
+				if let Some(name) = &session.client {
+					tracing::Span::current().record("client", name.as_str());
+					updates = Some(clients.welcome(id, name));
+				}
 			}
-		};
-		if let Err(e) = event_sender.send(sre).await {
-			tracing::error!(error = ?e, "Error sending ScreenReaderEvent over socket");
-		} else {
-			tracing::debug!("Sent SR event");
 		}
-		tracing::debug!(?address, response,);
+	}
+	if !session.unacknowledged.is_empty() {
+		tracing::warn!(unacknowledged = ?session.unacknowledged, "Input client left without acknowledging every update");
 	}
 }
 
@@ -263,7 +541,7 @@ fn get_file_paths() -> (String, String) {
             );
 
 			let pid_file_path = format!("{val}/odilias.pid");
-			let sock_file_path = format!("{val}/odilia.sock");
+			let sock_file_path = format!("{val}/odilia/odilia.sock");
 
 			(pid_file_path, sock_file_path)
 		}
@@ -271,9 +549,310 @@ fn get_file_paths() -> (String, String) {
 			tracing::warn!(error=%e, "XDG_RUNTIME_DIR Variable is not set, falling back to hardcoded path");
 
 			let pid_file_path = format!("/run/user/{}/odilias.pid", Uid::current());
-			let sock_file_path = format!("/run/user/{}/odilia.sock", Uid::current());
+			let sock_file_path =
+				format!("/run/user/{}/odilia/odilia.sock", Uid::current());
 
 			(pid_file_path, sock_file_path)
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+
+	use async_channel::unbounded;
+	use futures_lite::{
+		future::{block_on, zip},
+		io::BufReader,
+		AsyncBufReadExt, StreamExt,
+	};
+	use odilia_common::{
+		events::{Feature, KeyDescribed, Quit, StopSpeech},
+		modes::ScreenReaderMode,
+		protocol::{Hello, Update},
+		settings::InputSettings,
+	};
+	use proptest::prelude::*;
+
+	use super::*;
+
+	fn frame(message: &ClientMessage) -> Vec<u8> {
+		encode(message).expect("Encodable message")
+	}
+
+	fn hello() -> ClientMessage {
+		ClientMessage::Hello(Hello::new("test"))
+	}
+
+	fn quit() -> ClientMessage {
+		ClientMessage::Event(ScreenReaderEvent::Quit(Quit))
+	}
+
+	fn described() -> ClientMessage {
+		ClientMessage::Feedback(InputFeedback::KeyDescribed(KeyDescribed(
+			"CapsLock+H: next heading".to_string(),
+		)))
+	}
+
+	/// What came of the bytes one client sent: events, feedback, and replies.
+	type Exchanged = (Vec<ScreenReaderEvent>, Vec<InputFeedback>, Vec<ServerMessage>);
+
+	/// Send `bytes` as one client would, and return what came of it.
+	fn exchange(bytes: &[u8]) -> Exchanged {
+		let (mut client, server) = UnixStream::pair().expect("Socket pair");
+		let clients = Clients::default();
+		let id = clients.admit(&server).expect("Admitted").id;
+		let (tx, rx) = unbounded();
+		let (feedback_tx, feedback_rx) = unbounded();
+		block_on(async {
+			client.write_all(bytes).await.expect("Written");
+			client.shutdown(std::net::Shutdown::Write).expect("Shut down");
+			handle_event(
+				server,
+				id,
+				tx,
+				feedback_tx,
+				clients,
+				CancellationToken::new(),
+			)
+			.await;
+			let mut replies = Vec::new();
+			client.read_to_end(&mut replies).await.expect("Replies");
+			let mut decoder = FrameDecoder::new();
+			decoder.push(&replies);
+			let replies = std::iter::from_fn(|| decoder.next_frame())
+				.map(|frame| {
+					decode(&frame.expect("Short reply")).expect("Valid reply")
+				})
+				.collect();
+			(
+				std::iter::from_fn(|| rx.try_recv().ok()).collect(),
+				std::iter::from_fn(|| feedback_rx.try_recv().ok()).collect(),
+				replies,
+			)
+		})
+	}
+
+	#[test]
+	fn events_follow_a_hello() {
+		let bytes = [
+			frame(&hello()),
+			b"\xff\n".to_vec(),
+			frame(&ClientMessage::Event(ScreenReaderEvent::StopSpeech(StopSpeech))),
+			frame(&quit()),
+		]
+		.concat();
+		let (events, _, replies) = exchange(&bytes);
+		assert_eq!(
+			events,
+			vec![
+				ScreenReaderEvent::StopSpeech(StopSpeech),
+				ScreenReaderEvent::Quit(Quit)
+			]
+		);
+		assert_eq!(
+			replies,
+			vec![
+				ServerMessage::Welcome { version: PROTOCOL_VERSION },
+				ServerMessage::Error(ProtocolError::InvalidUtf8),
+			]
+		);
+	}
+
+	#[test]
+	fn events_before_a_hello_are_refused() {
+		let (events, _, replies) = exchange(&frame(&quit()));
+		assert!(events.is_empty());
+		assert_eq!(replies, vec![ServerMessage::Error(ProtocolError::HelloExpected)]);
+	}
+
+	#[test]
+	fn feedback_is_kept_apart_from_events() {
+		let (events, feedback, replies) = exchange(&frame(&described()));
+		assert!(events.is_empty() && feedback.is_empty());
+		assert_eq!(replies, vec![ServerMessage::Error(ProtocolError::HelloExpected)]);
+		let (events, feedback, _) =
+			exchange(&[frame(&hello()), frame(&described())].concat());
+		assert!(events.is_empty());
+		assert_eq!(
+			feedback,
+			vec![InputFeedback::KeyDescribed(KeyDescribed(
+				"CapsLock+H: next heading".to_string()
+			))]
+		);
+	}
+
+	#[test]
+	fn feedback_counts_towards_the_rate_limit() {
+		let flood = [vec![frame(&quit()); 30], vec![frame(&described()); 30]]
+			.concat()
+			.concat();
+		let (events, feedback, _) = exchange(&[frame(&hello()), flood].concat());
+		assert_eq!((events.len(), feedback.len()), (30, 20));
+	}
+
+	#[test]
+	fn unsupported_versions_are_hung_up_on() {
+		let old = ClientMessage::Hello(Hello { version: 0, client: "old".to_string() });
+		let (events, _, replies) = exchange(&[frame(&old), frame(&quit())].concat());
+		assert!(events.is_empty());
+		assert_eq!(
+			replies,
+			vec![ServerMessage::Error(ProtocolError::UnsupportedVersion(0))]
+		);
+	}
+
+	async fn next_reply(
+		lines: &mut (impl Stream<Item = std::io::Result<String>> + Unpin),
+	) -> ServerMessage {
+		let line = lines.next().await.expect("Reply").expect("Read");
+		decode(line.as_bytes()).expect("Valid reply")
+	}
+
+	#[test]
+	fn updates_follow_the_welcome_and_are_acknowledged() {
+		let clients = Clients::default();
+		clients.update(Update::Mode(ScreenReaderMode::Browse));
+		clients.update(Update::Mode(ScreenReaderMode::Focus));
+		let (mut client, server) = UnixStream::pair().expect("Socket pair");
+		let id = clients.admit(&server).expect("Admitted").id;
+		let (tx, _rx) = unbounded();
+		let (feedback_tx, _feedback_rx) = unbounded();
+		let server = handle_event(
+			server,
+			id,
+			tx,
+			feedback_tx,
+			clients.clone(),
+			CancellationToken::new(),
+		);
+		let client = async {
+			let mut lines = BufReader::new(client.clone()).lines();
+			client.write_all(&frame(&hello())).await.expect("Written");
+			assert_eq!(
+				next_reply(&mut lines).await,
+				ServerMessage::Welcome { version: PROTOCOL_VERSION }
+			);
+			// only the latest mode is sent
+			assert_eq!(
+				next_reply(&mut lines).await,
+				ServerMessage::Update {
+					id: 2,
+					update: Update::Mode(ScreenReaderMode::Focus)
+				}
+			);
+			clients.set_feature(Feature::Speech, false);
+			assert_eq!(
+				next_reply(&mut lines).await,
+				ServerMessage::Update {
+					id: 3,
+					update: Update::Features(vec![Feature::Braille])
+				}
+			);
+			let acks = [2, 3, 3].map(|id| frame(&ClientMessage::Ack(id))).concat();
+			client.write_all(&acks).await.expect("Written");
+			assert_eq!(
+				next_reply(&mut lines).await,
+				ServerMessage::Error(ProtocolError::UnknownUpdate(3))
+			);
+			client.shutdown(std::net::Shutdown::Write).expect("Shut down");
+		};
+		block_on(zip(server, client));
+	}
+
+	#[test]
+	fn acks_before_a_hello_are_refused() {
+		let (_, _, replies) = exchange(&frame(&ClientMessage::Ack(1)));
+		assert_eq!(replies, vec![ServerMessage::Error(ProtocolError::HelloExpected)]);
+	}
+
+	#[test]
+	fn floods_are_cut_off_and_reported_once() {
+		let flood = vec![frame(&quit()); 60].concat();
+		let (events, _, replies) = exchange(&[frame(&hello()), flood].concat());
+		assert_eq!(events.len(), 50);
+		assert_eq!(
+			replies,
+			vec![
+				ServerMessage::Welcome { version: PROTOCOL_VERSION },
+				ServerMessage::Error(ProtocolError::RateLimited),
+			]
+		);
+	}
+
+	#[test]
+	fn rate_limits_refill_over_time() {
+		let mut limit = RateLimit::new(2);
+		let start = limit.last;
+		assert!(limit.allow(start));
+		assert!(limit.allow(start));
+		assert!(!limit.allow(start));
+		assert!(limit.allow(start + Duration::from_millis(500)));
+		assert!(!limit.allow(start + Duration::from_millis(500)));
+		assert!(RateLimit::new(0).allow(start));
+	}
+
+	#[test]
+	fn sockets_are_only_for_their_owner() {
+		let dir = env::temp_dir().join(format!("odilia-test-{}", id()));
+		// a directory left over with looser permissions is narrowed
+		std::fs::create_dir(&dir).expect("Created");
+		std::fs::set_permissions(&dir, Permissions::from_mode(0o755)).expect("Opened up");
+		let path = dir.join("odilia.sock");
+		let listener = bind_private(&path).expect("Bound");
+		let mode = |path| {
+			std::fs::metadata(path).expect("Exists").permissions().mode() & 0o777
+		};
+		let modes = (mode(&dir), mode(&path));
+		std::fs::remove_dir_all(&dir).expect("Removed");
+		drop(listener);
+		assert_eq!(modes, (0o700, 0o600));
+	}
+
+	#[test]
+	fn clients_are_checked_and_tracked() {
+		let (_, server) = UnixStream::pair().expect("Socket pair");
+		let clients = Clients::default();
+		let client = clients.admit(&server).expect("Admitted");
+		assert_eq!(client.pid, i32::try_from(id()).expect("Small pid"));
+		assert_eq!(client.uid, Uid::current().as_raw());
+		assert_eq!(client.exe, env::current_exe().ok());
+		drop(clients.welcome(client.id, "test"));
+		assert_eq!(
+			clients.connected(),
+			vec![ClientInfo { name: Some("test".to_string()), ..client.clone() }]
+		);
+		clients.disconnect(client.id);
+		assert!(clients.connected().is_empty());
+
+		let only_others = InputSettings {
+			allowed_clients: vec!["/usr/bin/odilia-input-server-keyboard".into()],
+			..InputSettings::default()
+		};
+		let refused = Clients::new(&only_others).admit(&server);
+		assert_eq!(refused, Err(ProtocolError::Unauthorized));
+
+		let only_us = InputSettings {
+			allowed_clients: env::current_exe().into_iter().collect(),
+			..InputSettings::default()
+		};
+		assert!(Clients::new(&only_us).admit(&server).is_ok());
+	}
+
+	proptest! {
+		#[test]
+		fn garbage_gets_replies_not_panics(frames in proptest::collection::vec(proptest::collection::vec(any::<u8>(), 0..128), 0..16)) {
+			let mut session = Session::new(0);
+			session.receive(Ok(serde_json::to_vec(&hello()).expect("Encodable hello")));
+			for garbage in frames {
+				match session.receive(Ok(garbage)) {
+					Received::Reply(ServerMessage::Error(_))
+					| Received::Event(_)
+					| Received::Feedback(_) => {}
+					other => prop_assert!(false, "unexpected {other:?}"),
+				}
+			}
+		}
+	}
+}
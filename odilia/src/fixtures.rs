@@ -0,0 +1,38 @@
+//! Items for tests to work with, as they would come out of the cache.
+
+use std::sync::{Arc, Mutex};
+
+use atspi::{InterfaceSet, Role, StateSet};
+use odilia_cache::CacheItem;
+use odilia_common::{cache::AccessiblePrimitive, settings::SpeechSettings};
+
+use crate::{speech::language, state::UtteranceVoice};
+
+/// An item with `role` and `name`, and nothing else: no text, states, relatives or interfaces.
+pub fn cache_item(role: Role, name: Option<&str>) -> CacheItem {
+	let object = AccessiblePrimitive {
+		sender: ":1.1".to_string(),
+		id: "/org/a11y/atspi/accessible/1".to_string(),
+	};
+	CacheItem {
+		object: object.clone(),
+		app: object.clone(),
+		parent: object,
+		index: None,
+		children_num: None,
+		interfaces: InterfaceSet::empty(),
+		role,
+		states: StateSet::empty(),
+		children: Vec::new(),
+		name: name.map(ToString::to_string),
+		description: None,
+		help_text: None,
+		text: None,
+	}
+}
+
+/// The default voice, which speech is already using, so applying it does nothing.
+pub fn default_voice() -> UtteranceVoice {
+	let voice = language::select(&SpeechSettings::default(), None);
+	UtteranceVoice::new(voice.clone(), Arc::new(Mutex::new(voice)))
+}
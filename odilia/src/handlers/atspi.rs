@@ -1,24 +1,33 @@
-use std::{
-	cmp::{max, min},
-	fmt::Write,
-};
+use std::cmp::{max, min};
 
 use atspi::{
 	events::{
 		document::LoadCompleteEvent,
-		object::{StateChangedEvent, TextCaretMovedEvent},
+		object::{StateChangedEvent, TextCaretMovedEvent, TextChangedEvent},
+	},
+	Operation, Role, State,
+};
+use odilia_cache::{CacheItem, LabelledBy};
+use odilia_common::{
+	cache::AccessiblePrimitive,
+	command::{
+		Braille, CaretPos, Focus, OdiliaCommand, PlaySound, SetState, Speak, SpeakChar,
+		TryIntoCommands,
 	},
-	Role,
+	errors::OdiliaError,
+	settings::speech::{EchoMode, SpeechField},
 };
-use odilia_cache::LabelledBy;
-use odilia_common::command::{CaretPos, Focus, OdiliaCommand, SetState, Speak, TryIntoCommands};
 use ssip::Priority;
 
 use crate::{
-	state::{LastCaretPos, LastFocused},
+	speech::{
+		echo,
+		generator::{SpeechGenerator, SpeechSource},
+	},
+	state::{AccessibleHistory, LastCaretPos, LastFocused, LiveSpeechSettings, Symbols},
 	tower::{
 		state_changed::Focused, ActiveAppEvent, CacheEvent, EventProp, NonContainerEvent,
-		RelationSet, Subtree,
+		ParentItem, RelationSet, Shortcut, Subtree,
 	},
 };
 
@@ -32,51 +41,74 @@ pub async fn focused(
 	state_changed: NonContainerEvent<Focused>,
 	EventProp(relation_set): EventProp<RelationSet<LabelledBy>>,
 	EventProp(subtree): EventProp<Subtree>,
-) -> impl TryIntoCommands {
-	//because the current command implementation doesn't allow for multiple speak commands without interrupting the previous utterance, this is more or less an accumulating buffer for that utterance
-	let mut utterance_buffer = String::new();
+	EventProp(parent): EventProp<ParentItem>,
+	Shortcut(shortcut): Shortcut,
+	LiveSpeechSettings(settings): LiveSpeechSettings,
+) -> Result<Vec<OdiliaCommand>, OdiliaError> {
 	let item = state_changed.item;
-	//does this have a text or a name?
-	// in order for the borrow checker to not scream that we move ownership of item.text, therefore making item partially moved, we only take a reference here, because in truth the only thing that we need to know is if the string is empty, because the extending of the buffer will imply a clone anyway
-	if let Some(text) = item.text {
-		//then just append to the buffer and be done with it
-		utterance_buffer += &text;
-	} else {
-		//then the label can either be the accessible name, the description, or the relations set, aka labeled by another object
-		//unfortunately, the or_else function of result doesn't accept async cloasures or cloasures with async blocks, so we can't use lazy loading here at the moment. The performance penalty is minimal however, because this should be in cache anyway
-		let label = if let Some(n) = item.name.as_deref() {
-			n.to_string()
-		} else if let Some(d) = item.description.as_deref() {
-			d.to_string()
-		//otherwise, if this is empty too, we try to use the relations set to find the element labeling this one
-		} else {
-			relation_set.into_iter().filter_map(|this| this.text).collect()
-		};
-		utterance_buffer += &label;
+	let labelled_by = relation_set
+		.into_iter()
+		.filter_map(|this| this.text)
+		.collect::<String>();
+	// skip root element (`item`)
+	let content = subtree
+		.values()
+		.skip(1)
+		.filter_map(|child| child.text.as_deref())
+		.collect::<Vec<&str>>()
+		.join(" ");
+	let source = SpeechSource {
+		labelled_by: Some(labelled_by).filter(|label| !label.is_empty()),
+		content: Some(content).filter(|content| !content.is_empty()),
+		siblings: parent.and_then(|parent| parent.children_num),
+		shortcut,
+		..SpeechSource::new(&item)
+	};
+	let utterance = SpeechGenerator::new(&*settings.lock()?).generate(&source);
+	// like speech, braille never shows what is in a password field
+	let text = item.text.clone().filter(|_| {
+		item.states.contains(State::Editable) && item.role != Role::PasswordText
+	});
+	let braille = Braille {
+		name: utterance.get(SpeechField::Name).map(ToString::to_string),
+		role: Some(item.role),
+		text,
+		caret: None,
+	};
+	let mut commands = vec![Focus(item.object).into()];
+	if let Some(earcon) = utterance.earcon() {
+		commands.push(PlaySound(earcon.to_string()).into());
 	}
-	let role = item.role;
-	// This lets us read Fractal messages.
-	// But we don't know what the general method should be.
-	if role == Role::ListItem {
-		// skip root element (`item`)
-		for child in subtree.values().skip(1) {
-			if let Some(txt) = &child.text {
-				let _ = write!(utterance_buffer, "{txt}");
-			}
-		}
-	}
-	//there has to be a space between the accessible name of an object and its role, so insert it now
-	write!(utterance_buffer, " {}", role.name()).expect("Able to write to string");
-	Ok(vec![Focus(item.object).into(), Speak(utterance_buffer, Priority::Text).into()])
+	commands.push(Speak(utterance.to_string(), Priority::Text).into());
+	commands.push(braille.into());
+	Ok(commands)
 }
 
 #[tracing::instrument(ret)]
-pub async fn state_set(state_changed: CacheEvent<StateChangedEvent>) -> impl TryIntoCommands {
-	SetState {
-		item: state_changed.item.object.clone(),
+pub async fn state_set(
+	state_changed: CacheEvent<StateChangedEvent>,
+	AccessibleHistory(history): AccessibleHistory,
+	LiveSpeechSettings(settings): LiveSpeechSettings,
+) -> Result<Vec<OdiliaCommand>, OdiliaError> {
+	let item = &state_changed.item;
+	let mut commands = vec![SetState {
+		item: item.object.clone(),
 		state: state_changed.state,
 		enabled: state_changed.enabled,
 	}
+	.into()];
+	let is_focused = history.lock()?.iter().next() == Some(&item.object);
+	let generator = SpeechGenerator::new(&*settings.lock()?);
+	if is_focused && generator.verbosity(item.role).speaks_state_changes() {
+		let utterance = generator.state_change(state_changed.state, state_changed.enabled);
+		if let Some(earcon) = utterance.earcon() {
+			commands.push(PlaySound(earcon.to_string()).into());
+		}
+		if !utterance.is_empty() {
+			commands.push(Speak(utterance.to_string(), Priority::Text).into());
+		}
+	}
+	Ok(commands)
 }
 
 #[tracing::instrument(ret)]
@@ -100,21 +132,45 @@ pub async fn caret_moved(
 	caret_moved: CacheEvent<TextCaretMovedEvent>,
 	LastCaretPos(last_pos): LastCaretPos,
 	LastFocused(last_focus): LastFocused,
-) -> Option<OdiliaCommand> {
+) -> impl TryIntoCommands {
 	let pos = caret_moved
 		.position
 		.try_into()
 		.expect("Positive starting position for text insertion/deletion");
-	if let Some(ref text) = caret_moved.item.text {
-		if last_focus == caret_moved.item.object {
+	let speech = caret_speech(&caret_moved.item, pos, last_pos, &last_focus);
+	// the display follows the caret, whether or not anything is said about the move
+	let braille = caret_moved.item.text.clone().map(|text| Braille {
+		name: None,
+		role: None,
+		text: Some(text),
+		caret: Some(pos),
+	});
+	(speech, braille)
+}
+
+/// What to say when the caret in `item` moves from `last_pos` to `pos`.
+fn caret_speech(
+	item: &CacheItem,
+	pos: usize,
+	last_pos: usize,
+	last_focus: &AccessiblePrimitive,
+) -> Option<OdiliaCommand> {
+	if let Some(ref text) = item.text {
+		if *last_focus == item.object {
 			let min = min(pos, last_pos);
 			let max = max(pos, last_pos);
 			if min == 0 && max == 0 {
 				return None;
 			}
 			let text_slice = text.chars().skip(min).take(max - min).collect::<String>();
-			if !text_slice.is_empty() {
-				return Some(Speak(text_slice, Priority::Text).into());
+			let mut chars = text_slice.chars();
+			match (chars.next(), chars.next()) {
+				// a single character is named, so that symbols, spaces and capitals can be told apart
+				(Some(c), None) => return Some(SpeakChar(c, Priority::Text).into()),
+				(Some(_), Some(_)) => {
+					return Some(Speak(text_slice, Priority::Text).into())
+				}
+				(None, _) => {}
 			}
 		} else {
 			return Some(Speak(text.to_string(), Priority::Text).into());
@@ -122,3 +178,54 @@ pub async fn caret_moved(
 	}
 	None
 }
+
+#[tracing::instrument(ret)]
+pub async fn text_changed(
+	text_changed: CacheEvent<TextChangedEvent>,
+	AccessibleHistory(history): AccessibleHistory,
+	LiveSpeechSettings(settings): LiveSpeechSettings,
+	Symbols(symbols): Symbols,
+) -> Result<Vec<OdiliaCommand>, OdiliaError> {
+	let item = &text_changed.item;
+	// text changing anywhere else, like a terminal's output or a status label, is not typing
+	let is_focused = history.lock()?.iter().next() == Some(&item.object);
+	if !is_focused || !item.states.contains(State::Editable) {
+		return Ok(Vec::new());
+	}
+	let (echo_mode, password_stars, language) = {
+		let settings = settings.lock()?;
+		(settings.echo, settings.password_stars, settings.language.clone())
+	};
+	let start: usize = text_changed
+		.start_pos
+		.try_into()
+		.expect("Positive index for text insertion/deletion");
+	let changed = &text_changed.text;
+	let (said, caret) = match text_changed.operation {
+		Operation::Insert => {
+			let said = if item.role == Role::PasswordText {
+				Some(echo::password(changed))
+					.filter(|_| password_stars && echo_mode != EchoMode::None)
+			} else {
+				let text = item.text.as_deref().unwrap_or_default();
+				echo::inserted(echo_mode, &symbols, &language, text, start, changed)
+			};
+			(said, start + changed.chars().count())
+		}
+		Operation::Delete => {
+			let said = if item.role == Role::PasswordText {
+				Some(echo::password(changed)).filter(|_| password_stars)
+			} else {
+				echo::deleted(&symbols, &language, changed)
+			};
+			(said, start)
+		}
+	};
+	// `caret_moved` speaks whatever is between the old and new caret positions; moving the
+	// position here first leaves it nothing to repeat about this change
+	let mut commands = vec![CaretPos(caret).into()];
+	if let Some(said) = said.filter(|said| !said.is_empty()) {
+		commands.push(Speak(said, Priority::Text).into());
+	}
+	Ok(commands)
+}
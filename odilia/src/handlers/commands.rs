@@ -1,23 +1,201 @@
+use std::time::SystemTime;
+
+use odilia_braille::{ComputerBraille, Line, Viewport};
+use odilia_cache::Convertable;
 use odilia_common::{
-	command::{CaretPos, Focus, Speak, TryIntoCommands},
+	command::{
+		Activate, Braille, CaretPos, ControlSpeech, Focus, PlaySound, SetCaret, Speak,
+		SpeakChar, SpeechControl, Spell, TryIntoCommands,
+	},
 	errors::OdiliaError,
-	events::{StopSpeech, StructuralNavigation},
+	events::{PauseSpeech, ResumeSpeech, StopSpeech, StructuralNavigation},
+	settings::speech::CapitalIndication,
+};
+use ssip::Priority;
+
+use crate::{
+	speech::HistoryEntry,
+	state::{
+		AccessibilityBus, AccessibleHistory, ActiveAppName, BrailleOutput,
+		BrailleTranslation, BrailleView, Command, CurrentCaretPos, InputEvent,
+		LiveSpeechSettings, Pronunciation, SayAllState, Speech, SpokenHistory, Symbols,
+		UtteranceVoice,
+	},
 };
-use ssip::{Priority, Request};
 
-use crate::state::{AccessibleHistory, Command, CurrentCaretPos, InputEvent, Speech};
+/// How much higher than the normal pitch a capital letter is read, with [`CapitalIndication::Pitch`].
+const CAPITAL_PITCH_RAISE: i8 = 30;
+/// The sound icon played before a capital letter, with [`CapitalIndication::Icon`].
+/// This is one of the icons shipped with speech-dispatcher.
+const CAPITAL_SOUND_ICON: &str = "capital";
 
 #[tracing::instrument(ret, err, level = "debug")]
+#[allow(clippy::too_many_arguments)]
 pub async fn speak(
 	Command(Speak(text, priority)): Command<Speak>,
-	Speech(ssip): Speech,
+	Speech(speech): Speech,
+	Pronunciation(dictionary): Pronunciation,
+	ActiveAppName(app): ActiveAppName,
+	SpokenHistory(spoken): SpokenHistory,
+	AccessibleHistory(focused): AccessibleHistory,
+	Symbols(symbols): Symbols,
+	LiveSpeechSettings(settings): LiveSpeechSettings,
+	utterance: UtteranceVoice,
 ) -> Result<(), odilia_common::errors::OdiliaError> {
-	ssip.send(Request::SetPriority(priority)).await?;
-	ssip.send(Request::Speak).await?;
-	ssip.send(Request::SendLines(Vec::from([text]))).await?;
+	let source = focused.lock()?.iter().next().cloned();
+	spoken.lock()?.push(HistoryEntry {
+		text: text.clone(),
+		priority: priority.clone(),
+		time: SystemTime::now(),
+		source,
+	});
+	let text = dictionary.apply(&text, app.as_deref());
+	let text = {
+		let settings = settings.lock()?;
+		symbols.expand(
+			&text,
+			&utterance.voice.language,
+			settings.punctuation,
+			settings.numbers,
+		)
+		.into_owned()
+	};
+	utterance.apply(&*speech).await?;
+	speech.speak(priority, text).await
+}
+
+#[tracing::instrument(ret, err, level = "debug")]
+pub async fn speak_char(
+	Command(SpeakChar(character, priority)): Command<SpeakChar>,
+	Speech(speech): Speech,
+	Symbols(symbols): Symbols,
+	LiveSpeechSettings(settings): LiveSpeechSettings,
+	SpokenHistory(spoken): SpokenHistory,
+	AccessibleHistory(focused): AccessibleHistory,
+	utterance: UtteranceVoice,
+) -> Result<(), OdiliaError> {
+	let (capitals, pitch) = {
+		let settings = settings.lock()?;
+		(settings.capitals, settings.pitch)
+	};
+	let name = symbols.character(character, &utterance.voice.language).into_owned();
+	let source = focused.lock()?.iter().next().cloned();
+	spoken.lock()?.push(HistoryEntry {
+		text: name.clone(),
+		priority: priority.clone(),
+		time: SystemTime::now(),
+		source,
+	});
+	utterance.apply(&*speech).await?;
+	if !character.is_uppercase() {
+		return speech.speak(priority, name).await;
+	}
+	match capitals {
+		CapitalIndication::None => speech.speak(priority, name).await,
+		CapitalIndication::Say => speech.speak(priority, format!("cap {name}")).await,
+		CapitalIndication::Pitch => {
+			speech.set_pitch(pitch.saturating_add(CAPITAL_PITCH_RAISE).min(100))
+				.await?;
+			let spoken = speech.speak(priority, name).await;
+			// put the pitch back even if speaking failed
+			speech.set_pitch(pitch).await?;
+			spoken
+		}
+		CapitalIndication::Icon => {
+			speech.sound_icon(CAPITAL_SOUND_ICON.to_string()).await?;
+			speech.speak(priority, name).await
+		}
+	}
+}
+
+#[tracing::instrument(ret, err, level = "debug")]
+pub async fn spell(
+	Command(Spell(text, priority)): Command<Spell>,
+	Speech(speech): Speech,
+	SpokenHistory(spoken): SpokenHistory,
+	AccessibleHistory(focused): AccessibleHistory,
+	utterance: UtteranceVoice,
+) -> Result<(), OdiliaError> {
+	let source = focused.lock()?.iter().next().cloned();
+	spoken.lock()?.push(HistoryEntry {
+		text: text.clone(),
+		priority: priority.clone(),
+		time: SystemTime::now(),
+		source,
+	});
+	utterance.apply(&*speech).await?;
+	speech.spell(priority, text).await
+}
+
+#[tracing::instrument(ret, err, level = "debug")]
+pub async fn play_sound(
+	Command(PlaySound(name)): Command<PlaySound>,
+	Speech(speech): Speech,
+) -> Result<(), OdiliaError> {
+	speech.sound_icon(name).await
+}
+
+#[tracing::instrument(ret, err, level = "debug")]
+pub async fn show_braille(
+	Command(Braille { name, role, text, caret }): Command<Braille>,
+	BrailleOutput(output): BrailleOutput,
+	BrailleTranslation { translator, table }: BrailleTranslation,
+	BrailleView(view): BrailleView,
+) -> Result<(), OdiliaError> {
+	let line = Line::for_object(
+		name.as_deref(),
+		role.map(|role| role.name()),
+		text.as_deref(),
+		caret,
+	);
+	let columns = output.columns();
+	// a missing table should not leave the display blank
+	let viewport = Viewport::new(translator.as_ref(), &table, line.clone(), columns).or_else(
+		|error| {
+			tracing::warn!(%error, table, "Could not translate braille; using computer braille");
+			Viewport::new(&ComputerBraille, &table, line, columns)
+		},
+	)?;
+	let window = viewport.window(columns);
+	*view.lock()? = viewport;
+	output.write(window.text, window.cursor).await
+}
+
+#[tracing::instrument(ret, err, level = "debug")]
+pub async fn set_caret(
+	Command(SetCaret { item, offset }): Command<SetCaret>,
+	AccessibilityBus(connection): AccessibilityBus,
+) -> Result<(), OdiliaError> {
+	let accessible = item.into_accessible(&connection).await?;
+	let text = accessible.to_text().await?;
+	text.set_caret_offset(i32::try_from(offset)?).await?;
+	Ok(())
+}
+
+#[tracing::instrument(ret, err, level = "debug")]
+pub async fn activate(
+	Command(Activate(item)): Command<Activate>,
+	AccessibilityBus(connection): AccessibilityBus,
+) -> Result<(), OdiliaError> {
+	let accessible = item.into_accessible(&connection).await?;
+	let action = accessible.to_action().await?;
+	action.do_action(0).await?;
 	Ok(())
 }
 
+#[tracing::instrument(ret, err, level = "debug")]
+pub async fn control_speech(
+	Command(ControlSpeech(control)): Command<ControlSpeech>,
+	Speech(speech): Speech,
+	SayAllState(say_all): SayAllState,
+) -> Result<(), OdiliaError> {
+	// pausing keeps the place in a say all, but anything which throws speech away ends it
+	if matches!(control, SpeechControl::Stop | SpeechControl::CancelAll) {
+		say_all.stop()?;
+	}
+	speech.control(control).await
+}
+
 // TODO: move all cache logic behind the cache
 //#[tracing::instrument(ret, err)]
 //pub async fn set_state(
@@ -54,7 +232,17 @@ pub async fn new_caret_pos(
 
 #[tracing::instrument(ret)]
 pub async fn stop_speech(InputEvent(_): InputEvent<StopSpeech>) -> impl TryIntoCommands {
-	(Priority::Text, "Stop speech")
+	ControlSpeech(SpeechControl::CancelAll)
+}
+
+#[tracing::instrument(ret)]
+pub async fn pause_speech(InputEvent(_): InputEvent<PauseSpeech>) -> impl TryIntoCommands {
+	ControlSpeech(SpeechControl::Pause)
+}
+
+#[tracing::instrument(ret)]
+pub async fn resume_speech(InputEvent(_): InputEvent<ResumeSpeech>) -> impl TryIntoCommands {
+	ControlSpeech(SpeechControl::Resume)
 }
 
 #[tracing::instrument(ret)]
@@ -63,3 +251,65 @@ pub async fn structural_nav(
 ) -> impl TryIntoCommands {
 	(Priority::Text, format!("Navigate to {}, {:?}", sn.1, sn.0))
 }
+
+#[cfg(test)]
+mod tests {
+	use std::sync::{Arc, Mutex};
+
+	use atspi::Role;
+	use circular_queue::CircularQueue;
+	use futures_lite::future::block_on;
+	use odilia_common::{
+		command::Speak,
+		settings::{PronunciationSettings, SpeechSettings},
+	};
+	use odilia_tts::{Recorded, RecordingBackend};
+	use ssip::Priority;
+
+	use super::speak;
+	use crate::{
+		fixtures::{cache_item, default_voice},
+		speech::{
+			generator::{SpeechGenerator, SpeechSource},
+			PronunciationDictionary, SpeechHistory, SymbolTable,
+		},
+		state::{
+			AccessibleHistory, ActiveAppName, Command, LiveSpeechSettings,
+			Pronunciation, Speech, SpokenHistory, Symbols,
+		},
+	};
+
+	/// Speak `text` through the [`speak`] handler, as Odilia would, and return what the speech
+	/// backend was asked to do.
+	fn speak_through_handler(text: String) -> Vec<Recorded> {
+		let recorder = RecordingBackend::new();
+		block_on(speak(
+			Command(Speak(text, Priority::Text)),
+			Speech(Arc::new(recorder.clone())),
+			Pronunciation(Arc::new(PronunciationDictionary::new(
+				&PronunciationSettings::default(),
+			))),
+			ActiveAppName(None),
+			SpokenHistory(Arc::new(Mutex::new(SpeechHistory::with_capacity(4)))),
+			AccessibleHistory(Arc::new(Mutex::new(CircularQueue::with_capacity(4)))),
+			Symbols(Arc::new(SymbolTable::default())),
+			LiveSpeechSettings(Arc::new(Mutex::new(SpeechSettings::default()))),
+			default_voice(),
+		))
+		.expect("Recording never fails");
+		recorder.recorded()
+	}
+
+	#[test]
+	fn focusing_a_button_speaks_its_name_and_role() {
+		let button = cache_item(Role::Button, Some("OK"));
+		// what the focus handler asks to be spoken
+		let utterance = SpeechGenerator::new(&SpeechSettings::default())
+			.generate(&SpeechSource::new(&button))
+			.to_string();
+		assert_eq!(
+			speak_through_handler(utterance),
+			[Recorded::Speak(Priority::Text, "OK push button".to_string())]
+		);
+	}
+}
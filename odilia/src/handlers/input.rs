@@ -1,9 +1,550 @@
-use odilia_common::{command::TryIntoCommands, events::ChangeMode};
+use std::{iter, sync::atomic::Ordering};
+
+use odilia_braille::Routed;
+use odilia_common::{
+	command::{
+		Activate, ControlSpeech, OdiliaCommand, SetCaret, Speak, SpeakChar, SpeechControl,
+		Spell, TryIntoCommands,
+	},
+	errors::OdiliaError,
+	events::{
+		AdjustPitch, AdjustRate, AdjustVolume, Adjustment, CapsLockToggled, ChangeMode,
+		CycleOutputModule, CycleVerbosity, CycleVoice, Direction, Disable, Enable,
+		InputFeedback, Interrupt, KeyDescribed, KeySequenceStarted, KeybindingsListed,
+		LearningModeToggled, PanBraille, PassNextKey, ReadCharacter, RepeatLastSpeech,
+		ReviewSpeechHistory, RouteBraille, SayAll, SpellSpeechHistory, SpellWord,
+		SpellWordPhonetically,
+	},
+	protocol::Update,
+};
 use ssip::Priority;
 
-use crate::InputEvent;
+use crate::{
+	speech::{say_all, spelling},
+	state::{
+		ActiveAppName, BrailleOutput, BrailleView, CurrentCaretPos, DoublePress,
+		FocusedItem, InputServers, LiveSpeechSettings, Pronunciation, SayAllState, Speech,
+		SpokenHistory, Symbols, UtteranceVoice,
+	},
+	InputEvent,
+};
+
+/// How much a single rate, pitch or volume adjustment changes the value, out of -100 to 100.
+const ADJUSTMENT_STEP: i8 = 10;
 
 #[tracing::instrument(ret)]
-pub async fn change_mode(InputEvent(cm): InputEvent<ChangeMode>) -> impl TryIntoCommands {
+pub async fn change_mode(
+	InputEvent(cm): InputEvent<ChangeMode>,
+	InputServers(clients): InputServers,
+) -> impl TryIntoCommands {
+	// every input server switches keymaps, not only the one the change came from
+	clients.update(Update::Mode(cm.0));
 	(Priority::Text, format!("{:?} mode", cm.0))
 }
+
+/// Features are not actually turned on and off yet, but input servers are told which are on.
+#[tracing::instrument(ret)]
+pub async fn enable_feature(
+	InputEvent(Enable(feature)): InputEvent<Enable>,
+	InputServers(clients): InputServers,
+) -> impl TryIntoCommands {
+	clients.set_feature(feature, true);
+}
+
+#[tracing::instrument(ret)]
+pub async fn disable_feature(
+	InputEvent(Disable(feature)): InputEvent<Disable>,
+	InputServers(clients): InputServers,
+) -> impl TryIntoCommands {
+	clients.set_feature(feature, false);
+}
+
+#[tracing::instrument(ret)]
+pub async fn pass_next_key(_: InputEvent<PassNextKey>) -> impl TryIntoCommands {
+	(Priority::Text, "Pass next key".to_string())
+}
+
+/// What to say when an input server reports `feedback` about itself.
+#[must_use]
+pub fn announcement(feedback: InputFeedback) -> String {
+	match feedback {
+		InputFeedback::CapsLockToggled(CapsLockToggled(on)) => match on {
+			Some(true) => "Caps lock on".to_string(),
+			Some(false) => "Caps lock off".to_string(),
+			None => "Caps lock".to_string(),
+		},
+		InputFeedback::LearningModeToggled(LearningModeToggled(on)) => {
+			let state =
+				if on { "Keyboard learning on" } else { "Keyboard learning off" };
+			state.to_string()
+		}
+		InputFeedback::KeyDescribed(KeyDescribed(description)) => description,
+		InputFeedback::KeybindingsListed(KeybindingsListed(bindings)) => {
+			bindings.join("\n")
+		}
+		InputFeedback::KeySequenceStarted(KeySequenceStarted(combo)) => combo,
+		InputFeedback::KeySequenceCancelled(_) => "Sequence cancelled".to_string(),
+	}
+}
+
+fn adjust(value: i8, adjustment: Adjustment) -> i8 {
+	match adjustment {
+		Adjustment::Increase => value.saturating_add(ADJUSTMENT_STEP).min(100),
+		Adjustment::Decrease => value.saturating_sub(ADJUSTMENT_STEP).max(-100),
+	}
+}
+
+/// Find the item after (or before) `current` in `items`, wrapping around at either end.
+/// If `current` is not in the list, start from the beginning (or end).
+fn cycle<'a>(items: &'a [String], current: &str, direction: &Direction) -> Option<&'a String> {
+	let len = items.len();
+	let next = match (items.iter().position(|item| item == current), direction) {
+		(Some(idx), Direction::Forward) => (idx + 1) % len,
+		(Some(idx), Direction::Backward) => (idx + len - 1) % len,
+		(None, Direction::Forward) => 0,
+		(None, Direction::Backward) => len.checked_sub(1)?,
+	};
+	items.get(next)
+}
+
+#[tracing::instrument(ret, err)]
+pub async fn adjust_rate(
+	InputEvent(AdjustRate(adjustment)): InputEvent<AdjustRate>,
+	Speech(speech): Speech,
+	LiveSpeechSettings(settings): LiveSpeechSettings,
+) -> Result<(Priority, String), OdiliaError> {
+	let rate = {
+		let mut settings = settings.lock()?;
+		settings.rate = adjust(settings.rate, adjustment);
+		settings.rate
+	};
+	speech.set_rate(rate).await?;
+	Ok((Priority::Text, format!("Rate {rate}")))
+}
+
+#[tracing::instrument(ret, err)]
+pub async fn adjust_pitch(
+	InputEvent(AdjustPitch(adjustment)): InputEvent<AdjustPitch>,
+	Speech(speech): Speech,
+	LiveSpeechSettings(settings): LiveSpeechSettings,
+) -> Result<(Priority, String), OdiliaError> {
+	let pitch = {
+		let mut settings = settings.lock()?;
+		settings.pitch = adjust(settings.pitch, adjustment);
+		settings.pitch
+	};
+	speech.set_pitch(pitch).await?;
+	Ok((Priority::Text, format!("Pitch {pitch}")))
+}
+
+#[tracing::instrument(ret, err)]
+pub async fn adjust_volume(
+	InputEvent(AdjustVolume(adjustment)): InputEvent<AdjustVolume>,
+	Speech(speech): Speech,
+	LiveSpeechSettings(settings): LiveSpeechSettings,
+) -> Result<(Priority, String), OdiliaError> {
+	let volume = {
+		let mut settings = settings.lock()?;
+		settings.volume = adjust(settings.volume, adjustment);
+		settings.volume
+	};
+	speech.set_volume(volume).await?;
+	Ok((Priority::Text, format!("Volume {volume}")))
+}
+
+#[tracing::instrument(ret, err)]
+pub async fn cycle_voice(
+	InputEvent(CycleVoice(direction)): InputEvent<CycleVoice>,
+	Speech(speech): Speech,
+	LiveSpeechSettings(settings): LiveSpeechSettings,
+) -> Result<(Priority, String), OdiliaError> {
+	let voices = speech.list_voices().await?;
+	let voice = {
+		let mut settings = settings.lock()?;
+		let Some(voice) = cycle(&voices, &settings.person, &direction) else {
+			return Ok((Priority::Text, "No voices available".to_string()));
+		};
+		settings.person.clone_from(voice);
+		voice.clone()
+	};
+	speech.set_voice(voice.clone()).await?;
+	Ok((Priority::Text, voice))
+}
+
+#[tracing::instrument(ret, err)]
+pub async fn cycle_output_module(
+	InputEvent(CycleOutputModule(direction)): InputEvent<CycleOutputModule>,
+	Speech(speech): Speech,
+	LiveSpeechSettings(settings): LiveSpeechSettings,
+) -> Result<(Priority, String), OdiliaError> {
+	let modules = speech.list_output_modules().await?;
+	let module = {
+		let mut settings = settings.lock()?;
+		let Some(module) = cycle(&modules, &settings.module, &direction) else {
+			return Ok((Priority::Text, "No synthesizers available".to_string()));
+		};
+		settings.module.clone_from(module);
+		module.clone()
+	};
+	speech.set_output_module(module.clone()).await?;
+	Ok((Priority::Text, module))
+}
+
+#[tracing::instrument(ret, err)]
+pub async fn cycle_verbosity(
+	_: InputEvent<CycleVerbosity>,
+	LiveSpeechSettings(settings): LiveSpeechSettings,
+) -> Result<(Priority, String), OdiliaError> {
+	let verbosity = {
+		let mut settings = settings.lock()?;
+		settings.verbosity = settings.verbosity.next();
+		settings.verbosity
+	};
+	Ok((Priority::Text, format!("{verbosity:?} verbosity")))
+}
+
+#[tracing::instrument(ret, err)]
+pub async fn review_speech_history(
+	InputEvent(ReviewSpeechHistory(direction)): InputEvent<ReviewSpeechHistory>,
+	SpokenHistory(history): SpokenHistory,
+	Speech(speech): Speech,
+	Pronunciation(dictionary): Pronunciation,
+	ActiveAppName(app): ActiveAppName,
+) -> Result<(), OdiliaError> {
+	let entry = {
+		let mut history = history.lock()?;
+		match direction {
+			Direction::Backward => history.older().cloned(),
+			Direction::Forward => history.newer().cloned(),
+		}
+	};
+	let text = match (entry, direction) {
+		(Some(entry), _) => dictionary.apply(&entry.text, app.as_deref()).into_owned(),
+		(None, Direction::Backward) => "Start of history".to_string(),
+		(None, Direction::Forward) => "End of history".to_string(),
+	};
+	// spoken directly, rather than through a `Speak` command, so reviewing the history does not
+	// add to it
+	speech.cancel().await?;
+	speech.speak(Priority::Text, text).await
+}
+
+#[tracing::instrument(ret, err)]
+pub async fn repeat_last_speech(
+	_: InputEvent<RepeatLastSpeech>,
+	SpokenHistory(history): SpokenHistory,
+	Speech(speech): Speech,
+	Pronunciation(dictionary): Pronunciation,
+	ActiveAppName(app): ActiveAppName,
+) -> Result<(), OdiliaError> {
+	let Some(entry) = history.lock()?.latest().cloned() else {
+		return speech
+			.speak(Priority::Text, "Nothing has been said".to_string())
+			.await;
+	};
+	speech.cancel().await?;
+	speech.speak(entry.priority, dictionary.apply(&entry.text, app.as_deref()).into_owned())
+		.await
+}
+
+#[tracing::instrument(ret, err)]
+pub async fn spell_speech_history(
+	_: InputEvent<SpellSpeechHistory>,
+	SpokenHistory(history): SpokenHistory,
+	Speech(speech): Speech,
+) -> Result<(), OdiliaError> {
+	let Some(entry) = history.lock()?.current().cloned() else {
+		return speech
+			.speak(Priority::Text, "Nothing has been said".to_string())
+			.await;
+	};
+	// spelled directly, rather than through a `Spell` command, so the entry under the review
+	// cursor stays where it is
+	speech.cancel().await?;
+	speech.spell(Priority::Text, entry.text).await
+}
+
+#[tracing::instrument(ret, err)]
+pub async fn read_character(
+	_: InputEvent<ReadCharacter>,
+	DoublePress(double): DoublePress,
+	FocusedItem(item): FocusedItem,
+	CurrentCaretPos(caret): CurrentCaretPos,
+	Symbols(symbols): Symbols,
+	utterance: UtteranceVoice,
+) -> Result<OdiliaCommand, OdiliaError> {
+	let caret = caret.load(Ordering::Relaxed);
+	let Some(character) = item
+		.and_then(|item| item.text)
+		.and_then(|text| spelling::character_at(&text, caret))
+	else {
+		return Ok(Speak("blank".to_string(), Priority::Text).into());
+	};
+	if !double {
+		return Ok(SpeakChar(character, Priority::Text).into());
+	}
+	let said = spelling::spell_phonetically(
+		&character.to_string(),
+		&symbols,
+		&utterance.voice.language,
+	);
+	Ok(Speak(said, Priority::Text).into())
+}
+
+#[tracing::instrument(ret)]
+pub async fn spell_word(
+	_: InputEvent<SpellWord>,
+	FocusedItem(item): FocusedItem,
+	CurrentCaretPos(caret): CurrentCaretPos,
+) -> OdiliaCommand {
+	let caret = caret.load(Ordering::Relaxed);
+	match item
+		.and_then(|item| item.text)
+		.and_then(|text| spelling::word_at(&text, caret))
+	{
+		// spelled by the synthesizer, in SSIP's spelling mode
+		Some(word) => Spell(word, Priority::Text).into(),
+		None => Speak("blank".to_string(), Priority::Text).into(),
+	}
+}
+
+#[tracing::instrument(ret, err)]
+pub async fn spell_word_phonetically(
+	_: InputEvent<SpellWordPhonetically>,
+	FocusedItem(item): FocusedItem,
+	CurrentCaretPos(caret): CurrentCaretPos,
+	Symbols(symbols): Symbols,
+	utterance: UtteranceVoice,
+) -> Result<OdiliaCommand, OdiliaError> {
+	let caret = caret.load(Ordering::Relaxed);
+	let Some(word) = item
+		.and_then(|item| item.text)
+		.and_then(|text| spelling::word_at(&text, caret))
+	else {
+		return Ok(Speak("blank".to_string(), Priority::Text).into());
+	};
+	let said = spelling::spell_phonetically(&word, &symbols, &utterance.voice.language);
+	Ok(Speak(said, Priority::Text).into())
+}
+
+#[tracing::instrument(ret, err)]
+#[allow(clippy::too_many_arguments)]
+pub async fn say_all(
+	_: InputEvent<SayAll>,
+	FocusedItem(item): FocusedItem,
+	CurrentCaretPos(caret): CurrentCaretPos,
+	LiveSpeechSettings(settings): LiveSpeechSettings,
+	Pronunciation(dictionary): Pronunciation,
+	ActiveAppName(app): ActiveAppName,
+	Symbols(symbols): Symbols,
+	SayAllState(say_all): SayAllState,
+	Speech(speech): Speech,
+	utterance: UtteranceVoice,
+) -> Result<(), OdiliaError> {
+	let Some((object, text)) = item.and_then(|item| Some((item.object, item.text?))) else {
+		return speech.speak(Priority::Text, "Nothing to read".to_string()).await;
+	};
+	let (unit, punctuation, numbers) = {
+		let settings = settings.lock()?;
+		(settings.say_all_by, settings.punctuation, settings.numbers)
+	};
+	let language = &utterance.voice.language;
+	let mut chunks = say_all::chunks(&text, caret.load(Ordering::Relaxed), unit);
+	for chunk in &mut chunks {
+		let text = dictionary.apply(&chunk.text, app.as_deref());
+		chunk.text = symbols.expand(&text, language, punctuation, numbers).into_owned();
+	}
+	let mut reading = say_all::SayAll::new(object, chunks);
+	let first = iter::from_fn(|| reading.next_message())
+		.take(say_all::LOOKAHEAD)
+		.collect::<Vec<String>>();
+	if first.is_empty() {
+		return speech.speak(Priority::Text, "Nothing to read".to_string()).await;
+	}
+	// whatever was being read is thrown away first, and the new reading is stored before anything
+	// of it is spoken, so the first mark has something to follow
+	speech.cancel().await?;
+	say_all.start(reading)?;
+	utterance.apply(&*speech).await?;
+	for ssml in first {
+		speech.speak_ssml(Priority::Text, ssml).await?;
+	}
+	Ok(())
+}
+
+#[tracing::instrument(ret, err)]
+pub async fn interrupt(
+	_: InputEvent<Interrupt>,
+	SayAllState(say_all): SayAllState,
+) -> Result<Vec<OdiliaCommand>, OdiliaError> {
+	// other speech (like typing echo) is left alone; only say all is stopped by any key
+	if !say_all.stop()? {
+		return Ok(Vec::new());
+	}
+	Ok(vec![ControlSpeech(SpeechControl::CancelAll).into()])
+}
+
+#[tracing::instrument(ret, err)]
+pub async fn pan_braille(
+	InputEvent(PanBraille(direction)): InputEvent<PanBraille>,
+	BrailleView(view): BrailleView,
+	BrailleOutput(output): BrailleOutput,
+) -> Result<(), OdiliaError> {
+	let columns = output.columns();
+	let window = {
+		let mut view = view.lock()?;
+		if !view.pan(&direction, columns) {
+			return Ok(());
+		}
+		view.window(columns)
+	};
+	output.write(window.text, window.cursor).await
+}
+
+#[tracing::instrument(ret, err)]
+pub async fn route_braille(
+	InputEvent(RouteBraille(cell)): InputEvent<RouteBraille>,
+	BrailleView(view): BrailleView,
+	FocusedItem(item): FocusedItem,
+) -> Result<Option<OdiliaCommand>, OdiliaError> {
+	let Some(item) = item else {
+		return Ok(None);
+	};
+	let routed = view.lock()?.route(cell);
+	Ok(routed.map(|routed| match routed {
+		Routed::Text(offset) => SetCaret { item: item.object, offset }.into(),
+		Routed::Object => Activate(item.object).into(),
+	}))
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::{
+		atomic::AtomicUsize,
+		{Arc, Mutex},
+	};
+
+	use atspi::Role;
+	use futures_lite::future::block_on;
+	use odilia_common::{
+		cache::AccessiblePrimitive,
+		command::SpeechControl,
+		events::{Adjustment, Direction, SayAll},
+		settings::{PronunciationSettings, SpeechSettings},
+	};
+	use odilia_input::Clients;
+	use odilia_tts::{Recorded, RecordingBackend};
+	use ssip::Priority;
+
+	use super::{adjust, cycle, say_all, ADJUSTMENT_STEP};
+	use crate::{
+		fixtures::{cache_item, default_voice},
+		speech::{say_all as reading, PronunciationDictionary, SymbolTable},
+		state::{
+			ActiveAppName, CurrentCaretPos, FocusedItem, InputEvent,
+			LiveSpeechSettings, Pronunciation, SayAllState, Speech, Symbols,
+		},
+	};
+
+	#[test]
+	fn adjustments_step_and_clamp() {
+		// rate, pitch and volume all go from -100 to 100
+		assert_eq!(adjust(0, Adjustment::Increase), ADJUSTMENT_STEP);
+		assert_eq!(adjust(0, Adjustment::Decrease), -ADJUSTMENT_STEP);
+		assert_eq!(adjust(95, Adjustment::Increase), 100);
+		assert_eq!(adjust(100, Adjustment::Increase), 100);
+		assert_eq!(adjust(-95, Adjustment::Decrease), -100);
+		assert_eq!(adjust(-100, Adjustment::Decrease), -100);
+		// out of range values, like those from a hand edited configuration, are brought back
+		assert_eq!(adjust(i8::MAX, Adjustment::Increase), 100);
+		assert_eq!(adjust(i8::MIN, Adjustment::Decrease), -100);
+	}
+
+	#[test]
+	fn cycling_wraps_around() {
+		// voices and output modules are cycled through the same way
+		let voices = ["Alice", "Bob", "Carol"].map(String::from);
+		assert_eq!(
+			cycle(&voices, "Alice", &Direction::Forward).map(String::as_str),
+			Some("Bob")
+		);
+		assert_eq!(
+			cycle(&voices, "Carol", &Direction::Forward).map(String::as_str),
+			Some("Alice")
+		);
+		assert_eq!(
+			cycle(&voices, "Alice", &Direction::Backward).map(String::as_str),
+			Some("Carol")
+		);
+		assert_eq!(
+			cycle(&voices, "Bob", &Direction::Backward).map(String::as_str),
+			Some("Alice")
+		);
+	}
+
+	#[test]
+	fn cycling_from_an_unknown_item() {
+		let modules = ["espeak-ng", "rhvoice"].map(String::from);
+		assert_eq!(
+			cycle(&modules, "festival", &Direction::Forward).map(String::as_str),
+			Some("espeak-ng")
+		);
+		assert_eq!(
+			cycle(&modules, "festival", &Direction::Backward).map(String::as_str),
+			Some("rhvoice")
+		);
+		assert_eq!(cycle(&[], "espeak-ng", &Direction::Forward), None);
+		assert_eq!(cycle(&[], "espeak-ng", &Direction::Backward), None);
+	}
+
+	#[test]
+	fn say_all_while_already_reading() {
+		let earlier = AccessiblePrimitive {
+			sender: ":1.2".to_string(),
+			id: "/org/a11y/atspi/accessible/2".to_string(),
+		};
+		let state = reading::Reading::new(Clients::default());
+		state.start(reading::SayAll::new(
+			earlier,
+			reading::chunks("Not read yet.", 0, SpeechSettings::default().say_all_by),
+		))
+		.expect("Not poisoned");
+		let mut item = cache_item(Role::Paragraph, None);
+		item.text = Some("First. Second.".to_string());
+		let recorder = RecordingBackend::new();
+		block_on(say_all(
+			InputEvent(SayAll),
+			FocusedItem(Some(item.clone())),
+			CurrentCaretPos(Arc::new(AtomicUsize::new(0))),
+			LiveSpeechSettings(Arc::new(Mutex::new(SpeechSettings::default()))),
+			Pronunciation(Arc::new(PronunciationDictionary::new(
+				&PronunciationSettings::default(),
+			))),
+			ActiveAppName(None),
+			Symbols(Arc::new(SymbolTable::default())),
+			SayAllState(state.clone()),
+			Speech(Arc::new(recorder.clone())),
+			default_voice(),
+		))
+		.expect("Recording never fails");
+		// the earlier reading is stopped before any of the new one is spoken
+		assert_eq!(
+			recorder.recorded(),
+			[
+				Recorded::Control(SpeechControl::CancelAll),
+				Recorded::SpeakSsml(
+					Priority::Text,
+					"<speak><mark name=\"say-all:0\"/>First.</speak>".to_string()
+				),
+				Recorded::SpeakSsml(
+					Priority::Text,
+					"<speak><mark name=\"say-all:7\"/>Second.<mark name=\"say-all:end\"/></speak>"
+						.to_string()
+				),
+			]
+		);
+		// and marks are followed in the new one
+		let current = state.lock().expect("Not poisoned").clone();
+		assert_eq!(current.map(|reading| reading.item), Some(item.object));
+	}
+}
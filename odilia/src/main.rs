@@ -10,19 +10,23 @@
 )]
 
 mod cli;
+#[cfg(test)]
+mod fixtures;
 mod handlers;
 mod logging;
+mod speech;
 mod state;
 mod tower;
 use std::{
 	env,
+	future::Future,
 	path::{Path, PathBuf},
 	process::{exit, Child, Command as ProcCommand},
 	sync::Arc,
 	time::Duration,
 };
 
-use async_channel::bounded;
+use async_channel::{bounded, Receiver, Sender};
 use async_executor::StaticExecutor;
 use async_signal::{Signal, Signals};
 use atspi::events::{document, object};
@@ -33,27 +37,43 @@ use futures_lite::{
 };
 use futures_util::FutureExt as FatExt;
 use handlers::{
-	caret_moved, caret_moved_update_state, change_mode, doc_loaded, focused, new_caret_pos,
-	new_focused_item, speak, state_set, stop_speech, structural_nav,
+	activate, adjust_pitch, adjust_rate, adjust_volume, announcement, caret_moved,
+	caret_moved_update_state, change_mode, control_speech, cycle_output_module,
+	cycle_verbosity, cycle_voice, disable_feature, doc_loaded, enable_feature, focused,
+	interrupt, new_caret_pos, new_focused_item, pan_braille, pass_next_key, pause_speech,
+	play_sound, read_character, repeat_last_speech, resume_speech, review_speech_history,
+	route_braille, say_all, set_caret, show_braille, speak, speak_char, spell,
+	spell_speech_history, spell_word, spell_word_phonetically, state_set, stop_speech,
+	structural_nav, text_changed,
+};
+use odilia_braille::{
+	create_brlapi_connection, protocol::Packet, supervise_brlapi_commands, BrailleDisplay,
+	BrlApiDisplay, KeyBindings, MemoryDisplay,
 };
 use odilia_cache::{cache_handler_task, Cache, CacheActor};
 use odilia_common::{
 	command::TryIntoCommands,
 	errors::OdiliaError,
-	events::ScreenReaderEvent,
-	settings::{ApplicationConfig, InputMethod},
+	events::{InputFeedback, ScreenReaderEvent},
+	settings::{ApplicationConfig, InputMethod, PronunciationSettings, SymbolSettings},
 };
 use odilia_notify::listen_to_dbus_notifications;
+use odilia_tts::{SpeechEvent, SsipBackend};
+use serde::de::DeserializeOwned;
 use smol_cancellation_token::CancellationToken;
 use ssip::Priority;
 use tracing::Instrument;
 
 use crate::{
 	cli::Args,
+	speech::{say_all::Progress, PronunciationDictionary, SymbolTable},
 	state::{InputEvent, ScreenReaderState},
 	tower::Handlers,
 };
 
+/// The size of the display braille is kept in when there is no real one.
+const FALLBACK_BRAILLE_COLUMNS: usize = 40;
+
 fn find_it<P>(exe_name: P) -> Option<PathBuf>
 where
 	P: AsRef<Path>,
@@ -93,6 +113,7 @@ where
 }
 
 /// Try to spawn the `odilia-input-server-*` binary.
+/// Its keybindings are sent over the input socket once it connects, like any other update.
 #[tracing::instrument(skip(input), err)]
 fn try_spawn_input_server(input: &InputMethod) -> Result<Child, OdiliaError> {
 	let bin_name = format!(
@@ -148,6 +169,56 @@ async fn notifications_monitor(
 	}
 	Ok(())
 }
+/// Follow speech-dispatcher's progress through what Odilia has asked it to say.
+#[tracing::instrument(skip_all, err)]
+async fn speech_events_monitor(
+	state: Arc<ScreenReaderState>,
+	events: Receiver<SpeechEvent>,
+	shutdown: CancellationToken,
+) -> Result<(), OdiliaError> {
+	loop {
+		let Ok(maybe_event) = or_cancel(events.recv(), &shutdown).await else {
+			tracing::debug!("Shutting down speech event task.");
+			break;
+		};
+		let Ok(event) = maybe_event else {
+			tracing::debug!("Speech events are no longer being sent.");
+			break;
+		};
+		match event {
+			SpeechEvent::IndexMark(mark) => {
+				let Some(progress) = Progress::from_mark(&mark) else {
+					continue;
+				};
+				if let Err(e) = state.say_all_progress(progress).await {
+					tracing::error!(error = ?e, "Could not continue reading");
+				}
+			}
+			SpeechEvent::Reconnected => {
+				if let Err(e) = state.speech_reconnected() {
+					tracing::error!(error = ?e, "Could not reset the active voice");
+				}
+			}
+			_ => {}
+		}
+	}
+	Ok(())
+}
+/// Announce what input servers report about themselves, like a key described in learning mode.
+#[tracing::instrument(skip_all, err)]
+async fn input_feedback_monitor(
+	state: Arc<ScreenReaderState>,
+	feedback: Receiver<InputFeedback>,
+	shutdown: CancellationToken,
+) -> Result<(), OdiliaError> {
+	while let Ok(Ok(feedback)) = or_cancel(feedback.recv(), &shutdown).await {
+		if !state.say(Priority::Text, announcement(feedback)).await {
+			tracing::error!("Could not announce input feedback");
+		}
+	}
+	tracing::debug!("Shutting down input feedback task.");
+	Ok(())
+}
 #[tracing::instrument(skip_all, err)]
 async fn sigterm_signal_watcher(
 	token: CancellationToken,
@@ -169,6 +240,117 @@ async fn sigterm_signal_watcher(
 	Ok(())
 }
 
+/// Keep reading accessibility events from the bus, and send them on to `events`.
+///
+/// There is a reason we are not reading from the event stream directly.
+/// This `MessageStream` can only store 64 events in its buffer.
+/// And, even if it could store more (it can via options), `zbus` specifically states that:
+/// You must ensure a `MessageStream` is continuously polled or you will experience hangs.
+/// So, we continually poll it here, then receive it on the other end.
+/// Additioanlly, since sending is not async, but simply errors when there is an issue, this will
+/// help us avoid hangs.
+async fn forward_atspi_events(
+	state: Arc<ScreenReaderState>,
+	events: Sender<Result<atspi::Event, atspi::AtspiError>>,
+	shutdown: CancellationToken,
+) {
+	let mut stream = state.atspi.event_stream();
+	std::pin::pin!(&mut stream);
+	loop {
+		let maybe = or_cancel(stream.next(), &shutdown).await;
+		let Ok(maybe_ev) = maybe else {
+			return;
+		};
+		if let Some(ev) = maybe_ev {
+			if let Err(e) = events.try_send(ev) {
+				tracing::error!("Error sending event across channel! {e:?}");
+			}
+		}
+	}
+}
+
+/// Every handler Odilia has, for commands, accessibility events, and input.
+fn handlers(state: Arc<ScreenReaderState>) -> Handlers {
+	Handlers::new(state)
+		.command_listener(speak)
+		.command_listener(spell)
+		.command_listener(speak_char)
+		.command_listener(play_sound)
+		.command_listener(show_braille)
+		.command_listener(set_caret)
+		.command_listener(activate)
+		.command_listener(new_focused_item)
+		.command_listener(new_caret_pos)
+		.command_listener(control_speech)
+		//.command_listener(set_state)
+		.atspi_listener(doc_loaded)
+		.atspi_listener(caret_moved_update_state)
+		.atspi_listener(caret_moved)
+		.atspi_listener(focused)
+		.atspi_listener(state_set)
+		.atspi_listener(text_changed)
+		.input_listener(stop_speech)
+		.input_listener(pause_speech)
+		.input_listener(resume_speech)
+		.input_listener(change_mode)
+		.input_listener(enable_feature)
+		.input_listener(disable_feature)
+		.input_listener(pass_next_key)
+		.input_listener(structural_nav)
+		.input_listener(adjust_rate)
+		.input_listener(adjust_pitch)
+		.input_listener(adjust_volume)
+		.input_listener(cycle_voice)
+		.input_listener(cycle_output_module)
+		.input_listener(cycle_verbosity)
+		.input_listener(review_speech_history)
+		.input_listener(repeat_last_speech)
+		.input_listener(spell_speech_history)
+		.input_listener(read_character)
+		.input_listener(spell_word)
+		.input_listener(spell_word_phonetically)
+		.input_listener(say_all)
+		.input_listener(pan_braille)
+		.input_listener(route_braille)
+		.input_listener(interrupt)
+}
+
+/// Connect to brltty, and start the task which owns the connection; keys pressed on the display
+/// are sent to `events`.
+/// Without a display, braille is kept in memory, and the task does nothing.
+async fn braille_output(
+	config: &ApplicationConfig,
+	events: Sender<ScreenReaderEvent>,
+	shutdown: CancellationToken,
+) -> (Arc<dyn BrailleDisplay>, impl Future<Output = Result<(), OdiliaError>>) {
+	// like SSIP, the connection to brltty is owned by its own task, which these packets are sent to
+	let (requests, packets) = bounded::<Packet>(128);
+	let brlapi = create_brlapi_connection().await;
+	let display: Arc<dyn BrailleDisplay> = match &brlapi {
+		Ok(connection) => Arc::new(BrlApiDisplay::new(requests, connection)),
+		Err(e) => {
+			tracing::info!(error = %e, "No braille display found; braille is kept in memory");
+			Arc::new(MemoryDisplay::new(FALLBACK_BRAILLE_COLUMNS))
+		}
+	};
+	let bindings = KeyBindings::with_overrides(&config.braille.keys).unwrap_or_else(|e| {
+		tracing::error!(error = %e, "Invalid braille key bindings; the defaults will be used");
+		KeyBindings::default()
+	});
+	let task = async move {
+		match brlapi {
+			Ok(connection) => {
+				supervise_brlapi_commands(
+					connection, packets, events, bindings, shutdown,
+				)
+				.await
+			}
+			Err(_) => Ok(()),
+		}
+	};
+	(display, task)
+}
+
 static EXECUTOR: StaticExecutor = StaticExecutor::new();
 
 fn main() -> Result<(), OdiliaError> {
@@ -199,14 +381,28 @@ async fn async_main() -> Result<(), OdiliaError> {
 	// this is the channel which handles all SSIP commands. If SSIP is not allowed to operate on a separate task, then waiting for the receiving message can block other long-running operations like structural navigation.
 	// Although in the future, this may possibly be resolved through a proper cache, I think it still makes sense to separate SSIP's IO operations to a separate task.
 	//  it is very important that this is *never* full, since it can cause deadlocking if the other task sending the request is working with zbus.
-	let (ssip_req_tx, ssip_req_rx) = bounded::<ssip_client_async::Request>(128);
+	let (ssip_req_tx, ssip_req_rx) = bounded::<odilia_tts::SsipMessage>(128);
 	let (ev_tx, ev_rx) = bounded::<Result<atspi::Event, atspi::AtspiError>>(10_000);
 	let (input_tx, input_rx) = bounded::<ScreenReaderEvent>(255);
+	let (feedback_tx, feedback_rx) = bounded::<InputFeedback>(255);
+	// event notifications from speech-dispatcher; these are dropped rather than waited on if this is full
+	let (speech_ev_tx, speech_ev_rx) = bounded::<SpeechEvent>(128);
 	// Initialize state
 	// lots of space for caching just in case...
 	let (cache_tx, cache_rx) = bounded(4096);
 	let cache = CacheActor::new(cache_tx);
-	let state = Arc::new(ScreenReaderState::new(ssip_req_tx, config, cache).await?);
+	let speech = Arc::new(SsipBackend::new(ssip_req_tx));
+	// keys on the braille display are handled just like those from input servers
+	let (braille, braille_task) =
+		braille_output(&config, input_tx.clone(), token.clone()).await;
+	let pronunciation = PronunciationDictionary::new(
+		&load_extra_config::<PronunciationSettings>("pronunciation"),
+	);
+	let symbols = SymbolTable::new(&load_extra_config::<SymbolSettings>("symbols"));
+	let state = Arc::new(
+		ScreenReaderState::new(speech, braille, config, pronunciation, symbols, cache)
+			.await?,
+	);
 	let ssip = odilia_tts::create_ssip_client().await?;
 
 	if state.say(Priority::Message, "Welcome to Odilia!".to_string()).await {
@@ -221,6 +417,7 @@ async fn async_main() -> Result<(), OdiliaError> {
 	(
 		state.register_event::<object::StateChangedEvent>(),
 		state.register_event::<object::TextCaretMovedEvent>(),
+		state.register_event::<object::TextChangedEvent>(),
 		state.register_event::<document::LoadCompleteEvent>(),
 		//TODO: we don't handle these yet!
 		// state.add_cache_match_rule(),
@@ -228,58 +425,35 @@ async fn async_main() -> Result<(), OdiliaError> {
 		.try_join()
 		.await?;
 
-	// load handlers
-	let handlers = Handlers::new(state.clone())
-		.command_listener(speak)
-		.command_listener(new_focused_item)
-		.command_listener(new_caret_pos)
-		//.command_listener(set_state)
-		.atspi_listener(doc_loaded)
-		.atspi_listener(caret_moved_update_state)
-		.atspi_listener(caret_moved)
-		.atspi_listener(focused)
-		.atspi_listener(state_set)
-		.input_listener(stop_speech)
-		.input_listener(change_mode)
-		.input_listener(structural_nav);
+	let handlers = handlers(Arc::clone(&state));
 
-	let ssip_event_receiver =
-		odilia_tts::handle_ssip_commands(ssip, ssip_req_rx, token.clone());
+	let ssip_event_receiver = odilia_tts::supervise_ssip_commands(
+		ssip,
+		ssip_req_rx,
+		speech_ev_tx,
+		Arc::clone(&state.speech_settings),
+		token.clone(),
+	);
+	let speech_event_task =
+		speech_events_monitor(Arc::clone(&state), speech_ev_rx, token.clone());
 	let notification_task = notifications_monitor(Arc::clone(&state), token.clone());
-	let mut stream = state.atspi.event_stream();
-	// There is a reason we are not reading from the event stream directly.
-	// This `MessageStream` can only store 64 events in its buffer.
-	// And, even if it could store more (it can via options), `zbus` specifically states that:
-	// You must ensure a MessageStream is continuously polled or you will experience hangs.
-	// So, we continually poll it here, then receive it on the other end.
-	// Additioanlly, since sending is not async, but simply errors when there is an issue, this will
-	// help us avoid hangs.
-	let token_clone = token.clone();
-	let event_send_task = async move {
-		std::pin::pin!(&mut stream);
-		loop {
-			let maybe = or_cancel(stream.next(), &token_clone).await;
-			let Ok(maybe_ev) = maybe else {
-				return;
-			};
-			if let Some(ev) = maybe_ev {
-				if let Err(e) = ev_tx.try_send(ev) {
-					tracing::error!(
-						"Error sending event across channel! {e:?}"
-					);
-				}
-			}
-		}
-	};
+	let event_send_task = forward_atspi_events(Arc::clone(&state), ev_tx, token.clone());
 	let atspi_handlers_task = handlers.clone().atspi_handler(ev_rx, token.clone());
 	let listener = odilia_input::setup_input_server()
 		.await
 		.expect("We should be able to set up input server; without it, Odilia cannot be controlled via input methods");
-	let input_task = odilia_input::sr_event_receiver(listener, input_tx, token.clone())
-		.for_each(|fut| {
-			ex.spawn(fut).detach();
-		});
+	let input_task = odilia_input::sr_event_receiver(
+		listener,
+		input_tx,
+		feedback_tx,
+		state.input_servers.clone(),
+		token.clone(),
+	)
+	.for_each(|fut| {
+		ex.spawn(fut).detach();
+	});
 	let input_handler = handlers.input_handler(input_rx, token.clone());
+	let feedback_task = input_feedback_monitor(Arc::clone(&state), feedback_rx, token.clone());
 	let child = try_spawn_input_server(&state.config.input.method)?;
 	state.add_child_proc(child).expect("Able to add child to process!");
 
@@ -289,11 +463,14 @@ async fn async_main() -> Result<(), OdiliaError> {
 
 	let joined_tasks = (
 		ssip_event_receiver,
+		braille_task,
+		speech_event_task,
 		notification_task,
 		atspi_handlers_task,
 		event_send_task,
 		input_task,
 		input_handler,
+		feedback_task,
 		cache_handler,
 	)
 		.join();
@@ -335,3 +512,25 @@ fn load_configuration(cli_overide: Option<PathBuf>) -> Result<ApplicationConfig,
 	}
 	Ok(config.build()?.try_deserialize()?)
 }
+
+/// Load `<name>.toml`, which lives next to `config.toml`.
+/// The file in the XDG config directory takes priority over the system wide one in `/etc/odilia`.
+/// Neither file is required; if both are missing, or cannot be parsed, the default is used.
+fn load_extra_config<T: DeserializeOwned + Default>(name: &str) -> T {
+	let xdg_dirs = xdg::BaseDirectories::with_prefix("odilia").expect(
+		"unable to find the odilia config directory according to the xdg dirs specification",
+	);
+	let mut config = config::Config::builder().add_source(
+		config::File::with_name(&format!("/etc/odilia/{name}")).required(false),
+	);
+	if let Some(path) = xdg_dirs.find_config_file(format!("{name}.toml")) {
+		config = config.add_source(config::File::from(path).required(false));
+	}
+	match config.build().and_then(config::Config::try_deserialize::<T>) {
+		Ok(settings) => settings,
+		Err(e) => {
+			tracing::error!(error = %e, "Unable to load {name}.toml; the defaults will be used");
+			T::default()
+		}
+	}
+}
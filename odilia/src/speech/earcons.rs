@@ -0,0 +1,77 @@
+//! Choosing between speaking a role or state change, and playing a sound icon for it.
+
+use std::{collections::HashMap, hash::BuildHasher};
+
+use odilia_common::settings::{earcons::FeedbackMode, EarconSettings};
+
+/// How one piece of information is given to the user.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Feedback {
+	/// The sound icon to play, if any.
+	pub icon: Option<String>,
+	/// Whether the information should also be spoken.
+	pub speak: bool,
+}
+
+fn resolve<S: BuildHasher>(
+	mode: FeedbackMode,
+	icons: &HashMap<String, String, S>,
+	key: &str,
+) -> Feedback {
+	match icons.get(key).filter(|_| mode.plays()) {
+		Some(icon) => Feedback { icon: Some(icon.clone()), speak: mode.speaks() },
+		// with nothing to play, it has to be said
+		None => Feedback { icon: None, speak: true },
+	}
+}
+
+/// How the role named `role` (like "link") is given when an object with it is focused.
+#[must_use]
+pub fn role(settings: &EarconSettings, role: &str) -> Feedback {
+	resolve(settings.roles, &settings.role_icons, role)
+}
+
+/// How a state change is given; `said` is what would be spoken for it, like "checked".
+#[must_use]
+pub fn state_change(settings: &EarconSettings, said: &str) -> Feedback {
+	resolve(settings.states, &settings.state_icons, said)
+}
+
+#[cfg(test)]
+mod tests {
+	use odilia_common::settings::{earcons::FeedbackMode, EarconSettings};
+
+	use super::{role, state_change, Feedback};
+
+	#[test]
+	fn speech_only_by_default() {
+		let settings = EarconSettings::default();
+		assert_eq!(role(&settings, "link"), Feedback { icon: None, speak: true });
+		assert_eq!(
+			state_change(&settings, "checked"),
+			Feedback { icon: None, speak: true }
+		);
+	}
+
+	#[test]
+	fn sound_replaces_speech() {
+		let settings = EarconSettings { roles: FeedbackMode::Sound, ..Default::default() };
+		assert_eq!(
+			role(&settings, "link"),
+			Feedback { icon: Some("link".to_string()), speak: false }
+		);
+		// roles without an icon are still spoken
+		assert_eq!(role(&settings, "label"), Feedback { icon: None, speak: true });
+		// the mode is per kind of event
+		assert_eq!(state_change(&settings, "checked").icon, None);
+	}
+
+	#[test]
+	fn both() {
+		let settings = EarconSettings { states: FeedbackMode::Both, ..Default::default() };
+		assert_eq!(
+			state_change(&settings, "collapsed"),
+			Feedback { icon: Some("collapsed".to_string()), speak: true }
+		);
+	}
+}
@@ -0,0 +1,127 @@
+//! Deciding what to say when text is typed or deleted.
+
+use odilia_common::settings::speech::EchoMode;
+
+use super::SymbolTable;
+
+fn is_word_char(c: char) -> bool {
+	c.is_alphanumeric() || c == '_'
+}
+
+/// Text spoken in place of each character typed into a password field.
+#[must_use]
+pub fn password(changed: &str) -> String {
+	vec!["star"; changed.chars().count()].join(" ")
+}
+
+/// What to say when `inserted` is typed at character offset `start` of `text`.
+/// `text` is the full contents of the field, *after* the insertion.
+///
+/// Anything longer than a single character (a paste, or an autocompletion) is always spoken in
+/// full, unless echo is turned off completely.
+#[must_use]
+pub fn inserted(
+	mode: EchoMode,
+	symbols: &SymbolTable,
+	language: &str,
+	text: &str,
+	start: usize,
+	inserted: &str,
+) -> Option<String> {
+	let mut chars = inserted.chars();
+	let (Some(typed), None) = (chars.next(), chars.next()) else {
+		return Some(inserted.to_string())
+			.filter(|_| mode != EchoMode::None && !inserted.is_empty());
+	};
+	let mut parts = Vec::new();
+	if mode.echoes_characters() {
+		parts.push(symbols.character(typed, language).into_owned());
+	}
+	if mode.echoes_words() && !is_word_char(typed) {
+		let before = text.chars().take(start).collect::<Vec<char>>();
+		let word = before
+			.iter()
+			.rev()
+			.take_while(|c| is_word_char(**c))
+			.collect::<Vec<&char>>();
+		if !word.is_empty() {
+			parts.push(word.into_iter().rev().collect());
+		}
+	}
+	Some(parts.join(" ")).filter(|said| !said.is_empty())
+}
+
+/// What to say when `deleted` is removed, by backspace or delete.
+#[must_use]
+pub fn deleted(symbols: &SymbolTable, language: &str, deleted: &str) -> Option<String> {
+	let mut chars = deleted.chars();
+	match (chars.next(), chars.next()) {
+		(None, _) => None,
+		(Some(c), None) => Some(symbols.character(c, language).into_owned()),
+		(Some(_), Some(_)) => Some(deleted.to_string()),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use odilia_common::settings::speech::EchoMode;
+
+	use super::{deleted, password, SymbolTable};
+
+	fn inserted(mode: EchoMode, text: &str, start: usize, typed: &str) -> Option<String> {
+		super::inserted(mode, &SymbolTable::default(), "en", text, start, typed)
+	}
+
+	#[test]
+	fn character_echo() {
+		assert_eq!(inserted(EchoMode::Character, "hel", 2, "l").as_deref(), Some("l"));
+		assert_eq!(inserted(EchoMode::Character, "hi ", 2, " ").as_deref(), Some("space"));
+		assert_eq!(inserted(EchoMode::None, "hel", 2, "l"), None);
+	}
+
+	#[test]
+	fn word_echo_waits_for_the_end_of_the_word() {
+		assert_eq!(inserted(EchoMode::Word, "hel", 2, "l"), None);
+		assert_eq!(
+			inserted(EchoMode::Word, "say hello ", 9, " ").as_deref(),
+			Some("hello")
+		);
+		assert_eq!(
+			inserted(EchoMode::Word, "say hello.", 9, ".").as_deref(),
+			Some("hello")
+		);
+		// nothing to say after a second space
+		assert_eq!(inserted(EchoMode::Word, "hello  ", 6, " "), None);
+	}
+
+	#[test]
+	fn both_says_the_character_then_the_word() {
+		assert_eq!(inserted(EchoMode::Both, "hi,", 2, ",").as_deref(), Some("comma hi"));
+		assert_eq!(inserted(EchoMode::Both, "hi", 1, "i").as_deref(), Some("i"));
+	}
+
+	#[test]
+	fn pasted_text_is_spoken_in_full() {
+		assert_eq!(
+			inserted(EchoMode::Word, "hello world", 0, "hello world").as_deref(),
+			Some("hello world")
+		);
+		assert_eq!(inserted(EchoMode::None, "hello world", 0, "hello world"), None);
+	}
+
+	#[test]
+	fn deletions() {
+		let symbols = SymbolTable::default();
+		assert_eq!(deleted(&symbols, "en", "a").as_deref(), Some("a"));
+		assert_eq!(deleted(&symbols, "en", " ").as_deref(), Some("space"));
+		assert_eq!(deleted(&symbols, "en", ",").as_deref(), Some("comma"));
+		assert_eq!(deleted(&symbols, "en", "word").as_deref(), Some("word"));
+		assert_eq!(deleted(&symbols, "en", ""), None);
+	}
+
+	#[test]
+	fn password_stars() {
+		assert_eq!(password("a"), "star");
+		assert_eq!(password("abc"), "star star star");
+	}
+}
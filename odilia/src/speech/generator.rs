@@ -0,0 +1,542 @@
+//! Turning an accessible object into what should be said about it.
+//!
+//! The generator does not speak anything itself; it produces an [`Utterance`], a list of segments
+//! each tagged with the [`SpeechField`] it came from, so that handlers can add to it, remove
+//! parts of it, or just speak the whole thing.
+//! Depending on the user's [`EarconSettings`], some of what would be said is instead (or also)
+//! given as a sound icon, which is kept with the utterance.
+
+use std::{collections::HashMap, fmt};
+
+use atspi::{Role, State};
+use odilia_cache::CacheItem;
+use odilia_common::settings::{
+	speech::{SpeechField, Verbosity},
+	EarconSettings, SpeechSettings,
+};
+
+use super::earcons;
+
+/// One piece of an [`Utterance`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+	pub field: SpeechField,
+	pub text: String,
+}
+
+/// The structured result of describing an object.
+/// Its [`fmt::Display`] implementation joins all segments with spaces, which is what gets spoken.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Utterance {
+	segments: Vec<Segment>,
+	earcon: Option<String>,
+}
+
+impl Utterance {
+	/// Add a segment to the end of the utterance; empty text is ignored.
+	pub fn push(&mut self, field: SpeechField, text: impl Into<String>) {
+		let text = text.into();
+		if !text.trim().is_empty() {
+			self.segments.push(Segment { field, text });
+		}
+	}
+	/// The text of the first segment generated from `field`, if any.
+	#[must_use]
+	pub fn get(&self, field: SpeechField) -> Option<&str> {
+		self.segments
+			.iter()
+			.find(|seg| seg.field == field)
+			.map(|seg| seg.text.as_str())
+	}
+	/// The sound icon to play before the utterance is spoken, if any.
+	#[must_use]
+	pub fn earcon(&self) -> Option<&str> {
+		self.earcon.as_deref()
+	}
+	/// Whether there is nothing to speak; there may still be an [`Utterance::earcon`] to play.
+	#[must_use]
+	pub fn is_empty(&self) -> bool {
+		self.segments.is_empty()
+	}
+}
+
+impl fmt::Display for Utterance {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let mut segments = self.segments.iter();
+		if let Some(first) = segments.next() {
+			f.write_str(&first.text)?;
+		}
+		for seg in segments {
+			write!(f, " {}", seg.text)?;
+		}
+		Ok(())
+	}
+}
+
+/// Everything known about an object which may be spoken.
+/// Only `item` is required; the rest is filled in by handlers which are able to look it up.
+#[derive(Debug, Clone)]
+pub struct SpeechSource<'a> {
+	pub item: &'a CacheItem,
+	/// Text of the objects which label this one, through the `LabelledBy` relation.
+	pub labelled_by: Option<String>,
+	/// Text of the object's descendants, for roles where this is the only useful label.
+	pub content: Option<String>,
+	/// The number of children of the object's parent; used for "3 of 5".
+	pub siblings: Option<usize>,
+	/// The keyboard shortcut, as returned by the action interface.
+	pub shortcut: Option<String>,
+}
+
+impl<'a> SpeechSource<'a> {
+	#[must_use]
+	pub fn new(item: &'a CacheItem) -> Self {
+		SpeechSource {
+			item,
+			labelled_by: None,
+			content: None,
+			siblings: None,
+			shortcut: None,
+		}
+	}
+}
+
+/// Which of the [`SpeechField`]s make sense for a given role.
+/// Fields which are not applicable are skipped, even if they are in the configured order.
+#[derive(Debug, Clone, Copy)]
+#[allow(clippy::struct_excessive_bools)]
+struct RoleTemplate {
+	role: bool,
+	value: bool,
+	position: bool,
+	content: bool,
+}
+
+impl RoleTemplate {
+	fn for_role(role: Role) -> Self {
+		RoleTemplate {
+			// these are read as plain text; "label" or "paragraph" after each one is noise
+			role: !matches!(role, Role::Label | Role::Paragraph | Role::Static),
+			// never read out the contents of a password field
+			value: matches!(
+				role,
+				Role::Entry | Role::SpinButton | Role::ComboBox | Role::Slider
+			),
+			position: matches!(
+				role,
+				Role::ListItem
+					| Role::MenuItem | Role::CheckMenuItem
+					| Role::RadioMenuItem | Role::RadioButton
+					| Role::PageTab | Role::TreeItem
+			),
+			// This lets us read Fractal messages.
+			// But we don't know what the general method should be.
+			content: role == Role::ListItem,
+		}
+	}
+}
+
+/// Builds [`Utterance`]s according to the user's [`SpeechSettings`].
+#[derive(Debug, Clone)]
+pub struct SpeechGenerator {
+	order: Vec<SpeechField>,
+	verbosity: Verbosity,
+	role_verbosity: HashMap<String, Verbosity>,
+	earcons: EarconSettings,
+}
+
+impl SpeechGenerator {
+	#[must_use]
+	pub fn new(settings: &SpeechSettings) -> Self {
+		SpeechGenerator {
+			order: settings.field_order.clone(),
+			verbosity: settings.verbosity,
+			role_verbosity: settings.role_verbosity.clone(),
+			earcons: settings.earcons.clone(),
+		}
+	}
+
+	/// The verbosity used for objects with the given role.
+	#[must_use]
+	pub fn verbosity(&self, role: Role) -> Verbosity {
+		self.role_verbosity
+			.get(role.name())
+			.copied()
+			.unwrap_or(self.verbosity)
+	}
+
+	/// Describe the object in `source`.
+	#[must_use]
+	pub fn generate(&self, source: &SpeechSource<'_>) -> Utterance {
+		let template = RoleTemplate::for_role(source.item.role);
+		let verbosity = self.verbosity(source.item.role);
+		let role = earcons::role(&self.earcons, source.item.role.name());
+		let mut utterance = Utterance { earcon: role.icon, ..Utterance::default() };
+		for field in &self.order {
+			match field {
+				SpeechField::Name => {
+					utterance.push(*field, name(source));
+					if template.content {
+						if let Some(content) = &source.content {
+							utterance.push(*field, content.clone());
+						}
+					}
+				}
+				SpeechField::Value if template.value => {
+					if let Some(text) = &source.item.text {
+						utterance.push(*field, text.clone());
+					}
+				}
+				SpeechField::Role
+					if template.role
+						&& verbosity.speaks_role() && role.speak =>
+				{
+					utterance.push(*field, role_name(source.item.role));
+				}
+				SpeechField::States => {
+					for state in states(source.item) {
+						utterance.push(*field, state);
+					}
+				}
+				SpeechField::Position if template.position => {
+					if let Some(pos) = position(source) {
+						utterance.push(*field, pos);
+					}
+				}
+				SpeechField::Description if verbosity.speaks_description() => {
+					// when there is no name, the description is used as the name instead
+					if source.item.name.is_some() {
+						if let Some(desc) = &source.item.description {
+							utterance.push(*field, desc.clone());
+						}
+					}
+				}
+				SpeechField::Shortcut => {
+					if let Some(shortcut) = source
+						.shortcut
+						.as_deref()
+						.and_then(humanize_key_binding)
+					{
+						utterance.push(*field, shortcut);
+					}
+				}
+				SpeechField::HelpText if verbosity.speaks_help_text() => {
+					if let Some(help) = &source.item.help_text {
+						utterance.push(*field, help.clone());
+					}
+				}
+				SpeechField::Tutorial if verbosity.speaks_tutorial() => {
+					if let Some(hint) = tutorial(source.item) {
+						utterance.push(*field, hint);
+					}
+				}
+				SpeechField::Value
+				| SpeechField::Role
+				| SpeechField::Position
+				| SpeechField::Description
+				| SpeechField::HelpText
+				| SpeechField::Tutorial => {}
+			}
+		}
+		utterance
+	}
+
+	/// What to say, or play, when `state` is turned on or off for the focused object.
+	/// The utterance is empty for states which are not worth interrupting the user for.
+	#[must_use]
+	pub fn state_change(&self, state: State, enabled: bool) -> Utterance {
+		let mut utterance = Utterance::default();
+		if let Some(said) = state_change(state, enabled) {
+			let feedback = earcons::state_change(&self.earcons, said);
+			if feedback.speak {
+				utterance.push(SpeechField::States, said);
+			}
+			utterance.earcon = feedback.icon;
+		}
+		utterance
+	}
+}
+
+/// The name of an object is the first of: its accessible name, its description, the text of
+/// the objects labelling it, or (for non-editable objects) its text.
+fn name(source: &SpeechSource<'_>) -> String {
+	let item = source.item;
+	if let Some(name) = &item.name {
+		return name.clone();
+	}
+	if let Some(desc) = &item.description {
+		return desc.clone();
+	}
+	if let Some(label) = &source.labelled_by {
+		return label.clone();
+	}
+	if RoleTemplate::for_role(item.role).value || item.role == Role::PasswordText {
+		return String::new();
+	}
+	item.text.clone().unwrap_or_default()
+}
+
+fn states(item: &CacheItem) -> Vec<&'static str> {
+	let st = item.states;
+	let mut spoken = Vec::new();
+	if st.contains(State::Checkable)
+		|| matches!(item.role, Role::CheckBox | Role::CheckMenuItem)
+	{
+		spoken.push(if st.contains(State::Indeterminate) {
+			"partially checked"
+		} else if st.contains(State::Checked) {
+			"checked"
+		} else {
+			"not checked"
+		});
+	} else if item.role == Role::ToggleButton {
+		spoken.push(if st.contains(State::Pressed) { "pressed" } else { "not pressed" });
+	}
+	if st.contains(State::Expandable) {
+		spoken.push(if st.contains(State::Expanded) { "expanded" } else { "collapsed" });
+	}
+	if st.contains(State::Selected) {
+		spoken.push("selected");
+	}
+	if st.contains(State::Required) {
+		spoken.push("required");
+	}
+	if st.contains(State::InvalidEntry) {
+		spoken.push("invalid");
+	}
+	spoken
+}
+
+/// What to say when `state` is turned on or off for the focused object.
+/// Returns `None` for states which are not worth interrupting the user for.
+#[must_use]
+pub fn state_change(state: State, enabled: bool) -> Option<&'static str> {
+	Some(match (state, enabled) {
+		(State::Checked, true) => "checked",
+		(State::Checked, false) => "not checked",
+		(State::Indeterminate, true) => "partially checked",
+		(State::Expanded, true) => "expanded",
+		(State::Expanded, false) => "collapsed",
+		(State::Pressed, true) => "pressed",
+		(State::Pressed, false) => "not pressed",
+		(State::Selected, true) => "selected",
+		(State::Selected, false) => "not selected",
+		(State::InvalidEntry, true) => "invalid",
+		_ => return None,
+	})
+}
+
+fn tutorial(item: &CacheItem) -> Option<&'static str> {
+	if item.states.contains(State::Expandable) && item.role == Role::TreeItem {
+		return Some("Use the left and right arrow keys to collapse and expand");
+	}
+	Some(match item.role {
+		Role::Button | Role::MenuItem => "Press enter to activate",
+		Role::CheckBox | Role::CheckMenuItem | Role::ToggleButton => {
+			"Press space to toggle"
+		}
+		Role::RadioButton | Role::RadioMenuItem => {
+			"Use the arrow keys to change the selection"
+		}
+		Role::ComboBox => "Press alt and down arrow to open the list",
+		Role::Entry | Role::PasswordText => "Type to enter text",
+		Role::Slider | Role::SpinButton => "Use the arrow keys to change the value",
+		Role::PageTab => "Use the left and right arrow keys to switch tabs",
+		Role::Link => "Press enter to follow the link",
+		_ => return None,
+	})
+}
+
+fn position(source: &SpeechSource<'_>) -> Option<String> {
+	let index = source.item.index?;
+	let total = source.siblings?;
+	if index >= total {
+		return None;
+	}
+	Some(format!("{} of {total}", index + 1))
+}
+
+/// Turn an AT-SPI key binding ("mnemonic;sequence;shortcut") into something speakable.
+/// The shortcut is preferred over the sequence, which is preferred over the mnemonic.
+/// GTK-style modifiers, like `<Control>`, are rewritten to `Ctrl+`.
+#[must_use]
+pub fn humanize_key_binding(binding: &str) -> Option<String> {
+	let chosen = binding.split(';').rev().find(|part| !part.trim().is_empty())?;
+	let readable = chosen
+		.replace("<Primary>", "Ctrl+")
+		.replace("<Control>", "Ctrl+")
+		.replace("<Alt>", "Alt+")
+		.replace("<Shift>", "Shift+")
+		.replace("<Super>", "Super+");
+	Some(readable.trim().to_string())
+}
+
+/// The name AT-SPI gives `role`, as other screen readers say it.
+/// atspi names [`Role::Button`] "button", where AT-SPI calls it a "push button".
+#[must_use]
+pub fn role_name(role: Role) -> &'static str {
+	match role {
+		Role::Button => "push button",
+		role => role.name(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use atspi::{Role, State, StateSet};
+	use odilia_common::settings::{
+		earcons::FeedbackMode,
+		speech::{SpeechField, Verbosity},
+		EarconSettings, SpeechSettings,
+	};
+
+	use super::{humanize_key_binding, SpeechGenerator, SpeechSource};
+	use crate::fixtures::cache_item as item;
+
+	fn generate(source: &SpeechSource<'_>) -> String {
+		SpeechGenerator::new(&SpeechSettings::default())
+			.generate(source)
+			.to_string()
+	}
+
+	#[test]
+	fn push_button() {
+		let button = item(Role::Button, Some("OK"));
+		assert_eq!(generate(&SpeechSource::new(&button)), "OK push button");
+	}
+
+	#[test]
+	fn check_box_states() {
+		let mut check = item(Role::CheckBox, Some("Bold"));
+		assert_eq!(generate(&SpeechSource::new(&check)), "Bold check box not checked");
+		check.states = StateSet::new(State::Checkable | State::Checked | State::Required);
+		assert_eq!(generate(&SpeechSource::new(&check)), "Bold check box checked required");
+	}
+
+	#[test]
+	fn list_item_position_and_content() {
+		let mut list_item = item(Role::ListItem, None);
+		list_item.index = Some(2);
+		let source = SpeechSource {
+			content: Some("Hello there".to_string()),
+			siblings: Some(5),
+			..SpeechSource::new(&list_item)
+		};
+		assert_eq!(generate(&source), "Hello there list item 3 of 5");
+	}
+
+	#[test]
+	fn entry_value_and_label() {
+		let mut entry = item(Role::Entry, None);
+		entry.text = Some("hunter2".to_string());
+		let source = SpeechSource {
+			labelled_by: Some("Username".to_string()),
+			..SpeechSource::new(&entry)
+		};
+		assert_eq!(generate(&source), "Username hunter2 entry");
+	}
+
+	#[test]
+	fn password_is_never_read() {
+		let mut entry = item(Role::PasswordText, Some("Password"));
+		entry.text = Some("hunter2".to_string());
+		assert_eq!(generate(&SpeechSource::new(&entry)), "Password password text");
+	}
+
+	#[test]
+	fn configured_order() {
+		let settings = SpeechSettings {
+			field_order: vec![SpeechField::Role, SpeechField::Name],
+			..SpeechSettings::default()
+		};
+		let mut button = item(Role::Button, Some("OK"));
+		button.description = Some("Accept the changes".to_string());
+		let utterance =
+			SpeechGenerator::new(&settings).generate(&SpeechSource::new(&button));
+		assert_eq!(utterance.to_string(), "push button OK");
+	}
+
+	#[test]
+	fn verbosity_levels() {
+		let mut button = item(Role::Button, Some("OK"));
+		button.description = Some("Accept the changes".to_string());
+		button.help_text = Some("Saves the file".to_string());
+		let source = SpeechSource::new(&button);
+		let speak = |verbosity| {
+			let settings = SpeechSettings { verbosity, ..SpeechSettings::default() };
+			SpeechGenerator::new(&settings).generate(&source).to_string()
+		};
+		assert_eq!(
+			speak(Verbosity::Beginner),
+			"OK push button Accept the changes Saves the file Press enter to activate"
+		);
+		assert_eq!(speak(Verbosity::Normal), "OK push button Accept the changes");
+		assert_eq!(speak(Verbosity::Terse), "OK");
+	}
+
+	#[test]
+	fn role_verbosity_override() {
+		let settings = SpeechSettings {
+			role_verbosity: [(Role::CheckBox.name().to_string(), Verbosity::Terse)]
+				.into(),
+			..SpeechSettings::default()
+		};
+		let generator = SpeechGenerator::new(&settings);
+		let check = item(Role::CheckBox, Some("Bold"));
+		let button = item(Role::Button, Some("OK"));
+		assert_eq!(
+			generator.generate(&SpeechSource::new(&check)).to_string(),
+			"Bold not checked"
+		);
+		assert_eq!(
+			generator.generate(&SpeechSource::new(&button)).to_string(),
+			"OK push button"
+		);
+	}
+
+	#[test]
+	fn earcons_replace_roles_and_states() {
+		let settings = SpeechSettings {
+			earcons: EarconSettings {
+				roles: FeedbackMode::Sound,
+				states: FeedbackMode::Both,
+				..EarconSettings::default()
+			},
+			..SpeechSettings::default()
+		};
+		let generator = SpeechGenerator::new(&settings);
+		let link = item(Role::Link, Some("Home"));
+		let utterance = generator.generate(&SpeechSource::new(&link));
+		assert_eq!(utterance.to_string(), "Home");
+		assert_eq!(utterance.earcon(), Some("link"));
+		let checked = generator.state_change(State::Checked, true);
+		assert_eq!(checked.to_string(), "checked");
+		assert_eq!(checked.earcon(), Some("checked"));
+		assert!(generator.state_change(State::Focused, true).is_empty());
+	}
+
+	#[test]
+	fn button_earcon() {
+		// earcons are keyed by atspi's name for the role, not the one which is spoken
+		let settings = SpeechSettings {
+			earcons: EarconSettings {
+				roles: FeedbackMode::Sound,
+				..EarconSettings::default()
+			},
+			..SpeechSettings::default()
+		};
+		let button = item(Role::Button, Some("OK"));
+		let utterance =
+			SpeechGenerator::new(&settings).generate(&SpeechSource::new(&button));
+		assert_eq!(utterance.to_string(), "OK");
+		assert_eq!(utterance.earcon(), Some("button"));
+	}
+
+	#[test]
+	fn key_bindings() {
+		assert_eq!(humanize_key_binding("N;Alt+F:N;Ctrl+N").as_deref(), Some("Ctrl+N"));
+		assert_eq!(humanize_key_binding("<Control>s").as_deref(), Some("Ctrl+s"));
+		assert_eq!(humanize_key_binding("n;;").as_deref(), Some("n"));
+		assert_eq!(humanize_key_binding(""), None);
+	}
+}
@@ -0,0 +1,138 @@
+use std::{collections::VecDeque, time::SystemTime};
+
+use odilia_common::cache::AccessiblePrimitive;
+use ssip::Priority;
+
+/// Something which was spoken.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEntry {
+	/// The text as it was given to the speech handler, before the pronunciation dictionary is
+	/// applied.
+	pub text: String,
+	pub priority: Priority,
+	pub time: SystemTime,
+	/// The object which had focus when the text was spoken, if any.
+	pub source: Option<AccessiblePrimitive>,
+}
+
+/// A bounded log of everything Odilia has said, with a cursor for reviewing it.
+///
+/// The cursor points at the newest entry until the user moves it; speaking something new puts it
+/// back at the newest entry.
+#[derive(Debug, Clone)]
+pub struct SpeechHistory {
+	/// Newest entries first.
+	entries: VecDeque<HistoryEntry>,
+	capacity: usize,
+	/// How many entries back from the newest one the user is reviewing.
+	cursor: usize,
+}
+
+impl SpeechHistory {
+	#[must_use]
+	pub fn with_capacity(capacity: usize) -> Self {
+		SpeechHistory { entries: VecDeque::with_capacity(capacity), capacity, cursor: 0 }
+	}
+	/// Record a new entry, forgetting the oldest one if the history is full.
+	pub fn push(&mut self, entry: HistoryEntry) {
+		if self.capacity == 0 {
+			return;
+		}
+		if self.entries.len() == self.capacity {
+			self.entries.pop_back();
+		}
+		self.entries.push_front(entry);
+		self.cursor = 0;
+	}
+	/// The most recently spoken entry.
+	#[must_use]
+	pub fn latest(&self) -> Option<&HistoryEntry> {
+		self.entries.front()
+	}
+	/// The entry under the review cursor.
+	#[must_use]
+	pub fn current(&self) -> Option<&HistoryEntry> {
+		self.entries.get(self.cursor)
+	}
+	/// Move the cursor to the entry spoken before the current one.
+	/// Returns `None`, without moving, if the cursor is already at the oldest entry.
+	pub fn older(&mut self) -> Option<&HistoryEntry> {
+		if self.cursor + 1 >= self.entries.len() {
+			return None;
+		}
+		self.cursor += 1;
+		self.current()
+	}
+	/// Move the cursor to the entry spoken after the current one.
+	/// Returns `None`, without moving, if the cursor is already at the newest entry.
+	pub fn newer(&mut self) -> Option<&HistoryEntry> {
+		self.cursor = self.cursor.checked_sub(1)?;
+		self.current()
+	}
+	#[must_use]
+	pub fn len(&self) -> usize {
+		self.entries.len()
+	}
+	#[must_use]
+	pub fn is_empty(&self) -> bool {
+		self.entries.is_empty()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::SystemTime;
+
+	use ssip::Priority;
+
+	use super::{HistoryEntry, SpeechHistory};
+
+	fn entry(text: &str) -> HistoryEntry {
+		HistoryEntry {
+			text: text.to_string(),
+			priority: Priority::Text,
+			time: SystemTime::now(),
+			source: None,
+		}
+	}
+
+	fn text(entry: Option<&HistoryEntry>) -> Option<&str> {
+		entry.map(|entry| entry.text.as_str())
+	}
+
+	#[test]
+	fn oldest_entries_are_dropped() {
+		let mut history = SpeechHistory::with_capacity(2);
+		history.push(entry("one"));
+		history.push(entry("two"));
+		history.push(entry("three"));
+		assert_eq!(history.len(), 2);
+		assert_eq!(text(history.latest()), Some("three"));
+		assert_eq!(text(history.older()), Some("two"));
+		assert_eq!(text(history.older()), None);
+	}
+
+	#[test]
+	fn review_moves_cursor_both_ways() {
+		let mut history = SpeechHistory::with_capacity(8);
+		assert_eq!(history.current(), None);
+		for word in ["one", "two", "three"] {
+			history.push(entry(word));
+		}
+		assert_eq!(text(history.newer()), None);
+		assert_eq!(text(history.older()), Some("two"));
+		assert_eq!(text(history.older()), Some("one"));
+		assert_eq!(text(history.current()), Some("one"));
+		assert_eq!(text(history.newer()), Some("two"));
+		// anything new puts the cursor back at the end
+		history.push(entry("four"));
+		assert_eq!(text(history.current()), Some("four"));
+	}
+
+	#[test]
+	fn zero_capacity_records_nothing() {
+		let mut history = SpeechHistory::with_capacity(0);
+		history.push(entry("one"));
+		assert!(history.is_empty());
+	}
+}
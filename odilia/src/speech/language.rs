@@ -0,0 +1,304 @@
+//! Choosing the language and voice for each utterance.
+//!
+//! Applications say what language their content is in through object attributes (`lang` on web
+//! pages, `language` elsewhere) or their locale; those tags come in many spellings, so they are
+//! normalized to what speech-dispatcher expects before a voice is chosen.
+
+use std::{
+	collections::HashMap,
+	hash::BuildHasher,
+	sync::{Mutex, PoisonError},
+};
+
+use odilia_common::{cache::AccessiblePrimitive, errors::OdiliaError, settings::SpeechSettings};
+use odilia_tts::SpeechBackend;
+
+/// The language and voice speech-dispatcher was last asked to use.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ActiveVoice {
+	pub language: String,
+	pub voice: String,
+}
+
+/// What an object says about its language, which does not change while it is focused.
+/// Text can be in a different language from one place to the next, so that is looked up at the
+/// caret each time instead.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ObjectLanguage {
+	/// The language its attributes say it is in.
+	pub attributes: Option<String>,
+	/// Whether it has text, which may say what language the text at the caret is in.
+	pub text: bool,
+	/// The language of its locale, or else that of its application.
+	pub locale: Option<String>,
+}
+
+impl ObjectLanguage {
+	/// The language of the content, given the language of the text at the caret, if any.
+	/// The object's attributes come first, then the text, and finally the locale.
+	#[must_use]
+	pub fn resolve(&self, at_caret: Option<String>) -> Option<String> {
+		self.attributes.clone().or(at_caret).or_else(|| self.locale.clone())
+	}
+	/// Whether the language of the text at the caret could make a difference.
+	#[must_use]
+	pub fn needs_caret(&self) -> bool {
+		self.text && self.attributes.is_none()
+	}
+}
+
+/// The [`ObjectLanguage`] of the focused object, so every utterance and braille line does not
+/// have to ask the application all over again; focusing another object replaces it.
+#[derive(Debug, Default)]
+pub struct LanguageCache {
+	entry: Option<(AccessiblePrimitive, ObjectLanguage)>,
+}
+
+impl LanguageCache {
+	/// The language of `object`, if it is the one cached.
+	#[must_use]
+	pub fn get(&self, object: &AccessiblePrimitive) -> Option<&ObjectLanguage> {
+		self.entry
+			.as_ref()
+			.filter(|(cached, _)| cached == object)
+			.map(|(_, language)| language)
+	}
+	pub fn insert(&mut self, object: AccessiblePrimitive, language: ObjectLanguage) {
+		self.entry = Some((object, language));
+	}
+}
+
+/// Turn a locale or language tag (`en_US.UTF-8`, `pt-br`, `DE`) into the form speech-dispatcher
+/// expects (`en-US`, `pt-BR`, `de`).
+/// Returns `None` for tags which do not name a language, like the `C` locale.
+#[must_use]
+pub fn normalize(tag: &str) -> Option<String> {
+	// drop the encoding and modifier of POSIX locales: `de_DE.UTF-8@euro`
+	let tag = tag.split(['.', '@']).next().unwrap_or_default().trim();
+	let mut parts = tag.split(['-', '_']);
+	let language = parts.next().filter(|language| {
+		(2..=3).contains(&language.len())
+			&& language.chars().all(|c| c.is_ascii_alphabetic())
+	})?;
+	let mut normalized = language.to_ascii_lowercase();
+	if let Some(region) = parts.next().filter(|region| !region.is_empty()) {
+		normalized.push('-');
+		if region.len() == 2 {
+			normalized.push_str(&region.to_ascii_uppercase());
+		} else {
+			normalized.push_str(region);
+		}
+	}
+	Some(normalized)
+}
+
+fn primary(language: &str) -> &str {
+	language.split('-').next().unwrap_or_default()
+}
+
+/// The language an object's attributes say it is in, if any.
+#[must_use]
+pub fn from_attributes<S: BuildHasher>(attributes: &HashMap<String, String, S>) -> Option<String> {
+	["lang", "language"]
+		.iter()
+		.find_map(|key| attributes.get(*key).and_then(|tag| normalize(tag)))
+}
+
+/// What the user chose for `language` in `choices`, like a voice or a braille table: an exact
+/// match (`pt-BR`) is preferred over one for the language as a whole (`pt`).
+#[must_use]
+pub fn for_language<'a, S: BuildHasher>(
+	choices: &'a HashMap<String, String, S>,
+	language: &str,
+) -> Option<&'a str> {
+	let normalized = choices
+		.iter()
+		.filter_map(|(tag, choice)| Some((normalize(tag)?, choice.as_str())))
+		.collect::<Vec<(String, &str)>>();
+	[language, primary(language)].iter().find_map(|wanted| {
+		normalized
+			.iter()
+			.find(|(tag, _)| tag == wanted)
+			.map(|(_, choice)| *choice)
+	})
+}
+
+/// The language and voice to speak text in `language` with; `None` means the text is in the
+/// user's own language.
+/// Languages without a voice of their own fall back to the default voice.
+#[must_use]
+pub fn select(settings: &SpeechSettings, language: Option<&str>) -> ActiveVoice {
+	let default =
+		ActiveVoice { language: settings.language.clone(), voice: settings.person.clone() };
+	let Some(language) = language.filter(|_| settings.follow_language) else {
+		return default;
+	};
+	let default_language = normalize(&settings.language).unwrap_or_default();
+	if language == default_language || language == primary(&default_language) {
+		return default;
+	}
+	ActiveVoice {
+		language: language.to_string(),
+		voice: for_language(&settings.voices, language)
+			.unwrap_or(&settings.person)
+			.to_string(),
+	}
+}
+
+/// Switch `speech` to `voice`, unless `active` says it is using it already.
+/// `active` is only changed once the switch succeeded, so a failed one is tried again next time.
+///
+/// # Errors
+///
+/// If `speech` could not be switched.
+pub async fn switch(
+	active: &Mutex<ActiveVoice>,
+	voice: &ActiveVoice,
+	speech: &dyn SpeechBackend,
+) -> Result<(), OdiliaError> {
+	if *active.lock().unwrap_or_else(PoisonError::into_inner) == *voice {
+		return Ok(());
+	}
+	speech.switch_voice(voice.language.clone(), voice.voice.clone())
+		.await?;
+	active.lock()
+		.unwrap_or_else(PoisonError::into_inner)
+		.clone_from(voice);
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{collections::HashMap, sync::Mutex};
+
+	use futures_lite::future::block_on;
+	use odilia_common::{cache::AccessiblePrimitive, settings::SpeechSettings};
+	use odilia_tts::{Recorded, RecordingBackend};
+
+	use super::{
+		for_language, from_attributes, normalize, select, switch, ActiveVoice,
+		LanguageCache, ObjectLanguage,
+	};
+
+	#[test]
+	fn tags_are_normalized() {
+		assert_eq!(normalize("en_US.UTF-8").as_deref(), Some("en-US"));
+		assert_eq!(normalize("de_DE@euro").as_deref(), Some("de-DE"));
+		assert_eq!(normalize("pt-br").as_deref(), Some("pt-BR"));
+		assert_eq!(normalize("FR").as_deref(), Some("fr"));
+		assert_eq!(normalize("C"), None);
+		assert_eq!(normalize(""), None);
+	}
+
+	#[test]
+	fn attributes() {
+		let web = HashMap::from([("lang".to_string(), "es-MX".to_string())]);
+		assert_eq!(from_attributes(&web).as_deref(), Some("es-MX"));
+		let other = HashMap::from([("language".to_string(), "it".to_string())]);
+		assert_eq!(from_attributes(&other).as_deref(), Some("it"));
+		assert_eq!(from_attributes(&HashMap::new()), None);
+	}
+
+	#[test]
+	fn exact_voices_are_preferred() {
+		let voices = HashMap::from([
+			("pt".to_string(), "Portuguese".to_string()),
+			("pt_BR".to_string(), "Brazil".to_string()),
+		]);
+		assert_eq!(for_language(&voices, "pt-BR"), Some("Brazil"));
+		assert_eq!(for_language(&voices, "pt-PT"), Some("Portuguese"));
+		assert_eq!(for_language(&voices, "de"), None);
+	}
+
+	#[test]
+	fn selection() {
+		let settings = SpeechSettings {
+			voices: HashMap::from([("de".to_string(), "German".to_string())]),
+			..SpeechSettings::default()
+		};
+		let default = ActiveVoice {
+			language: settings.language.clone(),
+			voice: settings.person.clone(),
+		};
+		assert_eq!(select(&settings, None), default);
+		assert_eq!(select(&settings, Some("en")), default);
+		assert_eq!(
+			select(&settings, Some("de-AT")),
+			ActiveVoice { language: "de-AT".to_string(), voice: "German".to_string() }
+		);
+		// no voice of its own
+		assert_eq!(select(&settings, Some("fr")).voice, settings.person);
+		let ignoring = SpeechSettings { follow_language: false, ..settings };
+		assert_eq!(select(&ignoring, Some("de")), default);
+	}
+
+	#[test]
+	fn object_language_precedence() {
+		let web = ObjectLanguage {
+			attributes: Some("es".to_string()),
+			text: true,
+			locale: Some("en-US".to_string()),
+		};
+		assert_eq!(web.resolve(Some("fr".to_string())).as_deref(), Some("es"));
+		assert!(!web.needs_caret());
+		let document = ObjectLanguage { attributes: None, ..web };
+		assert!(document.needs_caret());
+		assert_eq!(document.resolve(Some("fr".to_string())).as_deref(), Some("fr"));
+		assert_eq!(document.resolve(None).as_deref(), Some("en-US"));
+		assert_eq!(ObjectLanguage::default().resolve(None), None);
+	}
+
+	#[test]
+	fn only_the_focused_object_is_cached() {
+		let object = |id: &str| AccessiblePrimitive {
+			sender: ":1.2".to_string(),
+			id: id.to_string(),
+		};
+		let german =
+			ObjectLanguage { locale: Some("de".to_string()), ..Default::default() };
+		let mut cache = LanguageCache::default();
+		assert_eq!(cache.get(&object("/a")), None);
+		cache.insert(object("/a"), german.clone());
+		assert_eq!(cache.get(&object("/a")), Some(&german));
+		assert_eq!(cache.get(&object("/b")), None);
+		cache.insert(object("/b"), ObjectLanguage::default());
+		assert_eq!(cache.get(&object("/a")), None);
+		assert_eq!(cache.get(&object("/b")), Some(&ObjectLanguage::default()));
+	}
+
+	#[test]
+	fn switching_only_when_needed() {
+		let recorder = RecordingBackend::new();
+		let active = Mutex::new(ActiveVoice::default());
+		let german =
+			ActiveVoice { language: "de".to_string(), voice: "German".to_string() };
+		block_on(switch(&active, &german, &recorder)).expect("Recording never fails");
+		block_on(switch(&active, &german, &recorder)).expect("Recording never fails");
+		assert_eq!(
+			recorder.recorded(),
+			[
+				Recorded::Language("de".to_string()),
+				Recorded::Voice("German".to_string())
+			]
+		);
+		assert_eq!(*active.lock().expect("Not poisoned"), german);
+	}
+
+	#[test]
+	fn failed_switches_are_retried() {
+		let recorder = RecordingBackend::new();
+		let active = Mutex::new(ActiveVoice::default());
+		let german =
+			ActiveVoice { language: "de".to_string(), voice: "German".to_string() };
+		recorder.set_failing(true);
+		assert!(block_on(switch(&active, &german, &recorder)).is_err());
+		assert_eq!(*active.lock().expect("Not poisoned"), ActiveVoice::default());
+		recorder.set_failing(false);
+		block_on(switch(&active, &german, &recorder)).expect("Recording never fails");
+		assert_eq!(*active.lock().expect("Not poisoned"), german);
+		assert_eq!(
+			recorder.recorded().last(),
+			Some(&Recorded::Voice("German".to_string()))
+		);
+	}
+}
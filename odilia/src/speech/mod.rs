@@ -0,0 +1,16 @@
+//! Text processing which happens between a handler deciding what to say, and that text being sent
+//! to the speech backend.
+
+pub mod earcons;
+pub mod echo;
+pub mod generator;
+pub mod history;
+pub mod language;
+pub mod pronunciation;
+pub mod say_all;
+pub mod spelling;
+pub mod symbols;
+
+pub use history::{HistoryEntry, SpeechHistory};
+pub use pronunciation::PronunciationDictionary;
+pub use symbols::SymbolTable;
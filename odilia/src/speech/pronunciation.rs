@@ -0,0 +1,215 @@
+use std::borrow::Cow;
+
+use odilia_common::settings::{
+	pronunciation::{PatternKind, PronunciationRule},
+	PronunciationSettings,
+};
+use regex::{NoExpand, Regex, RegexBuilder};
+
+/// A [`PronunciationRule`] which has been compiled, and is ready to be applied to text.
+#[derive(Debug)]
+struct CompiledRule {
+	regex: Regex,
+	replacement: String,
+	/// Whether `$1`-style references in the replacement refer to capture groups.
+	/// This is only the case for regex rules; literal replacements are inserted exactly as written.
+	expand: bool,
+	app: Option<String>,
+}
+
+fn is_word_char(c: char) -> bool {
+	c.is_alphanumeric() || c == '_'
+}
+
+impl CompiledRule {
+	fn new(rule: &PronunciationRule) -> Result<Self, regex::Error> {
+		let pattern = match rule.kind {
+			// `\b` only means something next to a word character; a literal like "C++" would
+			// otherwise never match when followed by a space, so only add it where it makes sense.
+			PatternKind::Literal if rule.whole_word => {
+				let start = if rule.pattern.starts_with(is_word_char) {
+					r"\b"
+				} else {
+					""
+				};
+				let end = if rule.pattern.ends_with(is_word_char) {
+					r"\b"
+				} else {
+					""
+				};
+				format!("{start}{}{end}", regex::escape(&rule.pattern))
+			}
+			PatternKind::Literal => regex::escape(&rule.pattern),
+			PatternKind::Regex if rule.whole_word => {
+				format!(r"\b(?:{})\b", rule.pattern)
+			}
+			PatternKind::Regex => rule.pattern.clone(),
+		};
+		let regex = RegexBuilder::new(&pattern)
+			.case_insensitive(!rule.case_sensitive)
+			.build()?;
+		Ok(CompiledRule {
+			regex,
+			replacement: rule.replacement.clone(),
+			expand: rule.kind == PatternKind::Regex,
+			app: rule.app.clone(),
+		})
+	}
+	fn applies_to(&self, app: Option<&str>) -> bool {
+		match (&self.app, app) {
+			(None, _) => true,
+			(Some(scope), Some(app)) => scope.eq_ignore_ascii_case(app),
+			(Some(_), None) => false,
+		}
+	}
+}
+
+/// The user's pronunciation rules, applied to all text before it is spoken.
+#[derive(Debug, Default)]
+pub struct PronunciationDictionary {
+	rules: Vec<CompiledRule>,
+}
+
+impl PronunciationDictionary {
+	/// Compile the rules from `settings`.
+	/// A rule with an invalid regular expression is logged and skipped, so that one mistake in the
+	/// file does not throw away the rest of the dictionary.
+	#[must_use]
+	pub fn new(settings: &PronunciationSettings) -> Self {
+		let rules = settings
+			.rules
+			.iter()
+			.filter_map(|rule| match CompiledRule::new(rule) {
+				Ok(compiled) => Some(compiled),
+				Err(e) => {
+					tracing::error!(?rule, error = %e, "Skipping invalid pronunciation rule");
+					None
+				}
+			})
+			.collect();
+		PronunciationDictionary { rules }
+	}
+	/// Apply every rule which is in scope for `app` (the name of the application the text comes
+	/// from, if known), in order.
+	#[must_use]
+	pub fn apply<'a>(&self, text: &'a str, app: Option<&str>) -> Cow<'a, str> {
+		let mut text = Cow::Borrowed(text);
+		for rule in self.rules.iter().filter(|rule| rule.applies_to(app)) {
+			let replaced = if rule.expand {
+				rule.regex.replace_all(&text, rule.replacement.as_str())
+			} else {
+				rule.regex.replace_all(&text, NoExpand(&rule.replacement))
+			};
+			if let Cow::Owned(new) = replaced {
+				text = Cow::Owned(new);
+			}
+		}
+		text
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use odilia_common::settings::{
+		pronunciation::{PatternKind, PronunciationRule},
+		PronunciationSettings,
+	};
+
+	use super::PronunciationDictionary;
+
+	fn rule(pattern: &str, replacement: &str) -> PronunciationRule {
+		PronunciationRule {
+			pattern: pattern.to_string(),
+			replacement: replacement.to_string(),
+			kind: PatternKind::Literal,
+			case_sensitive: false,
+			whole_word: false,
+			app: None,
+		}
+	}
+
+	fn dictionary(rules: Vec<PronunciationRule>) -> PronunciationDictionary {
+		PronunciationDictionary::new(&PronunciationSettings { rules })
+	}
+
+	#[test]
+	fn literal_is_case_insensitive_by_default() {
+		let dict = dictionary(vec![rule("gtk", "gee tee kay")]);
+		assert_eq!(dict.apply("GTK and gtk", None), "gee tee kay and gee tee kay");
+	}
+
+	#[test]
+	fn literal_case_sensitive() {
+		let dict = dictionary(vec![PronunciationRule {
+			case_sensitive: true,
+			..rule("Qt", "cute")
+		}]);
+		assert_eq!(dict.apply("Qt, not QT", None), "cute, not QT");
+	}
+
+	#[test]
+	fn literal_is_not_a_regex() {
+		let dict = dictionary(vec![rule("a.b", "$1")]);
+		assert_eq!(dict.apply("a.b axb", None), "$1 axb");
+	}
+
+	#[test]
+	fn whole_word() {
+		let dict = dictionary(vec![PronunciationRule {
+			whole_word: true,
+			..rule("vim", "vee eye em")
+		}]);
+		assert_eq!(dict.apply("vim neovim vim.", None), "vee eye em neovim vee eye em.");
+	}
+
+	#[test]
+	fn whole_word_with_symbols() {
+		let dict = dictionary(vec![PronunciationRule {
+			whole_word: true,
+			..rule("C++", "see plus plus")
+		}]);
+		assert_eq!(dict.apply("C++ code", None), "see plus plus code");
+		assert_eq!(dict.apply("ObjC++", None), "ObjC++");
+	}
+
+	#[test]
+	fn regex_with_captures() {
+		let dict = dictionary(vec![PronunciationRule {
+			kind: PatternKind::Regex,
+			..rule(r"(\d+)px", "$1 pixels")
+		}]);
+		assert_eq!(dict.apply("12px by 30px", None), "12 pixels by 30 pixels");
+	}
+
+	#[test]
+	fn invalid_regex_is_skipped() {
+		let dict = dictionary(vec![
+			PronunciationRule { kind: PatternKind::Regex, ..rule("(", "paren") },
+			rule("foo", "bar"),
+		]);
+		assert_eq!(dict.apply("foo (", None), "bar (");
+	}
+
+	#[test]
+	fn app_scoped_rules() {
+		let dict = dictionary(vec![PronunciationRule {
+			app: Some("Firefox".to_string()),
+			..rule("tab", "browser tab")
+		}]);
+		assert_eq!(dict.apply("new tab", Some("firefox")), "new browser tab");
+		assert_eq!(dict.apply("new tab", Some("gedit")), "new tab");
+		assert_eq!(dict.apply("new tab", None), "new tab");
+	}
+
+	#[test]
+	fn rules_apply_in_order() {
+		let dict = dictionary(vec![rule("a", "b"), rule("b", "c")]);
+		assert_eq!(dict.apply("a", None), "c");
+	}
+
+	#[test]
+	fn no_match_borrows() {
+		let dict = dictionary(vec![rule("x", "y")]);
+		assert!(matches!(dict.apply("abc", None), std::borrow::Cow::Borrowed(_)));
+	}
+}
@@ -0,0 +1,245 @@
+//! Reading a whole document from the caret onwards, one piece at a time.
+//!
+//! Each piece is sent as SSML starting with an index mark naming its offset in the document, so
+//! that when speech-dispatcher reports reaching the mark Odilia knows where reading has got to.
+//! Only a couple of pieces are queued ahead of what is being spoken; the rest are sent as marks
+//! come in, so stopping never leaves much to throw away.
+
+use std::{
+	collections::VecDeque,
+	sync::{Arc, LockResult, Mutex, MutexGuard},
+};
+
+use odilia_common::{
+	cache::AccessiblePrimitive, errors::OdiliaError, protocol::Update,
+	settings::speech::SayAllUnit,
+};
+use odilia_input::Clients;
+
+/// Prefix of every index mark used by say all, so they can be told apart from anyone else's.
+const MARK_PREFIX: &str = "say-all:";
+/// Mark placed after the very last piece.
+const END_MARK: &str = "say-all:end";
+/// How many pieces are queued with the synthesizer when reading starts.
+pub const LOOKAHEAD: usize = 2;
+
+/// A piece of the document, and where it starts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk {
+	/// Character offset of the start of the piece within the whole text.
+	pub offset: usize,
+	pub text: String,
+}
+
+/// How far reading has got, according to an index mark.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Progress {
+	/// Speech has reached the piece starting at this character offset.
+	Reached(usize),
+	/// The last piece has been spoken in full.
+	Finished,
+}
+
+impl Progress {
+	/// Parse the name of an index mark; `None` if the mark was not placed by say all.
+	#[must_use]
+	pub fn from_mark(mark: &str) -> Option<Self> {
+		if mark == END_MARK {
+			return Some(Progress::Finished);
+		}
+		mark.strip_prefix(MARK_PREFIX)?.parse().ok().map(Progress::Reached)
+	}
+}
+
+fn ends_chunk(chars: &[char], i: usize, unit: SayAllUnit) -> bool {
+	match (unit, chars[i]) {
+		(_, '\n') => true,
+		(SayAllUnit::Sentence, '.' | '!' | '?') => {
+			chars.get(i + 1).map_or(true, |next| next.is_whitespace())
+		}
+		_ => false,
+	}
+}
+
+/// Split `text`, starting at character offset `from`, into pieces of the given `unit`.
+/// Whitespace around each piece is trimmed, and pieces with nothing but whitespace are skipped.
+#[must_use]
+pub fn chunks(text: &str, from: usize, unit: SayAllUnit) -> VecDeque<Chunk> {
+	let chars = text.chars().collect::<Vec<char>>();
+	let mut chunks = VecDeque::new();
+	let from = from.min(chars.len());
+	let mut start = from;
+	for i in from..chars.len() {
+		if !ends_chunk(&chars, i, unit) && i + 1 != chars.len() {
+			continue;
+		}
+		let piece = &chars[start..=i];
+		let leading = piece.iter().take_while(|c| c.is_whitespace()).count();
+		let text = piece[leading..].iter().collect::<String>();
+		let text = text.trim_end();
+		if !text.is_empty() {
+			chunks.push_back(Chunk { offset: start + leading, text: text.to_string() });
+		}
+		start = i + 1;
+	}
+	chunks
+}
+
+fn escape(text: &str) -> String {
+	let mut escaped = String::with_capacity(text.len());
+	for c in text.chars() {
+		match c {
+			'&' => escaped.push_str("&amp;"),
+			'<' => escaped.push_str("&lt;"),
+			'>' => escaped.push_str("&gt;"),
+			'"' => escaped.push_str("&quot;"),
+			'\'' => escaped.push_str("&apos;"),
+			c => escaped.push(c),
+		}
+	}
+	escaped
+}
+
+/// A say all in progress.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SayAll {
+	/// The object being read.
+	pub item: AccessiblePrimitive,
+	/// Pieces which have not been sent to the synthesizer yet.
+	remaining: VecDeque<Chunk>,
+}
+
+impl SayAll {
+	#[must_use]
+	pub fn new(item: AccessiblePrimitive, chunks: VecDeque<Chunk>) -> Self {
+		SayAll { item, remaining: chunks }
+	}
+	/// The next piece to send to the synthesizer, as SSML, marked with its offset.
+	/// The last piece is also marked at its end, so that the end of the document is reported.
+	pub fn next_message(&mut self) -> Option<String> {
+		let chunk = self.remaining.pop_front()?;
+		let end = if self.remaining.is_empty() {
+			format!("<mark name=\"{END_MARK}\"/>")
+		} else {
+			String::new()
+		};
+		Some(format!(
+			"<speak><mark name=\"{MARK_PREFIX}{}\"/>{}{end}</speak>",
+			chunk.offset,
+			escape(&chunk.text)
+		))
+	}
+}
+
+/// The say all in progress, if any. Input servers are told whenever one starts or stops, so that
+/// they only interrupt Odilia with keys meant for applications while it is reading.
+#[derive(Debug, Clone)]
+pub struct Reading {
+	current: Arc<Mutex<Option<SayAll>>>,
+	clients: Clients,
+}
+
+impl Reading {
+	#[must_use]
+	pub fn new(clients: Clients) -> Self {
+		Reading { current: Arc::new(Mutex::new(None)), clients }
+	}
+	/// Follow `say_all`, in place of any other.
+	///
+	/// # Errors
+	///
+	/// If the lock is poisoned.
+	pub fn start(&self, say_all: SayAll) -> Result<(), OdiliaError> {
+		*self.current.lock()? = Some(say_all);
+		self.clients.update(Update::Reading(true));
+		Ok(())
+	}
+	/// Stop following the say all in progress, so reading stops at the next mark.
+	/// Returns whether there was one.
+	///
+	/// # Errors
+	///
+	/// If the lock is poisoned.
+	pub fn stop(&self) -> Result<bool, OdiliaError> {
+		let stopped = self.current.lock()?.take().is_some();
+		if stopped {
+			self.clients.update(Update::Reading(false));
+		}
+		Ok(stopped)
+	}
+	/// The say all in progress; use [`Self::stop`] rather than taking it out, so input servers
+	/// are told.
+	///
+	/// # Errors
+	///
+	/// If the lock is poisoned.
+	pub fn lock(&self) -> LockResult<MutexGuard<'_, Option<SayAll>>> {
+		self.current.lock()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use odilia_common::{cache::AccessiblePrimitive, settings::speech::SayAllUnit};
+
+	use super::{chunks, Chunk, Progress, SayAll};
+
+	fn texts(text: &str, from: usize, unit: SayAllUnit) -> Vec<(usize, String)> {
+		chunks(text, from, unit)
+			.into_iter()
+			.map(|Chunk { offset, text }| (offset, text))
+			.collect()
+	}
+
+	#[test]
+	fn sentences() {
+		let text = "Hello there. Version 1.2 is out!\nRead it? Yes";
+		assert_eq!(
+			texts(text, 0, SayAllUnit::Sentence),
+			[
+				(0, "Hello there.".to_string()),
+				(13, "Version 1.2 is out!".to_string()),
+				(33, "Read it?".to_string()),
+				(42, "Yes".to_string()),
+			]
+		);
+		// starting in the middle of a sentence reads the rest of it
+		assert_eq!(texts(text, 6, SayAllUnit::Sentence)[0], (6, "there.".to_string()));
+	}
+
+	#[test]
+	fn lines() {
+		let text = "one. two\n\n  three\n";
+		assert_eq!(
+			texts(text, 0, SayAllUnit::Line),
+			[(0, "one. two".to_string()), (12, "three".to_string())]
+		);
+		assert!(texts(text, 100, SayAllUnit::Line).is_empty());
+	}
+
+	#[test]
+	fn messages_are_marked() {
+		let item = AccessiblePrimitive {
+			id: "/org/a11y/atspi/accessible/1".to_string(),
+			sender: ":1.1".to_string(),
+		};
+		let mut say_all =
+			SayAll::new(item, chunks("a < b. Done.", 0, SayAllUnit::Sentence));
+		assert_eq!(
+			say_all.next_message().as_deref(),
+			Some("<speak><mark name=\"say-all:0\"/>a &lt; b.</speak>")
+		);
+		assert_eq!(
+			say_all.next_message().as_deref(),
+			Some("<speak><mark name=\"say-all:7\"/>Done.<mark name=\"say-all:end\"/></speak>")
+		);
+		assert_eq!(say_all.next_message(), None);
+	}
+
+	#[test]
+	fn parse_marks() {
+		assert_eq!(Progress::from_mark("say-all:42"), Some(Progress::Reached(42)));
+		assert_eq!(Progress::from_mark("say-all:end"), Some(Progress::Finished));
+		assert_eq!(Progress::from_mark("something-else"), None);
+	}
+}
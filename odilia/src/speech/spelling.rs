@@ -0,0 +1,101 @@
+//! Finding the character or word at the caret, and spelling it out.
+
+use super::SymbolTable;
+
+/// The NATO phonetic alphabet, from alpha to zulu.
+const NATO: [&str; 26] = [
+	"alpha", "bravo", "charlie", "delta", "echo", "foxtrot", "golf", "hotel", "india",
+	"juliett", "kilo", "lima", "mike", "november", "oscar", "papa", "quebec", "romeo",
+	"sierra", "tango", "uniform", "victor", "whiskey", "x-ray", "yankee", "zulu",
+];
+
+fn is_word_char(c: char) -> bool {
+	c.is_alphanumeric() || c == '_'
+}
+
+/// The phonetic word for an (ASCII) letter, in either case.
+#[must_use]
+pub fn phonetic(c: char) -> Option<&'static str> {
+	let c = c.to_ascii_lowercase();
+	if !c.is_ascii_lowercase() {
+		return None;
+	}
+	NATO.get(usize::from(c as u8 - b'a')).copied()
+}
+
+/// Spell `text` using the phonetic alphabet; anything which is not a letter is given its name
+/// from `symbols` instead.
+#[must_use]
+pub fn spell_phonetically(text: &str, symbols: &SymbolTable, language: &str) -> String {
+	text.chars()
+		.map(|c| match phonetic(c) {
+			Some(word) => word.to_string(),
+			None => symbols.character(c, language).into_owned(),
+		})
+		.collect::<Vec<String>>()
+		.join(" ")
+}
+
+/// The character at character offset `caret`, if the caret is not at the very end of `text`.
+#[must_use]
+pub fn character_at(text: &str, caret: usize) -> Option<char> {
+	text.chars().nth(caret)
+}
+
+/// The word the caret is in (or directly after, as when the caret is at the end of a line).
+/// Returns `None` if the caret is surrounded by spaces or punctuation.
+#[must_use]
+pub fn word_at(text: &str, caret: usize) -> Option<String> {
+	let chars = text.chars().collect::<Vec<char>>();
+	let caret = caret.min(chars.len());
+	let mut start = caret;
+	// with the caret just after a word, that word is the one meant
+	if chars.get(caret).map_or(true, |c| !is_word_char(*c)) {
+		start = start.checked_sub(1).filter(|prev| is_word_char(chars[*prev]))?;
+	}
+	while start > 0 && is_word_char(chars[start - 1]) {
+		start -= 1;
+	}
+	let word = chars[start..]
+		.iter()
+		.take_while(|c| is_word_char(**c))
+		.collect::<String>();
+	Some(word).filter(|word| !word.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{character_at, phonetic, spell_phonetically, word_at, SymbolTable};
+
+	#[test]
+	fn phonetic_alphabet() {
+		assert_eq!(phonetic('a'), Some("alpha"));
+		assert_eq!(phonetic('Z'), Some("zulu"));
+		assert_eq!(phonetic('1'), None);
+		assert_eq!(phonetic('é'), None);
+		assert_eq!(
+			spell_phonetically("Hi!", &SymbolTable::default(), "en"),
+			"hotel india bang"
+		);
+	}
+
+	#[test]
+	fn character_at_caret() {
+		assert_eq!(character_at("héllo", 1), Some('é'));
+		assert_eq!(character_at("hello", 5), None);
+	}
+
+	#[test]
+	fn word_at_caret() {
+		let text = "hello big world.";
+		assert_eq!(word_at(text, 0).as_deref(), Some("hello"));
+		assert_eq!(word_at(text, 8).as_deref(), Some("big"));
+		// just after a word
+		assert_eq!(word_at(text, 5).as_deref(), Some("hello"));
+		assert_eq!(word_at(text, 15).as_deref(), Some("world"));
+		assert_eq!(word_at(text, 16), None);
+		assert_eq!(word_at("a  b", 2), None);
+		assert_eq!(word_at("ab", 2).as_deref(), Some("ab"));
+		assert_eq!(word_at("", 0), None);
+	}
+}
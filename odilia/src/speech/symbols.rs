@@ -0,0 +1,249 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use odilia_common::settings::{
+	speech::{NumberReading, PunctuationSpellingMode},
+	SymbolSettings,
+};
+
+/// Odilia's own names for symbols, with the lowest punctuation level at which each is spoken.
+/// These are used for any language the user has not provided names for.
+const BUILTIN: &[(char, &str, PunctuationSpellingMode)] = &[
+	('#', "number", PunctuationSpellingMode::Some),
+	('$', "dollar", PunctuationSpellingMode::Some),
+	('%', "percent", PunctuationSpellingMode::Some),
+	('&', "and", PunctuationSpellingMode::Some),
+	('*', "star", PunctuationSpellingMode::Some),
+	('+', "plus", PunctuationSpellingMode::Some),
+	('/', "slash", PunctuationSpellingMode::Some),
+	('<', "less", PunctuationSpellingMode::Some),
+	('=', "equals", PunctuationSpellingMode::Some),
+	('>', "greater", PunctuationSpellingMode::Some),
+	('@', "at", PunctuationSpellingMode::Some),
+	('"', "quote", PunctuationSpellingMode::Most),
+	('(', "left paren", PunctuationSpellingMode::Most),
+	(')', "right paren", PunctuationSpellingMode::Most),
+	('-', "dash", PunctuationSpellingMode::Most),
+	(':', "colon", PunctuationSpellingMode::Most),
+	(';', "semicolon", PunctuationSpellingMode::Most),
+	('[', "left bracket", PunctuationSpellingMode::Most),
+	('\\', "backslash", PunctuationSpellingMode::Most),
+	(']', "right bracket", PunctuationSpellingMode::Most),
+	('^', "caret", PunctuationSpellingMode::Most),
+	('_', "underline", PunctuationSpellingMode::Most),
+	('`', "grave", PunctuationSpellingMode::Most),
+	('{', "left brace", PunctuationSpellingMode::Most),
+	('|', "bar", PunctuationSpellingMode::Most),
+	('}', "right brace", PunctuationSpellingMode::Most),
+	('~', "tilde", PunctuationSpellingMode::Most),
+	('!', "bang", PunctuationSpellingMode::All),
+	('\'', "tick", PunctuationSpellingMode::All),
+	(',', "comma", PunctuationSpellingMode::All),
+	('.', "dot", PunctuationSpellingMode::All),
+	('?', "question", PunctuationSpellingMode::All),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Symbol {
+	name: String,
+	level: PunctuationSpellingMode,
+}
+
+/// Names for symbols, so that punctuation is read the same way no matter which synthesizer is in
+/// use.
+#[derive(Debug, Clone)]
+pub struct SymbolTable {
+	/// The user's names, keyed by language; names for every language are under `""`.
+	user: HashMap<String, HashMap<char, Symbol>>,
+	builtin: HashMap<char, Symbol>,
+}
+
+impl Default for SymbolTable {
+	fn default() -> Self {
+		SymbolTable::new(&SymbolSettings::default())
+	}
+}
+
+/// Named symbols and split digits are separated from what is around them by a single space.
+fn separate(expanded: &mut String, word: &str) {
+	if !expanded.is_empty() && !expanded.ends_with(char::is_whitespace) {
+		expanded.push(' ');
+	}
+	expanded.push_str(word);
+	expanded.push(' ');
+}
+
+/// The primary language subtag, which is all that is compared: `en-US` and `en_GB` are both `en`.
+fn primary_language(language: &str) -> String {
+	language.split(['-', '_']).next().unwrap_or_default().to_lowercase()
+}
+
+impl SymbolTable {
+	#[must_use]
+	pub fn new(settings: &SymbolSettings) -> Self {
+		let builtin = BUILTIN
+			.iter()
+			.map(|(c, name, level)| {
+				(*c, Symbol { name: (*name).to_string(), level: *level })
+			})
+			.collect();
+		let mut user: HashMap<String, HashMap<char, Symbol>> = HashMap::new();
+		for entry in &settings.symbols {
+			let language =
+				entry.language.as_deref().map(primary_language).unwrap_or_default();
+			user.entry(language).or_default().insert(
+				entry.symbol,
+				Symbol { name: entry.name.clone(), level: entry.level },
+			);
+		}
+		SymbolTable { user, builtin }
+	}
+
+	fn lookup(&self, c: char, language: &str) -> Option<&Symbol> {
+		let language = primary_language(language);
+		[language.as_str(), ""]
+			.iter()
+			.find_map(|lang| self.user.get(*lang)?.get(&c))
+			.or_else(|| self.builtin.get(&c))
+	}
+
+	/// The name of a single character, read by itself: symbols are always named, no matter the
+	/// punctuation level, and characters which would be silent are given a name as well.
+	/// Letters and digits are returned as they are.
+	#[must_use]
+	pub fn character(&self, c: char, language: &str) -> Cow<'_, str> {
+		if let Some(symbol) = self.lookup(c, language) {
+			return Cow::Borrowed(&symbol.name);
+		}
+		match c {
+			' ' => Cow::Borrowed("space"),
+			'\n' => Cow::Borrowed("new line"),
+			'\t' => Cow::Borrowed("tab"),
+			c => Cow::Owned(c.to_string()),
+		}
+	}
+
+	/// Replace every symbol in `text` which should be spoken at the `punctuation` level with its
+	/// name, and split up numbers if they should be read one digit at a time.
+	/// Symbols which are not named are left for the synthesizer to deal with.
+	#[must_use]
+	pub fn expand<'a>(
+		&self,
+		text: &'a str,
+		language: &str,
+		punctuation: PunctuationSpellingMode,
+		numbers: NumberReading,
+	) -> Cow<'a, str> {
+		let split_digits = numbers == NumberReading::Digits;
+		let named = |c: char| {
+			self.lookup(c, language)
+				.filter(|symbol| punctuation.includes(symbol.level))
+		};
+		if !text.chars()
+			.any(|c| named(c).is_some() || (split_digits && c.is_ascii_digit()))
+		{
+			return Cow::Borrowed(text);
+		}
+		let mut expanded = String::with_capacity(text.len() * 2);
+		for c in text.chars() {
+			if let Some(symbol) = named(c) {
+				separate(&mut expanded, &symbol.name);
+			} else if split_digits && c.is_ascii_digit() {
+				separate(&mut expanded, c.encode_utf8(&mut [0; 4]));
+			} else if c == ' ' && expanded.ends_with(' ') {
+				// already separated
+			} else {
+				expanded.push(c);
+			}
+		}
+		Cow::Owned(expanded.trim_end().to_string())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use odilia_common::settings::{
+		speech::{NumberReading, PunctuationSpellingMode},
+		symbols::SymbolEntry,
+		SymbolSettings,
+	};
+
+	use super::SymbolTable;
+
+	fn expand(text: &str, punctuation: PunctuationSpellingMode) -> String {
+		SymbolTable::default()
+			.expand(text, "en-US", punctuation, NumberReading::Whole)
+			.into_owned()
+	}
+
+	#[test]
+	fn punctuation_levels() {
+		let text = "Save (ctrl+s) now.";
+		assert_eq!(expand(text, PunctuationSpellingMode::None), text);
+		assert_eq!(expand(text, PunctuationSpellingMode::Some), "Save (ctrl plus s) now.");
+		assert_eq!(
+			expand(text, PunctuationSpellingMode::Most),
+			"Save left paren ctrl plus s right paren now."
+		);
+		assert_eq!(
+			expand(text, PunctuationSpellingMode::All),
+			"Save left paren ctrl plus s right paren now dot"
+		);
+	}
+
+	#[test]
+	fn single_characters_are_always_named() {
+		let table = SymbolTable::default();
+		assert_eq!(table.character('.', "en"), "dot");
+		assert_eq!(table.character(' ', "en"), "space");
+		assert_eq!(table.character('a', "en"), "a");
+	}
+
+	#[test]
+	fn digits() {
+		let table = SymbolTable::default();
+		assert_eq!(
+			table.expand(
+				"room 101",
+				"en",
+				PunctuationSpellingMode::None,
+				NumberReading::Digits
+			),
+			"room 1 0 1"
+		);
+		assert_eq!(
+			table.expand(
+				"room 101",
+				"en",
+				PunctuationSpellingMode::None,
+				NumberReading::Whole
+			),
+			"room 101"
+		);
+	}
+
+	#[test]
+	fn localized_names() {
+		let settings = SymbolSettings {
+			symbols: vec![
+				SymbolEntry {
+					symbol: '#',
+					name: "Raute".to_string(),
+					level: PunctuationSpellingMode::Some,
+					language: Some("de".to_string()),
+				},
+				SymbolEntry {
+					symbol: '§',
+					name: "section".to_string(),
+					level: PunctuationSpellingMode::Some,
+					language: None,
+				},
+			],
+		};
+		let table = SymbolTable::new(&settings);
+		assert_eq!(table.character('#', "de-DE"), "Raute");
+		assert_eq!(table.character('#', "en-US"), "number");
+		assert_eq!(table.character('§', "de"), "section");
+		// languages without their own names fall back to the built in ones
+		assert_eq!(table.character('$', "de"), "dollar");
+	}
+}
@@ -1,31 +1,60 @@
 use std::{
+	collections::BTreeMap,
 	fmt,
 	fmt::Debug,
+	future::Future,
+	pin::Pin,
 	process::Child,
-	sync::{atomic::AtomicUsize, Arc, Mutex},
+	sync::{
+		atomic::{AtomicUsize, Ordering},
+		Arc, Mutex,
+	},
+	time::{Duration, Instant, SystemTime},
 };
 
-use async_channel::Sender;
 use atspi::{
 	connection::AccessibilityConnection,
 	events::{DBusMatchRule, RegistryEventString},
-	Event,
+	proxy::text::TextProxy,
+	Event, Interface,
 };
 use circular_queue::CircularQueue;
 use futures_util::future::{err, ok, Ready};
-use odilia_cache::{CacheActor, CacheItem, CacheRequest, CacheResponse, Item};
+use odilia_braille::{BrailleDisplay, Translator, Viewport};
+use odilia_cache::{
+	CacheActor, CacheItem, CacheKey, CacheRequest, CacheResponse, Convertable, Item,
+};
 use odilia_common::{
 	cache::AccessiblePrimitive,
 	command::CommandType,
 	errors::OdiliaError,
-	events::EventType,
-	settings::{speech::PunctuationSpellingMode, ApplicationConfig},
+	events::{EventType, ScreenReaderEventDiscriminants},
+	modes::ScreenReaderMode,
+	protocol::Update,
+	settings::{ApplicationConfig, SpeechSettings},
 	Result as OdiliaResult,
 };
-use ssip_client_async::{Priority, PunctuationMode, Request as SSIPRequest};
+use odilia_input::Clients;
+use odilia_tts::SpeechBackend;
+use ssip_client_async::Priority;
 use tracing::{Instrument, Level};
+use zbus::proxy::CacheProperties;
+
+use crate::{
+	speech::{
+		language::{self, ActiveVoice, LanguageCache, ObjectLanguage},
+		say_all::{self, Progress},
+		HistoryEntry, PronunciationDictionary, SpeechHistory, SymbolTable,
+	},
+	tower::from_state::TryFromState,
+};
 
-use crate::tower::from_state::TryFromState;
+/// How many utterances are kept in [`ScreenReaderState::speech_history`].
+const SPEECH_HISTORY_SIZE: usize = 100;
+/// `LC_MESSAGES`, as AT-SPI numbers locale categories; the locale used for an application's text.
+const LOCALE_MESSAGES: u32 = 0;
+/// Two presses of the same key binding closer together than this count as a [`DoublePress`].
+const DOUBLE_PRESS_TIMEOUT: Duration = Duration::from_millis(500);
 
 impl Debug for ScreenReaderState {
 	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
@@ -36,12 +65,33 @@ impl Debug for ScreenReaderState {
 #[allow(clippy::module_name_repetitions)]
 pub struct ScreenReaderState {
 	pub atspi: AccessibilityConnection,
-	pub ssip: Sender<SSIPRequest>,
+	pub speech: Arc<dyn SpeechBackend>,
+	pub braille: Arc<dyn BrailleDisplay>,
+	pub braille_translator: Arc<dyn Translator>,
+	/// The line on the braille display, and how far it has been panned.
+	pub braille_viewport: Arc<Mutex<Viewport>>,
+	/// The speech settings currently in effect; these start out as `config.speech`, but may be changed at runtime.
+	pub speech_settings: Arc<Mutex<SpeechSettings>>,
+	pub pronunciation: Arc<PronunciationDictionary>,
+	pub symbols: Arc<SymbolTable>,
 	pub previous_caret_position: Arc<AtomicUsize>,
 	pub accessible_history: Arc<Mutex<CircularQueue<AccessiblePrimitive>>>,
+	/// Everything which has been spoken recently, so the user can review it.
+	pub speech_history: Arc<Mutex<SpeechHistory>>,
+	/// When each kind of input event was last received, to detect double presses.
+	pub input_presses: Arc<Mutex<BTreeMap<ScreenReaderEventDiscriminants, Instant>>>,
+	/// The document being read from the caret onwards, if a say all is in progress.
+	pub say_all: say_all::Reading,
+	/// The language and voice speech-dispatcher is currently set to use.
+	pub active_voice: Arc<Mutex<ActiveVoice>>,
+	/// The language of the focused object, shared by speech and braille.
+	pub language_cache: Arc<Mutex<LanguageCache>>,
 	pub cache_actor: CacheActor,
 	pub config: Arc<ApplicationConfig>,
 	pub children_pids: Arc<Mutex<Vec<Child>>>,
+	/// The input servers connected to Odilia, which are told when its mode, features or
+	/// keybindings change.
+	pub input_servers: Clients,
 }
 #[derive(Debug, Clone)]
 pub struct AccessibleHistory(pub Arc<Mutex<CircularQueue<AccessiblePrimitive>>>);
@@ -67,7 +117,67 @@ pub struct LastFocused(pub AccessiblePrimitive);
 pub struct CurrentCaretPos(pub Arc<AtomicUsize>);
 #[derive(Debug, Clone)]
 pub struct LastCaretPos(pub usize);
-pub struct Speech(pub Sender<SSIPRequest>);
+pub struct Speech(pub Arc<dyn SpeechBackend>);
+pub struct BrailleOutput(pub Arc<dyn BrailleDisplay>);
+/// The line on the braille display, for panning and routing keys.
+#[derive(Debug, Clone)]
+pub struct BrailleView(pub Arc<Mutex<Viewport>>);
+/// The connection to the accessibility bus, to act on objects through.
+#[derive(Debug, Clone)]
+pub struct AccessibilityBus(pub zbus::Connection);
+/// The translator for braille output, and the table to use for the focused content.
+#[derive(Debug, Clone)]
+pub struct BrailleTranslation {
+	pub translator: Arc<dyn Translator>,
+	pub table: String,
+}
+#[derive(Debug, Clone)]
+pub struct LiveSpeechSettings(pub Arc<Mutex<SpeechSettings>>);
+#[derive(Debug, Clone)]
+pub struct Pronunciation(pub Arc<PronunciationDictionary>);
+#[derive(Debug, Clone)]
+pub struct Symbols(pub Arc<SymbolTable>);
+#[derive(Debug, Clone)]
+pub struct SpokenHistory(pub Arc<Mutex<SpeechHistory>>);
+/// The say all in progress, if any; stopping it stops reading at the next mark.
+#[derive(Debug, Clone)]
+pub struct SayAllState(pub say_all::Reading);
+/// The language and voice to speak with, chosen for the language of the focused content.
+/// These must be [applied](Self::apply) before speaking.
+#[derive(Debug, Clone)]
+pub struct UtteranceVoice {
+	pub voice: ActiveVoice,
+	active: Arc<Mutex<ActiveVoice>>,
+}
+
+impl UtteranceVoice {
+	/// `voice`, for an utterance on a speech backend which is using `active`.
+	#[must_use]
+	pub fn new(voice: ActiveVoice, active: Arc<Mutex<ActiveVoice>>) -> Self {
+		UtteranceVoice { voice, active }
+	}
+	/// Switch speech-dispatcher to [`Self::voice`], if it is not using it already.
+	///
+	/// # Errors
+	///
+	/// If switching fails; it will be tried again before the next utterance.
+	pub async fn apply(&self, speech: &dyn SpeechBackend) -> OdiliaResult<()> {
+		language::switch(&self.active, &self.voice, speech).await
+	}
+}
+/// The most recently focused item, if there is one and it is still in the cache.
+#[derive(Debug, Clone)]
+pub struct FocusedItem(pub Option<CacheItem>);
+/// Whether this input event is the second of two in quick succession.
+/// A third press starts over, and counts as a single press again.
+#[derive(Debug, Clone, Copy)]
+pub struct DoublePress(pub bool);
+/// The name of the application which contains the most recently focused item, if it can be found.
+#[derive(Debug, Clone)]
+pub struct ActiveAppName(pub Option<String>);
+/// The connected input servers, to tell about changes they depend on, like the mode.
+#[derive(Debug, Clone)]
+pub struct InputServers(pub Clients);
 #[derive(Debug)]
 pub struct Command<T>(pub T)
 where
@@ -102,12 +212,188 @@ where
 
 impl<C> TryFromState<Arc<ScreenReaderState>, C> for Speech
 where
-	C: CommandType + Debug,
+	C: Debug,
 {
 	type Error = OdiliaError;
 	type Future = Ready<Result<Speech, Self::Error>>;
 	fn try_from_state(state: Arc<ScreenReaderState>, _cmd: C) -> Self::Future {
-		ok(Speech(state.ssip.clone()))
+		ok(Speech(Arc::clone(&state.speech)))
+	}
+}
+
+impl<C> TryFromState<Arc<ScreenReaderState>, C> for BrailleOutput
+where
+	C: Debug,
+{
+	type Error = OdiliaError;
+	type Future = Ready<Result<BrailleOutput, Self::Error>>;
+	fn try_from_state(state: Arc<ScreenReaderState>, _cmd: C) -> Self::Future {
+		ok(BrailleOutput(Arc::clone(&state.braille)))
+	}
+}
+
+impl<C> TryFromState<Arc<ScreenReaderState>, C> for BrailleView
+where
+	C: Debug,
+{
+	type Error = OdiliaError;
+	type Future = Ready<Result<BrailleView, Self::Error>>;
+	fn try_from_state(state: Arc<ScreenReaderState>, _cmd: C) -> Self::Future {
+		ok(BrailleView(Arc::clone(&state.braille_viewport)))
+	}
+}
+
+impl<C> TryFromState<Arc<ScreenReaderState>, C> for AccessibilityBus
+where
+	C: Debug,
+{
+	type Error = OdiliaError;
+	type Future = Ready<Result<AccessibilityBus, Self::Error>>;
+	fn try_from_state(state: Arc<ScreenReaderState>, _cmd: C) -> Self::Future {
+		ok(AccessibilityBus(state.connection().clone()))
+	}
+}
+
+impl<C> TryFromState<Arc<ScreenReaderState>, C> for BrailleTranslation
+where
+	C: Debug,
+{
+	type Error = OdiliaError;
+	type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>> + Send + 'static>>;
+	fn try_from_state(state: Arc<ScreenReaderState>, _cmd: C) -> Self::Future {
+		Box::pin(async move {
+			Ok(BrailleTranslation {
+				translator: Arc::clone(&state.braille_translator),
+				table: state.braille_table().await,
+			})
+		})
+	}
+}
+
+impl<E> TryFromState<Arc<ScreenReaderState>, E> for LiveSpeechSettings
+where
+	E: Debug,
+{
+	type Error = OdiliaError;
+	type Future = Ready<Result<Self, Self::Error>>;
+	fn try_from_state(state: Arc<ScreenReaderState>, _event: E) -> Self::Future {
+		ok(LiveSpeechSettings(Arc::clone(&state.speech_settings)))
+	}
+}
+
+impl<E> TryFromState<Arc<ScreenReaderState>, E> for SpokenHistory
+where
+	E: Debug,
+{
+	type Error = OdiliaError;
+	type Future = Ready<Result<Self, Self::Error>>;
+	fn try_from_state(state: Arc<ScreenReaderState>, _event: E) -> Self::Future {
+		ok(SpokenHistory(Arc::clone(&state.speech_history)))
+	}
+}
+
+impl<E> TryFromState<Arc<ScreenReaderState>, E> for SayAllState
+where
+	E: Debug,
+{
+	type Error = OdiliaError;
+	type Future = Ready<Result<Self, Self::Error>>;
+	fn try_from_state(state: Arc<ScreenReaderState>, _event: E) -> Self::Future {
+		ok(SayAllState(state.say_all.clone()))
+	}
+}
+
+impl<E> TryFromState<Arc<ScreenReaderState>, E> for InputServers
+where
+	E: Debug,
+{
+	type Error = OdiliaError;
+	type Future = Ready<Result<Self, Self::Error>>;
+	fn try_from_state(state: Arc<ScreenReaderState>, _event: E) -> Self::Future {
+		ok(InputServers(state.input_servers.clone()))
+	}
+}
+
+impl<E> TryFromState<Arc<ScreenReaderState>, E> for Symbols
+where
+	E: Debug,
+{
+	type Error = OdiliaError;
+	type Future = Ready<Result<Self, Self::Error>>;
+	fn try_from_state(state: Arc<ScreenReaderState>, _event: E) -> Self::Future {
+		ok(Symbols(Arc::clone(&state.symbols)))
+	}
+}
+
+impl<E> TryFromState<Arc<ScreenReaderState>, E> for Pronunciation
+where
+	E: Debug,
+{
+	type Error = OdiliaError;
+	type Future = Ready<Result<Self, Self::Error>>;
+	fn try_from_state(state: Arc<ScreenReaderState>, _event: E) -> Self::Future {
+		ok(Pronunciation(Arc::clone(&state.pronunciation)))
+	}
+}
+
+impl<E> TryFromState<Arc<ScreenReaderState>, E> for ActiveAppName
+where
+	E: Debug,
+{
+	type Error = OdiliaError;
+	type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>> + Send + 'static>>;
+	fn try_from_state(state: Arc<ScreenReaderState>, _event: E) -> Self::Future {
+		Box::pin(async move { Ok(ActiveAppName(state.active_app_name().await)) })
+	}
+}
+
+impl<E> TryFromState<Arc<ScreenReaderState>, E> for FocusedItem
+where
+	E: Debug,
+{
+	type Error = OdiliaError;
+	type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>> + Send + 'static>>;
+	fn try_from_state(state: Arc<ScreenReaderState>, _event: E) -> Self::Future {
+		Box::pin(async move {
+			let Some(focused) = state.history_item(0) else {
+				return Ok(FocusedItem(None));
+			};
+			Ok(FocusedItem(state.cache_item(focused).await.ok()))
+		})
+	}
+}
+
+impl<E> TryFromState<Arc<ScreenReaderState>, E> for UtteranceVoice
+where
+	E: Debug,
+{
+	type Error = OdiliaError;
+	type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>> + Send + 'static>>;
+	fn try_from_state(state: Arc<ScreenReaderState>, _event: E) -> Self::Future {
+		Box::pin(async move { state.utterance_voice().await })
+	}
+}
+
+impl<E> TryFromState<Arc<ScreenReaderState>, E> for DoublePress
+where
+	E: EventType,
+{
+	type Error = OdiliaError;
+	type Future = Ready<Result<Self, Self::Error>>;
+	fn try_from_state(state: Arc<ScreenReaderState>, _event: E) -> Self::Future {
+		let Ok(mut presses) = state.input_presses.lock() else {
+			return err(OdiliaError::Generic(
+				"Could not get a lock on the input press times".to_string(),
+			));
+		};
+		let now = Instant::now();
+		let double = presses
+			.remove(&E::ETYPE)
+			.is_some_and(|last| now.duration_since(last) < DOUBLE_PRESS_TIMEOUT);
+		if !double {
+			presses.insert(E::ETYPE, now);
+		}
+		ok(DoublePress(double))
 	}
 }
 
@@ -118,10 +404,7 @@ where
 	type Error = OdiliaError;
 	type Future = Ready<Result<Self, Self::Error>>;
 	fn try_from_state(state: Arc<ScreenReaderState>, _event: E) -> Self::Future {
-		ok(LastCaretPos(
-			state.previous_caret_position
-				.load(core::sync::atomic::Ordering::Relaxed),
-		))
+		ok(LastCaretPos(state.previous_caret_position.load(Ordering::Relaxed)))
 	}
 }
 
@@ -153,8 +436,11 @@ where
 impl ScreenReaderState {
 	#[tracing::instrument(skip_all)]
 	pub async fn new(
-		ssip: Sender<SSIPRequest>,
+		speech: Arc<dyn SpeechBackend>,
+		braille: Arc<dyn BrailleDisplay>,
 		config: ApplicationConfig,
+		pronunciation: PronunciationDictionary,
+		symbols: SymbolTable,
 		cache_actor: CacheActor,
 	) -> Result<ScreenReaderState, OdiliaError> {
 		let atspi = AccessibilityConnection::new()
@@ -165,56 +451,35 @@ impl ScreenReaderState {
 
 		let previous_caret_position = Arc::new(AtomicUsize::new(0));
 		let accessible_history = Arc::new(Mutex::new(CircularQueue::with_capacity(16)));
-		ssip.send(SSIPRequest::SetPitch(
-			ssip_client_async::ClientScope::Current,
-			config.speech.pitch,
-		))
-		.await?;
-		ssip.send(SSIPRequest::SetVolume(
-			ssip_client_async::ClientScope::Current,
-			config.speech.volume,
-		))
-		.await?;
-		ssip.send(SSIPRequest::SetOutputModule(
-			ssip_client_async::ClientScope::Current,
-			config.speech.module.clone(),
-		))
-		.await?;
-		ssip.send(SSIPRequest::SetLanguage(
-			ssip_client_async::ClientScope::Current,
-			config.speech.language.clone(),
-		))
-		.await?;
-		ssip.send(SSIPRequest::SetSynthesisVoice(
-			ssip_client_async::ClientScope::Current,
-			config.speech.person.clone(),
-		))
-		.await?;
-		//doing it this way for now. It could have been done with a From impl, but I don't want to make ssip_client_async a dependency of odilia_common, so this conversion is done directly inside state, especially since this enum isn't supposed to grow any further, in complexity or variants
-		let punctuation_mode = match config.speech.punctuation {
-			PunctuationSpellingMode::Some => PunctuationMode::Some,
-			PunctuationSpellingMode::Most => PunctuationMode::Most,
-			PunctuationSpellingMode::None => PunctuationMode::None,
-			PunctuationSpellingMode::All => PunctuationMode::All,
-		};
-		ssip.send(SSIPRequest::SetPunctuationMode(
-			ssip_client_async::ClientScope::Current,
-			punctuation_mode,
-		))
-		.await?;
-		ssip.send(SSIPRequest::SetRate(
-			ssip_client_async::ClientScope::Current,
-			config.speech.rate,
-		))
-		.await?;
+		speech.apply_settings(&config.speech).await?;
+		let speech_settings = Arc::new(Mutex::new(config.speech.clone()));
+		let active_voice = Arc::new(Mutex::new(language::select(&config.speech, None)));
+		let speech_history =
+			Arc::new(Mutex::new(SpeechHistory::with_capacity(SPEECH_HISTORY_SIZE)));
+		// input servers which connect are told what they need to know straight away
+		let input_servers = Clients::new(&config.input);
+		input_servers.update(Update::Mode(ScreenReaderMode::Focus));
+		input_servers.update(Update::Keybindings(config.keybindings.clone()));
 		Ok(Self {
 			atspi,
-			ssip,
+			speech,
+			braille,
+			braille_translator: Arc::from(odilia_braille::default_translator()),
+			braille_viewport: Arc::new(Mutex::new(Viewport::default())),
+			speech_settings,
+			pronunciation: Arc::new(pronunciation),
+			symbols: Arc::new(symbols),
 			previous_caret_position,
 			accessible_history,
+			speech_history,
+			input_presses: Arc::new(Mutex::new(BTreeMap::new())),
+			say_all: say_all::Reading::new(input_servers.clone()),
+			active_voice,
+			language_cache: Arc::new(Mutex::new(LanguageCache::default())),
 			cache_actor,
 			config: Arc::new(config),
 			children_pids: Arc::new(Mutex::new(Vec::new())),
+			input_servers,
 		})
 	}
 
@@ -248,28 +513,199 @@ impl ScreenReaderState {
 	}
 	#[tracing::instrument(name = "closing speech dispatcher connection", skip(self))]
 	pub async fn close_speech(&self) -> bool {
-		self.ssip.send(SSIPRequest::Quit).await.is_ok()
+		self.speech.close().await.is_ok()
 	}
 	#[tracing::instrument(skip(self))]
 	pub async fn say(&self, priority: Priority, text: String) -> bool {
-		if self.ssip.send(SSIPRequest::SetPriority(priority)).await.is_err() {
-			return false;
+		if let Ok(mut history) = self.speech_history.lock() {
+			history.push(HistoryEntry {
+				text: text.clone(),
+				priority: priority.clone(),
+				time: SystemTime::now(),
+				source: self.history_item(0),
+			});
 		}
-		if self.ssip.send(SSIPRequest::Speak).await.is_err() {
+		let app = self.active_app_name().await;
+		let text = self.pronunciation.apply(&text, app.as_deref());
+		let Ok(utterance) = self.utterance_voice().await else {
 			return false;
-		}
-		// this crashed ssip-client because the connection is automatically stopped when invalid text is sent; since the period character on a line by itself is the stop character, there's not much we can do except filter it out explicitly.
-		if text == *"." {
+		};
+		let text = match self.speech_settings.lock() {
+			Ok(settings) => self
+				.symbols
+				.expand(
+					&text,
+					&utterance.voice.language,
+					settings.punctuation,
+					settings.numbers,
+				)
+				.into_owned(),
+			Err(_) => text.into_owned(),
+		};
+		if utterance.apply(&*self.speech).await.is_err() {
 			return false;
 		}
-		if self.ssip
-			.send(SSIPRequest::SendLines(Vec::from([text])))
+		self.speech.speak(priority, text).await.is_ok()
+	}
+
+	/// The language of the focused content, according to the application.
+	/// In order, this comes from the focused object's attributes, the text at the caret, the
+	/// object's locale, and finally the locale of the application.
+	/// Only the text at the caret is asked about each time; the rest is kept in
+	/// [`Self::language_cache`] for as long as the object has focus.
+	#[tracing::instrument(skip(self), level = "trace", ret)]
+	pub async fn content_language(&self) -> Option<String> {
+		let focused = self.history_item(0)?;
+		let cached = self.language_cache.lock().ok()?.get(&focused).cloned();
+		let object = if let Some(object) = cached {
+			object
+		} else {
+			let object = self.object_language(focused.clone()).await?;
+			self.language_cache
+				.lock()
+				.ok()?
+				.insert(focused.clone(), object.clone());
+			object
+		};
+		let at_caret = if object.needs_caret() {
+			self.caret_language(focused).await
+		} else {
+			None
+		};
+		object.resolve(at_caret)
+	}
+
+	/// Everything about the language of `focused` which does not depend on the caret.
+	async fn object_language(&self, focused: AccessiblePrimitive) -> Option<ObjectLanguage> {
+		let accessible = focused.clone().into_accessible(self.connection()).await.ok()?;
+		let attributes = accessible
+			.get_attributes()
+			.await
+			.ok()
+			.and_then(|attributes| language::from_attributes(&attributes));
+		if attributes.is_some() {
+			// nothing else would be used
+			return Some(ObjectLanguage { attributes, ..ObjectLanguage::default() });
+		}
+		let text = accessible
+			.get_interfaces()
+			.await
+			.is_ok_and(|interfaces| interfaces.contains(Interface::Text));
+		let locale = match accessible
+			.locale()
 			.await
-			.is_err()
+			.ok()
+			.and_then(|tag| language::normalize(&tag))
 		{
-			return false;
+			Some(locale) => Some(locale),
+			None => self.application_language(focused).await,
+		};
+		Some(ObjectLanguage { attributes, text, locale })
+	}
+
+	/// The language of the text at the caret in `focused`, which must have text.
+	async fn caret_language(&self, focused: AccessiblePrimitive) -> Option<String> {
+		// the interface was already checked for when the object's language was cached
+		let text = TextProxy::builder(self.connection())
+			.destination(focused.sender)
+			.ok()?
+			.path(focused.id)
+			.ok()?
+			.cache_properties(CacheProperties::No)
+			.build()
+			.await
+			.ok()?;
+		let caret = self.previous_caret_position.load(Ordering::Relaxed);
+		let caret = i32::try_from(caret).unwrap_or(i32::MAX);
+		text.get_attribute_value(caret, "language")
+			.await
+			.ok()
+			.and_then(|tag| language::normalize(&tag))
+	}
+
+	/// The language of the application containing `focused`.
+	async fn application_language(&self, focused: AccessiblePrimitive) -> Option<String> {
+		let app = self.cache_item(focused).await.ok()?.app;
+		let app = app.into_accessible(self.connection()).await.ok()?;
+		let locale = app
+			.to_application()
+			.await
+			.ok()?
+			.get_locale(LOCALE_MESSAGES)
+			.await
+			.ok()?;
+		language::normalize(&locale)
+	}
+
+	/// The braille table for the focused content: the one the user chose for its language, if
+	/// there is one.
+	pub async fn braille_table(&self) -> String {
+		let settings = &self.config.braille;
+		let content =
+			if settings.follow_language { self.content_language().await } else { None };
+		content.as_deref()
+			.and_then(|language| language::for_language(&settings.tables, language))
+			.unwrap_or(&settings.table)
+			.to_string()
+	}
+
+	/// Choose the language and voice for the next utterance.
+	pub async fn utterance_voice(&self) -> OdiliaResult<UtteranceVoice> {
+		let follow = self.speech_settings.lock()?.follow_language;
+		let content = if follow { self.content_language().await } else { None };
+		let voice = language::select(&*self.speech_settings.lock()?, content.as_deref());
+		Ok(UtteranceVoice::new(voice, Arc::clone(&self.active_voice)))
+	}
+
+	/// speech-dispatcher was reconnected to, and set back to the live speech settings; whatever
+	/// language and voice it was switched to for the content before is no longer in use.
+	pub fn speech_reconnected(&self) -> OdiliaResult<()> {
+		let voice = language::select(&*self.speech_settings.lock()?, None);
+		*self.active_voice.lock()? = voice;
+		Ok(())
+	}
+
+	/// Follow a say all along as speech-dispatcher reports reaching each of its index marks.
+	/// The caret position Odilia remembers, which commands like reading the current character
+	/// start from, is moved to the start of the piece being read; the application's own caret is
+	/// left where it was. The next piece is queued so there is always something ahead of it.
+	#[tracing::instrument(skip(self), level = "debug", err)]
+	pub async fn say_all_progress(&self, progress: Progress) -> OdiliaResult<()> {
+		let next = {
+			let mut say_all = self.say_all.lock()?;
+			let (Progress::Reached(offset), Some(reading)) =
+				(progress, say_all.as_mut())
+			else {
+				drop(say_all);
+				self.say_all.stop()?;
+				return Ok(());
+			};
+			self.previous_caret_position.store(offset, Ordering::Relaxed);
+			reading.next_message()
+		};
+		match next {
+			Some(ssml) => self.speech.speak_ssml(Priority::Text, ssml).await,
+			None => Ok(()),
 		}
-		true
+	}
+
+	async fn cache_item(&self, key: CacheKey) -> OdiliaResult<CacheItem> {
+		match self.cache_actor.request(CacheRequest::Item(key)).await? {
+			CacheResponse::Item(Item(ci)) => Ok(ci),
+			e => Err(format!(
+				"Inappropriate response from cache for `Item` request: {e:?}"
+			)
+			.into()),
+		}
+	}
+
+	/// The name of the application containing the most recently focused item.
+	/// This is `None` if nothing has been focused yet, or the application has no name.
+	#[tracing::instrument(skip(self), level = "trace", ret)]
+	pub async fn active_app_name(&self) -> Option<String> {
+		let focused = self.history_item(0)?;
+		let item = self.cache_item(focused).await.ok()?;
+		self.cache_item(item.app).await.ok()?.name
 	}
 
 	pub fn history_item(&self, index: usize) -> Option<AccessiblePrimitive> {
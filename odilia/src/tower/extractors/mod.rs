@@ -2,7 +2,11 @@ mod cache_event;
 pub use cache_event::{ActiveAppEvent, CacheEvent, NonContainerEvent};
 mod event_property;
 pub use event_property::{EventProp, GetProperty, PropertyType};
+mod parent;
+pub use parent::ParentItem;
 mod relation_set;
 pub use relation_set::RelationSet;
 mod subtree;
 pub use subtree::Subtree;
+mod shortcut;
+pub use shortcut::Shortcut;
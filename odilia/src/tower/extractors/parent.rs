@@ -0,0 +1,36 @@
+use odilia_cache::{CacheActor, CacheItem, CacheRequest, CacheResponse, Parent};
+
+use crate::{
+	tower::{EventProp, GetProperty, PropertyType},
+	OdiliaError,
+};
+
+/// A property type that returns the parent of the [`CacheItem`] passed in.
+///
+/// This is `None` if the parent could not be retrieved; many objects (like the application root)
+/// have no useful parent, and that should not stop a handler from running.
+pub struct ParentItem;
+
+impl PropertyType for ParentItem {
+	type Type = Option<CacheItem>;
+}
+
+impl GetProperty<ParentItem> for CacheItem {
+	async fn get_property(
+		&self,
+		cache: &CacheActor,
+	) -> Result<EventProp<ParentItem>, OdiliaError> {
+		let parent = match cache.request(CacheRequest::Parent(self.parent.clone())).await {
+			Ok(CacheResponse::Parent(Parent(parent))) => Some(parent),
+			Ok(e) => {
+				tracing::error!("Inappropriate response from cache for `Parent` request: {e:?}");
+				None
+			}
+			Err(e) => {
+				tracing::debug!(error = %e, "Unable to get parent from cache");
+				None
+			}
+		};
+		Ok(EventProp(parent))
+	}
+}
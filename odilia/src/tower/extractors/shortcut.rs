@@ -0,0 +1,35 @@
+use core::{future::Future, pin::Pin};
+use std::sync::Arc;
+
+use atspi::EventProperties;
+use odilia_cache::Convertable;
+use odilia_common::cache::AccessiblePrimitive;
+
+use crate::{tower::from_state::TryFromState, OdiliaError, ScreenReaderState};
+
+/// The key binding of the first action of the object an event came from, in the
+/// "mnemonic;sequence;shortcut" format used by AT-SPI.
+///
+/// This is `None` if the object has no actions, or no key binding for its first action.
+#[derive(Debug, Clone)]
+pub struct Shortcut(pub Option<String>);
+
+impl<E> TryFromState<Arc<ScreenReaderState>, E> for Shortcut
+where
+	E: EventProperties + Send + Sync + 'static,
+{
+	type Error = OdiliaError;
+	type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>> + Send + 'static>>;
+	fn try_from_state(state: Arc<ScreenReaderState>, event: E) -> Self::Future {
+		let prim = AccessiblePrimitive::from_event(&event);
+		Box::pin(async move {
+			let binding: zbus::Result<String> = async {
+				let accessible = prim.into_accessible(state.connection()).await?;
+				let action = accessible.to_action().await?;
+				action.get_key_binding(0).await
+			}
+			.await;
+			Ok(Shortcut(binding.ok().filter(|binding| !binding.is_empty())))
+		})
+	}
+}
@@ -46,6 +46,9 @@ impl_handler!(T1, T2, T3, T4,);
 impl_handler!(T1, T2, T3, T4, T5,);
 impl_handler!(T1, T2, T3, T4, T5, T6,);
 impl_handler!(T1, T2, T3, T4, T5, T6, T7,);
+impl_handler!(T1, T2, T3, T4, T5, T6, T7, T8,);
+impl_handler!(T1, T2, T3, T4, T5, T6, T7, T8, T9,);
+impl_handler!(T1, T2, T3, T4, T5, T6, T7, T8, T9, T10,);
 
 #[allow(clippy::type_complexity)]
 pub struct HandlerService<H, T> {
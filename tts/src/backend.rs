@@ -0,0 +1,341 @@
+use std::fmt::Debug;
+
+use async_channel::{bounded, Sender};
+use futures_util::future::BoxFuture;
+use odilia_common::{
+	command::SpeechControl,
+	errors::{OdiliaError, SendError},
+	settings::{speech::PunctuationSpellingMode, SpeechSettings},
+};
+use ssip_client_async::{ClientScope, MessageScope, Priority, PunctuationMode, Request, Response};
+
+use crate::SsipMessage;
+
+/// Something which can turn text into speech.
+///
+/// All of Odilia's speech goes through an implementation of this trait, which means that handlers
+/// do not need to know (or care) whether they are talking to speech-dispatcher or to a buffer in
+/// a test.
+///
+/// Methods return boxed futures so that the trait can be used as `Arc<dyn SpeechBackend>`.
+/// `Debug` is required so that handlers taking a backend can still be instrumented.
+pub trait SpeechBackend: Debug + Send + Sync {
+	/// Queue `text` to be spoken at the given `priority`.
+	fn speak(&self, priority: Priority, text: String)
+		-> BoxFuture<'_, Result<(), OdiliaError>>;
+	/// Queue `text` to be spelled out, one character at a time, at the given `priority`.
+	fn spell(&self, priority: Priority, text: String)
+		-> BoxFuture<'_, Result<(), OdiliaError>>;
+	/// Queue an SSML document to be spoken at the given `priority`.
+	/// Any `<mark name="..."/>` in it is reported back as a
+	/// [`SpeechEvent::IndexMark`](crate::SpeechEvent::IndexMark) once speech reaches it.
+	fn speak_ssml(
+		&self,
+		priority: Priority,
+		ssml: String,
+	) -> BoxFuture<'_, Result<(), OdiliaError>>;
+	/// Play one of the synthesizer's sound icons, by name (e.g. `capital`).
+	fn sound_icon(&self, name: String) -> BoxFuture<'_, Result<(), OdiliaError>>;
+	/// Stop, pause, resume or cancel speech; see [`SpeechControl`].
+	fn control(&self, control: SpeechControl) -> BoxFuture<'_, Result<(), OdiliaError>>;
+	/// Set the speech rate, from -100 to 100.
+	fn set_rate(&self, rate: i8) -> BoxFuture<'_, Result<(), OdiliaError>>;
+	/// Set the speech pitch, from -100 to 100.
+	fn set_pitch(&self, pitch: i8) -> BoxFuture<'_, Result<(), OdiliaError>>;
+	/// Set the speech volume, from -100 to 100.
+	fn set_volume(&self, volume: i8) -> BoxFuture<'_, Result<(), OdiliaError>>;
+	/// Set the voice by name, as listed by the synthesizer.
+	fn set_voice(&self, voice: String) -> BoxFuture<'_, Result<(), OdiliaError>>;
+	/// Set the synthesizer (output module) by name.
+	fn set_output_module(&self, module: String) -> BoxFuture<'_, Result<(), OdiliaError>>;
+	/// Set the language, as an RFC 1766 language code (e.g. `en-US`).
+	fn set_language(&self, language: String) -> BoxFuture<'_, Result<(), OdiliaError>>;
+	/// Set how much punctuation is spoken.
+	fn set_punctuation(
+		&self,
+		mode: PunctuationSpellingMode,
+	) -> BoxFuture<'_, Result<(), OdiliaError>>;
+	/// The names of the voices available for the current output module.
+	fn list_voices(&self) -> BoxFuture<'_, Result<Vec<String>, OdiliaError>>;
+	/// The names of the output modules (synthesizers) which are available.
+	fn list_output_modules(&self) -> BoxFuture<'_, Result<Vec<String>, OdiliaError>>;
+	/// Close the connection to the synthesizer; nothing can be spoken after this.
+	fn close(&self) -> BoxFuture<'_, Result<(), OdiliaError>>;
+
+	/// Switch to another language and voice for everything spoken after this.
+	/// The language is set first, since it changes the voice as well.
+	fn switch_voice(
+		&self,
+		language: String,
+		voice: String,
+	) -> BoxFuture<'_, Result<(), OdiliaError>> {
+		Box::pin(async move {
+			self.set_language(language).await?;
+			self.set_voice(voice).await
+		})
+	}
+	/// Stop the current message and throw away everything queued after it.
+	fn cancel(&self) -> BoxFuture<'_, Result<(), OdiliaError>> {
+		self.control(SpeechControl::CancelAll)
+	}
+	/// Apply every value in `settings`.
+	/// The output module is set before the language and voice, since those depend on it.
+	fn apply_settings<'a>(
+		&'a self,
+		settings: &'a SpeechSettings,
+	) -> BoxFuture<'a, Result<(), OdiliaError>> {
+		Box::pin(async move {
+			self.set_pitch(settings.pitch).await?;
+			self.set_volume(settings.volume).await?;
+			self.set_output_module(settings.module.clone()).await?;
+			self.set_language(settings.language.clone()).await?;
+			self.set_voice(settings.person.clone()).await?;
+			self.set_punctuation(settings.punctuation).await?;
+			self.set_rate(settings.rate).await?;
+			Ok(())
+		})
+	}
+}
+
+fn punctuation_mode(mode: PunctuationSpellingMode) -> PunctuationMode {
+	//doing it this way for now. It could have been done with a From impl, but I don't want to make ssip_client_async a dependency of odilia_common, so this conversion is done directly inside the backend, especially since this enum isn't supposed to grow any further, in complexity or variants
+	match mode {
+		PunctuationSpellingMode::Some => PunctuationMode::Some,
+		PunctuationSpellingMode::Most => PunctuationMode::Most,
+		PunctuationSpellingMode::None => PunctuationMode::None,
+		PunctuationSpellingMode::All => PunctuationMode::All,
+	}
+}
+
+/// The requests which apply every value in `settings`, in the same order as
+/// [`SpeechBackend::apply_settings`].
+pub(crate) fn settings_requests(settings: &SpeechSettings) -> Vec<Request> {
+	vec![
+		Request::SetPitch(ClientScope::Current, settings.pitch),
+		Request::SetVolume(ClientScope::Current, settings.volume),
+		Request::SetOutputModule(ClientScope::Current, settings.module.clone()),
+		Request::SetLanguage(ClientScope::Current, settings.language.clone()),
+		Request::SetSynthesisVoice(ClientScope::Current, settings.person.clone()),
+		Request::SetPunctuationMode(
+			ClientScope::Current,
+			punctuation_mode(settings.punctuation),
+		),
+		Request::SetRate(ClientScope::Current, settings.rate),
+	]
+}
+
+/// A [`SpeechBackend`] which sends requests to speech-dispatcher.
+///
+/// The requests are sent over a channel to the task running [`crate::handle_ssip_commands`],
+/// which owns the actual connection.
+#[derive(Debug, Clone)]
+pub struct SsipBackend {
+	requests: Sender<SsipMessage>,
+}
+
+impl SsipBackend {
+	#[must_use]
+	pub fn new(requests: Sender<SsipMessage>) -> Self {
+		SsipBackend { requests }
+	}
+	async fn send(&self, request: Request) -> Result<(), OdiliaError> {
+		self.requests
+			.send((request, None))
+			.await
+			.map_err(|e| OdiliaError::SendError(SendError::Ssip(e.0 .0)))
+	}
+	/// Send a request, and wait for speech-dispatcher's response to it.
+	async fn query(&self, request: Request) -> Result<Response, OdiliaError> {
+		let (reply, response) = bounded(1);
+		self.requests
+			.send((request, Some(reply)))
+			.await
+			.map_err(|e| OdiliaError::SendError(SendError::Ssip(e.0 .0)))?;
+		response.recv().await.map_err(|_| {
+			OdiliaError::Generic("The SSIP task stopped before responding".to_string())
+		})
+	}
+}
+
+impl SpeechBackend for SsipBackend {
+	fn speak(
+		&self,
+		priority: Priority,
+		text: String,
+	) -> BoxFuture<'_, Result<(), OdiliaError>> {
+		Box::pin(async move {
+			// a period on a line by itself ends the message in SSIP, and the connection is closed by
+			// speech-dispatcher if this is sent as text; there's not much we can do except filter it out explicitly.
+			if text == "." {
+				return Ok(());
+			}
+			self.send(Request::SetPriority(priority)).await?;
+			self.send(Request::Speak).await?;
+			self.send(Request::SendLines(Vec::from([text]))).await
+		})
+	}
+	fn spell(
+		&self,
+		priority: Priority,
+		text: String,
+	) -> BoxFuture<'_, Result<(), OdiliaError>> {
+		Box::pin(async move {
+			self.send(Request::SetSpelling(ClientScope::Current, true)).await?;
+			let spoken = self.speak(priority, text).await;
+			// turn spelling off again even if speaking failed, or everything after this is spelled too
+			self.send(Request::SetSpelling(ClientScope::Current, false)).await?;
+			spoken
+		})
+	}
+	fn speak_ssml(
+		&self,
+		priority: Priority,
+		ssml: String,
+	) -> BoxFuture<'_, Result<(), OdiliaError>> {
+		Box::pin(async move {
+			self.send(Request::SetSsmlMode(true)).await?;
+			let spoken = self.speak(priority, ssml).await;
+			// back to plain text, or the next message is parsed as (probably invalid) markup
+			self.send(Request::SetSsmlMode(false)).await?;
+			spoken
+		})
+	}
+	fn sound_icon(&self, name: String) -> BoxFuture<'_, Result<(), OdiliaError>> {
+		// the client library has no request for this, but takes any command as a single line
+		Box::pin(self.send(Request::SendLine(format!("SOUND_ICON {name}"))))
+	}
+	fn control(&self, control: SpeechControl) -> BoxFuture<'_, Result<(), OdiliaError>> {
+		// `MessageScope::Last` refers to this client's messages only; `All` would also affect
+		// every other program connected to speech-dispatcher.
+		let request = match control {
+			SpeechControl::Stop => Request::Stop(MessageScope::Last),
+			SpeechControl::Pause => Request::Pause(MessageScope::Last),
+			SpeechControl::Resume => Request::Resume(MessageScope::Last),
+			SpeechControl::CancelAll => Request::Cancel(MessageScope::Last),
+		};
+		Box::pin(self.send(request))
+	}
+	fn set_rate(&self, rate: i8) -> BoxFuture<'_, Result<(), OdiliaError>> {
+		Box::pin(self.send(Request::SetRate(ClientScope::Current, rate)))
+	}
+	fn set_pitch(&self, pitch: i8) -> BoxFuture<'_, Result<(), OdiliaError>> {
+		Box::pin(self.send(Request::SetPitch(ClientScope::Current, pitch)))
+	}
+	fn set_volume(&self, volume: i8) -> BoxFuture<'_, Result<(), OdiliaError>> {
+		Box::pin(self.send(Request::SetVolume(ClientScope::Current, volume)))
+	}
+	fn set_voice(&self, voice: String) -> BoxFuture<'_, Result<(), OdiliaError>> {
+		Box::pin(self.send(Request::SetSynthesisVoice(ClientScope::Current, voice)))
+	}
+	fn set_output_module(&self, module: String) -> BoxFuture<'_, Result<(), OdiliaError>> {
+		Box::pin(self.send(Request::SetOutputModule(ClientScope::Current, module)))
+	}
+	fn set_language(&self, language: String) -> BoxFuture<'_, Result<(), OdiliaError>> {
+		Box::pin(self.send(Request::SetLanguage(ClientScope::Current, language)))
+	}
+	fn set_punctuation(
+		&self,
+		mode: PunctuationSpellingMode,
+	) -> BoxFuture<'_, Result<(), OdiliaError>> {
+		Box::pin(self.send(Request::SetPunctuationMode(
+			ClientScope::Current,
+			punctuation_mode(mode),
+		)))
+	}
+	fn list_voices(&self) -> BoxFuture<'_, Result<Vec<String>, OdiliaError>> {
+		Box::pin(async move {
+			match self.query(Request::ListSynthesisVoices).await? {
+				Response::VoicesListSent(voices) => {
+					Ok(voices.into_iter().map(|voice| voice.name).collect())
+				}
+				other => Err(OdiliaError::Generic(format!(
+					"Unexpected response when listing voices: {other:?}"
+				))),
+			}
+		})
+	}
+	fn list_output_modules(&self) -> BoxFuture<'_, Result<Vec<String>, OdiliaError>> {
+		Box::pin(async move {
+			match self.query(Request::ListOutputModules).await? {
+				Response::OutputModulesListSent(modules) => Ok(modules),
+				other => Err(OdiliaError::Generic(format!(
+					"Unexpected response when listing output modules: {other:?}"
+				))),
+			}
+		})
+	}
+	fn close(&self) -> BoxFuture<'_, Result<(), OdiliaError>> {
+		Box::pin(self.send(Request::Quit))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use async_channel::unbounded;
+	use futures_lite::future::block_on;
+	use odilia_common::command::SpeechControl;
+	use ssip_client_async::{ClientScope, MessageScope, Priority, Request};
+
+	use super::{SpeechBackend, SsipBackend};
+
+	#[test]
+	fn speak_sends_priority_then_text() {
+		let (tx, rx) = unbounded();
+		let backend = SsipBackend::new(tx);
+		block_on(backend.speak(Priority::Text, "Hello".to_string()))
+			.expect("Able to send to channel");
+		assert!(matches!(rx.try_recv(), Ok((Request::SetPriority(Priority::Text), None))));
+		assert!(matches!(rx.try_recv(), Ok((Request::Speak, None))));
+		assert!(
+			matches!(rx.try_recv(), Ok((Request::SendLines(lines), None)) if lines == ["Hello"])
+		);
+		assert!(rx.is_empty());
+	}
+
+	#[test]
+	fn lone_period_is_not_sent() {
+		let (tx, rx) = unbounded();
+		let backend = SsipBackend::new(tx);
+		block_on(backend.speak(Priority::Text, ".".to_string()))
+			.expect("Able to send to channel");
+		assert!(rx.is_empty());
+	}
+
+	#[test]
+	fn spelling_is_turned_off_afterwards() {
+		let (tx, rx) = unbounded();
+		let backend = SsipBackend::new(tx);
+		block_on(backend.spell(Priority::Text, "abc".to_string()))
+			.expect("Able to send to channel");
+		assert!(matches!(
+			rx.try_recv(),
+			Ok((Request::SetSpelling(ClientScope::Current, true), None))
+		));
+		let last = std::iter::from_fn(|| rx.try_recv().ok()).last();
+		assert!(matches!(
+			last,
+			Some((Request::SetSpelling(ClientScope::Current, false), None))
+		));
+	}
+
+	#[test]
+	fn ssml_mode_is_turned_off_afterwards() {
+		let (tx, rx) = unbounded();
+		let backend = SsipBackend::new(tx);
+		block_on(backend.speak_ssml(Priority::Text, "<speak>Hi</speak>".to_string()))
+			.expect("Able to send to channel");
+		assert!(matches!(rx.try_recv(), Ok((Request::SetSsmlMode(true), None))));
+		let last = std::iter::from_fn(|| rx.try_recv().ok()).last();
+		assert!(matches!(last, Some((Request::SetSsmlMode(false), None))));
+	}
+
+	#[test]
+	fn cancel_only_affects_this_client() {
+		let (tx, rx) = unbounded();
+		let backend = SsipBackend::new(tx);
+		block_on(backend.cancel()).expect("Able to send to channel");
+		block_on(backend.control(SpeechControl::Pause)).expect("Able to send to channel");
+		assert!(matches!(rx.try_recv(), Ok((Request::Cancel(MessageScope::Last), None))));
+		assert!(matches!(rx.try_recv(), Ok((Request::Pause(MessageScope::Last), None))));
+	}
+}
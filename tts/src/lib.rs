@@ -8,21 +8,160 @@
 )]
 #![allow(clippy::multiple_crate_versions)]
 
+mod backend;
+mod recording;
+mod supervisor;
+
 use std::{
 	io::ErrorKind,
-	pin::pin,
-	process::{exit, Command, Stdio},
+	path::PathBuf,
+	process::{Command, Stdio},
 };
 
-use async_channel::Receiver;
+use async_channel::{bounded, Receiver, Sender};
 use async_net::unix::UnixStream;
-use futures_lite::{io::BufReader, FutureExt};
+pub use backend::{SpeechBackend, SsipBackend};
+use futures_lite::{
+	future,
+	io::{self, BufReader, Empty, Sink},
+	FutureExt,
+};
 use futures_util::FutureExt as FatExt;
 use odilia_common::errors::OdiliaError;
+pub use recording::{Recorded, RecordingBackend};
 use smol_cancellation_token::CancellationToken;
 use ssip_client_async::{
-	async_io::AsyncClient, fifo::asynchronous_async_io::Builder, ClientName, Request,
+	async_io::AsyncClient, ClientError, ClientName, MessageScope, NotificationType, Request,
+	Response,
 };
+pub use supervisor::supervise_ssip_commands;
+
+/// A request for speech-dispatcher, along with where to send its response, if anywhere.
+pub type SsipMessage = (Request, Option<Sender<Response>>);
+
+/// Something speech-dispatcher reports about one of Odilia's messages as it is being spoken.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpeechEvent {
+	/// A message started being spoken.
+	Begin,
+	/// A message was spoken until the end.
+	End,
+	/// A message was thrown away before it was finished.
+	Canceled,
+	Paused,
+	Resumed,
+	/// Speech reached an SSML `<mark name="..."/>`; this contains the mark's name.
+	IndexMark(String),
+	/// The connection to speech-dispatcher was lost and made again, and speech-dispatcher was set
+	/// back to the live speech settings; anything set for one utterance only is gone.
+	Reconnected,
+}
+
+impl SpeechEvent {
+	fn from_response(response: &Response) -> Option<Self> {
+		Some(match response {
+			Response::EventBegin(_) => SpeechEvent::Begin,
+			Response::EventEnd(_) => SpeechEvent::End,
+			Response::EventCanceled(_) => SpeechEvent::Canceled,
+			Response::EventPaused(_) => SpeechEvent::Paused,
+			Response::EventResumed(_) => SpeechEvent::Resumed,
+			Response::EventIndexMark(_, mark) => SpeechEvent::IndexMark(mark.clone()),
+			_ => return None,
+		})
+	}
+}
+
+/// Pass `response` on to `events` if it is an event notification.
+/// Returns the response back if it was not, since it is then the answer to a request.
+fn forward_event(response: Response, events: &Sender<SpeechEvent>) -> Option<Response> {
+	let Some(event) = SpeechEvent::from_response(&response) else {
+		return Some(response);
+	};
+	tracing::trace!(?event, "SSIP event notification");
+	if let Err(e) = events.try_send(event) {
+		tracing::debug!(error = %e, "Dropping SSIP event notification");
+	}
+	None
+}
+
+/// What speech-dispatcher answered to a request: `None` if it refused it (for example, a voice which
+/// does not exist); only a broken connection is an error.
+type Answer = Result<Option<Response>, OdiliaError>;
+
+/// A connection to speech-dispatcher, split in two over the same socket: requests are only ever
+/// written with `writer`, and everything speech-dispatcher sends is only ever read with `reader`.
+pub struct SsipConnection {
+	writer: AsyncClient<Empty, UnixStream>,
+	reader: AsyncClient<BufReader<UnixStream>, Sink>,
+}
+
+impl SsipConnection {
+	/// Connect to speech-dispatcher's socket, where the freedesktop.org specification puts it.
+	async fn connect() -> std::io::Result<Self> {
+		let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR").ok_or_else(|| {
+			std::io::Error::new(ErrorKind::NotFound, "XDG_RUNTIME_DIR is not set")
+		})?;
+		let path = PathBuf::from(runtime_dir)
+			.join("speech-dispatcher")
+			.join("speechd.sock");
+		let stream = UnixStream::connect(path).await?;
+		Ok(SsipConnection {
+			writer: AsyncClient::new(io::empty(), stream.clone()),
+			reader: AsyncClient::new(BufReader::new(stream), io::sink()),
+		})
+	}
+}
+
+/// Read everything speech-dispatcher sends for as long as the connection lasts: event
+/// notifications are forwarded to `events`, and answers to requests to `answers`.
+///
+/// This is the only place the connection is read from, and it is never interrupted; a reply can
+/// take several lines, and giving up on reading one part of the way through would lose the rest.
+/// Once the connection breaks, the error is sent to `answers` and this returns.
+async fn read_ssip(
+	mut reader: AsyncClient<BufReader<UnixStream>, Sink>,
+	answers: Sender<Answer>,
+	events: Sender<SpeechEvent>,
+) {
+	loop {
+		let answer = match reader.receive().await {
+			Ok(received) => match forward_event(received, &events) {
+				Some(response) => Ok(Some(response)),
+				None => continue,
+			},
+			Err(ClientError::Ssip(status)) => {
+				tracing::warn!(?status, "speech-dispatcher refused a request");
+				Ok(None)
+			}
+			Err(e) => Err(e.into()),
+		};
+		let broken = answer.is_err();
+		if answers.send(answer).await.is_err() || broken {
+			return;
+		}
+	}
+}
+
+/// Writes requests to speech-dispatcher, and takes their answers from [`read_ssip`].
+struct Requester {
+	writer: AsyncClient<Empty, UnixStream>,
+	answers: Receiver<Answer>,
+}
+
+impl Requester {
+	/// Send `request`, and wait for its answer.
+	/// speech-dispatcher answers requests in the order they are sent, so the next answer read is
+	/// the one for this request.
+	async fn request(&mut self, request: Request) -> Answer {
+		self.writer.send(request).await?;
+		match self.answers.recv().await {
+			Ok(answer) => answer,
+			Err(_) => Err(OdiliaError::Generic(
+				"Stopped reading from speech-dispatcher".to_string(),
+			)),
+		}
+	}
+}
 
 async fn or_cancel<F>(f: F, token: &CancellationToken) -> Result<F::Output, std::io::Error>
 where
@@ -34,47 +173,57 @@ where
 		.await
 }
 
-/// Creates a new async SSIP client which can be sent commends, and can await responses to.
+/// Creates a new connection to speech-dispatcher, registered as Odilia's speech client.
+/// speech-dispatcher is spawned if it is not running.
 /// # Errors
 /// There may be errors when trying to send the initial registration command, or when parsing the response.
 #[tracing::instrument(level = "debug", err)]
-pub async fn create_ssip_client(
-) -> Result<AsyncClient<BufReader<UnixStream>, UnixStream>, OdiliaError> {
+pub async fn create_ssip_client() -> Result<SsipConnection, OdiliaError> {
 	tracing::debug!("Attempting to register SSIP client odilia:speech");
-	let mut ssip_core =
-		match Builder::default().build().await {
-			Ok(ssip) => ssip,
-			Err(e) => {
-				if e.kind() == ErrorKind::ConnectionRefused {
-					tracing::debug!("Speech dispatcher is not active. Attempting to spawn it.");
-					Command::new("speech-dispatcher")
-						.arg("--spawn")
-						.stdin(Stdio::null())
-						.stdout(Stdio::null())
-						.stderr(Stdio::null())
-						.spawn()?;
-					tracing::debug!(
-						"Attempting to connect to speech-dispatcher again!"
-					);
-					Builder::default().build().await?
-				} else {
-					tracing::debug!("Speech dispatcher could not be started.");
-					exit(1);
-				}
+	let mut connection = match SsipConnection::connect().await {
+		Ok(connection) => connection,
+		Err(e) => {
+			// a stale socket refuses connections; a cleanly stopped server removes it
+			if matches!(e.kind(), ErrorKind::ConnectionRefused | ErrorKind::NotFound) {
+				tracing::debug!(
+					"Speech dispatcher is not active. Attempting to spawn it."
+				);
+				Command::new("speech-dispatcher")
+					.arg("--spawn")
+					.stdin(Stdio::null())
+					.stdout(Stdio::null())
+					.stderr(Stdio::null())
+					.spawn()?;
+				tracing::debug!(
+					"Attempting to connect to speech-dispatcher again!"
+				);
+				SsipConnection::connect().await?
+			} else {
+				tracing::debug!("Speech dispatcher could not be started.");
+				return Err(e.into());
 			}
-		};
+		}
+	};
 	tracing::debug!("Client created. Setting name");
-	ssip_core
-		.set_client_name(ClientName::new("odilia", "speech"))
-		.await?
-		.check_client_name_set()
+	// nothing else reads from the connection yet, so the answer can be read right here
+	connection
+		.writer
+		.send(Request::SetName(ClientName::new("odilia", "speech")))
 		.await?;
+	let response = connection.reader.receive().await?;
+	if response != Response::ClientNameSet {
+		return Err(OdiliaError::Generic(format!(
+			"Unexpected response to setting the client name: {response:?}"
+		)));
+	}
 	tracing::debug!("SSIP client registered as odilia:speech");
-	Ok(ssip_core)
+	Ok(connection)
 }
 
 /// A handler task for incoming SSIP requests
 /// This function will run untill it gets canceled via the cancellation token
+/// Event notifications (the beginning and end of messages, and index marks) are sent to `events`
+/// as they arrive.
 ///
 /// # Errors
 ///
@@ -83,31 +232,101 @@ pub async fn create_ssip_client(
 /// Any of these failures will result in this function exiting with an `Err(_)` variant.
 #[tracing::instrument(level = "debug", skip_all, err)]
 pub async fn handle_ssip_commands(
-	mut client: AsyncClient<BufReader<UnixStream>, UnixStream>,
-	mut requests: Receiver<Request>,
+	connection: SsipConnection,
+	requests: Receiver<SsipMessage>,
+	events: Sender<SpeechEvent>,
+	shutdown: CancellationToken,
+) -> Result<(), OdiliaError> {
+	serve(connection, Vec::new(), requests, events, shutdown).await
+}
+
+/// [`handle_ssip_commands`], sending the `setup` requests before any others.
+async fn serve(
+	connection: SsipConnection,
+	setup: Vec<Request>,
+	requests: Receiver<SsipMessage>,
+	events: Sender<SpeechEvent>,
 	shutdown: CancellationToken,
 ) -> Result<(), OdiliaError> {
-	pin!(&mut requests);
+	let SsipConnection { writer, reader } = connection;
+	let (answers_tx, answers) = bounded(16);
+	// the reader only stops when the connection breaks, and the error it sends is picked up below
+	let reading = async {
+		read_ssip(reader, answers_tx, events).await;
+		future::pending().await
+	};
+	let mut requester = Requester { writer, answers };
+	let commands = async {
+		requester
+			.request(Request::SetNotification(NotificationType::All, true))
+			.await?;
+		for req in setup {
+			requester.request(req).await?;
+		}
+		handle_requests(&mut requester, &requests, &shutdown).await
+	};
+	commands.or(reading).await
+}
+
+/// Pass `requests` on to speech-dispatcher until `shutdown` is cancelled, then say goodbye.
+async fn handle_requests(
+	requester: &mut Requester,
+	requests: &Receiver<SsipMessage>,
+	shutdown: &CancellationToken,
+) -> Result<(), OdiliaError> {
 	loop {
-		let maybe_request = or_cancel(requests.recv(), &shutdown).await;
-		let Ok(request_option) = maybe_request else {
+		// waiting on both channels notices a broken connection even when nothing is being said
+		let next = requests.recv().map(Ok).or(requester.answers.recv().map(Err));
+		let Ok(next) = or_cancel(next, shutdown).await else {
+			// Throw away anything still queued (or paused) so the goodbye is heard right away.
+			requester.request(Request::Cancel(MessageScope::Last)).await?;
 			tracing::debug!("Saying goodbye message.");
-			client.send(Request::Speak).await?.receive().await?;
-			client.send(Request::SendLines(Vec::from(["Quitting Odilia".to_string()])))
-				.await?
-				.receive()
+			requester.request(Request::Speak).await?;
+			requester
+				.request(Request::SendLines(Vec::from([
+					"Quitting Odilia".to_string()
+				])))
 				.await?;
 			tracing::debug!("Attempting to quit SSIP.");
-			let response = client.send(Request::Quit).await?.receive().await?;
+			let response = requester.request(Request::Quit).await?;
 			tracing::debug!(?response, "Recieved response from server");
 			tracing::debug!("SSIP command interpreter shutdown completed");
-			break;
+			return Ok(());
 		};
-		if let Ok(request) = request_option {
-			tracing::debug!(?request, "SSIP command received");
-			let response = client.send(request).await?.receive().await?;
-			tracing::debug!(?response, "Recieved response from server");
+		match next {
+			Ok(Ok((req, reply))) => {
+				tracing::debug!(request = ?req, "SSIP command received");
+				let Some(response) = requester.request(req).await? else {
+					// dropping `reply` lets the requester know there is no response coming
+					continue;
+				};
+				tracing::debug!(?response, "Recieved response from server");
+				if let Some(reply) = reply {
+					if reply.send(response).await.is_err() {
+						tracing::debug!(
+							"Requester stopped waiting for the response"
+						);
+					}
+				}
+			}
+			Ok(Err(_)) => {
+				tracing::debug!("Nothing can send SSIP requests any more");
+				return Ok(());
+			}
+			Err(answer) => {
+				let answer = answer.map_err(|_| {
+					OdiliaError::Generic(
+						"Stopped reading from speech-dispatcher"
+							.to_string(),
+					)
+				})?;
+				if let Some(response) = answer? {
+					tracing::debug!(
+						?response,
+						"Unexpected response from server"
+					);
+				}
+			}
 		}
 	}
-	Ok(())
 }
@@ -0,0 +1,208 @@
+use std::sync::{
+	atomic::{AtomicBool, Ordering},
+	Arc, Mutex, PoisonError,
+};
+
+use futures_util::future::{ready, BoxFuture};
+use odilia_common::{
+	command::SpeechControl, errors::OdiliaError, settings::speech::PunctuationSpellingMode,
+};
+use ssip_client_async::Priority;
+
+/// A single call made on a [`RecordingBackend`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Recorded {
+	Speak(Priority, String),
+	Spell(Priority, String),
+	SpeakSsml(Priority, String),
+	SoundIcon(String),
+	Control(SpeechControl),
+	Rate(i8),
+	Pitch(i8),
+	Volume(i8),
+	Voice(String),
+	OutputModule(String),
+	Language(String),
+	Punctuation(PunctuationSpellingMode),
+	Close,
+}
+
+/// A [`SpeechBackend`](crate::SpeechBackend) which does not speak, but instead keeps a log of
+/// everything it was asked to do.
+///
+/// Clones share the same log, so a clone may be handed to the screen reader while the original is
+/// kept around to make assertions about what would have been spoken.
+#[derive(Debug, Clone, Default)]
+pub struct RecordingBackend {
+	log: Arc<Mutex<Vec<Recorded>>>,
+	failing: Arc<AtomicBool>,
+	voices: Vec<String>,
+	output_modules: Vec<String>,
+}
+
+impl RecordingBackend {
+	#[must_use]
+	pub fn new() -> Self {
+		Self::default()
+	}
+	/// Set the voices reported by [`list_voices`](crate::SpeechBackend::list_voices).
+	#[must_use]
+	pub fn with_voices(mut self, voices: Vec<String>) -> Self {
+		self.voices = voices;
+		self
+	}
+	/// Set the output modules reported by [`list_output_modules`](crate::SpeechBackend::list_output_modules).
+	#[must_use]
+	pub fn with_output_modules(mut self, output_modules: Vec<String>) -> Self {
+		self.output_modules = output_modules;
+		self
+	}
+	/// Every call made on this backend so far, oldest first.
+	#[must_use]
+	pub fn recorded(&self) -> Vec<Recorded> {
+		self.log.lock().unwrap_or_else(PoisonError::into_inner).clone()
+	}
+	/// Only the text which would have been spoken, oldest first.
+	#[must_use]
+	pub fn spoken(&self) -> Vec<String> {
+		self.log.lock()
+			.unwrap_or_else(PoisonError::into_inner)
+			.iter()
+			.filter_map(|rec| match rec {
+				Recorded::Speak(_, text) => Some(text.clone()),
+				_ => None,
+			})
+			.collect()
+	}
+	/// Forget everything recorded so far.
+	pub fn clear(&self) {
+		self.log.lock().unwrap_or_else(PoisonError::into_inner).clear();
+	}
+	/// Make every call fail, as if speech-dispatcher could not be reached, until this is called
+	/// again with `false`. Failed calls are not recorded.
+	pub fn set_failing(&self, failing: bool) {
+		self.failing.store(failing, Ordering::Relaxed);
+	}
+	fn record(&self, rec: Recorded) -> BoxFuture<'_, Result<(), OdiliaError>> {
+		if self.failing.load(Ordering::Relaxed) {
+			return Box::pin(ready(Err(OdiliaError::Generic(
+				"The recording backend is set to fail".to_string(),
+			))));
+		}
+		let res =
+			self.log.lock()
+				.map(|mut log| log.push(rec))
+				.map_err(OdiliaError::from);
+		Box::pin(ready(res))
+	}
+}
+
+impl crate::SpeechBackend for RecordingBackend {
+	fn speak(
+		&self,
+		priority: Priority,
+		text: String,
+	) -> BoxFuture<'_, Result<(), OdiliaError>> {
+		self.record(Recorded::Speak(priority, text))
+	}
+	fn spell(
+		&self,
+		priority: Priority,
+		text: String,
+	) -> BoxFuture<'_, Result<(), OdiliaError>> {
+		self.record(Recorded::Spell(priority, text))
+	}
+	fn speak_ssml(
+		&self,
+		priority: Priority,
+		ssml: String,
+	) -> BoxFuture<'_, Result<(), OdiliaError>> {
+		self.record(Recorded::SpeakSsml(priority, ssml))
+	}
+	fn sound_icon(&self, name: String) -> BoxFuture<'_, Result<(), OdiliaError>> {
+		self.record(Recorded::SoundIcon(name))
+	}
+	fn control(&self, control: SpeechControl) -> BoxFuture<'_, Result<(), OdiliaError>> {
+		self.record(Recorded::Control(control))
+	}
+	fn set_rate(&self, rate: i8) -> BoxFuture<'_, Result<(), OdiliaError>> {
+		self.record(Recorded::Rate(rate))
+	}
+	fn set_pitch(&self, pitch: i8) -> BoxFuture<'_, Result<(), OdiliaError>> {
+		self.record(Recorded::Pitch(pitch))
+	}
+	fn set_volume(&self, volume: i8) -> BoxFuture<'_, Result<(), OdiliaError>> {
+		self.record(Recorded::Volume(volume))
+	}
+	fn set_voice(&self, voice: String) -> BoxFuture<'_, Result<(), OdiliaError>> {
+		self.record(Recorded::Voice(voice))
+	}
+	fn set_output_module(&self, module: String) -> BoxFuture<'_, Result<(), OdiliaError>> {
+		self.record(Recorded::OutputModule(module))
+	}
+	fn set_language(&self, language: String) -> BoxFuture<'_, Result<(), OdiliaError>> {
+		self.record(Recorded::Language(language))
+	}
+	fn set_punctuation(
+		&self,
+		mode: PunctuationSpellingMode,
+	) -> BoxFuture<'_, Result<(), OdiliaError>> {
+		self.record(Recorded::Punctuation(mode))
+	}
+	fn list_voices(&self) -> BoxFuture<'_, Result<Vec<String>, OdiliaError>> {
+		Box::pin(ready(Ok(self.voices.clone())))
+	}
+	fn list_output_modules(&self) -> BoxFuture<'_, Result<Vec<String>, OdiliaError>> {
+		Box::pin(ready(Ok(self.output_modules.clone())))
+	}
+	fn close(&self) -> BoxFuture<'_, Result<(), OdiliaError>> {
+		self.record(Recorded::Close)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+
+	use futures_lite::future::block_on;
+	use odilia_common::{command::SpeechControl, settings::SpeechSettings};
+	use ssip_client_async::Priority;
+
+	use super::{Recorded, RecordingBackend};
+	use crate::SpeechBackend;
+
+	#[test]
+	fn records_utterances_in_order() {
+		let recorder = RecordingBackend::new();
+		let backend: Arc<dyn SpeechBackend> = Arc::new(recorder.clone());
+		block_on(async {
+			backend.speak(Priority::Text, "OK push button".to_string()).await?;
+			backend.cancel().await?;
+			backend.speak(Priority::Important, "Cancel push button".to_string())
+				.await
+		})
+		.expect("Recording never fails");
+		assert_eq!(recorder.spoken(), ["OK push button", "Cancel push button"]);
+		assert_eq!(recorder.recorded()[1], Recorded::Control(SpeechControl::CancelAll));
+		recorder.clear();
+		assert!(recorder.recorded().is_empty());
+	}
+
+	#[test]
+	fn apply_settings_sets_module_before_voice() {
+		let recorder = RecordingBackend::new();
+		let settings = SpeechSettings::default();
+		block_on(recorder.apply_settings(&settings)).expect("Recording never fails");
+		let requests = recorder.recorded();
+		let module = requests
+			.iter()
+			.position(|rec| matches!(rec, Recorded::OutputModule(_)))
+			.expect("Output module is set");
+		let voice = requests
+			.iter()
+			.position(|rec| matches!(rec, Recorded::Voice(_)))
+			.expect("Voice is set");
+		assert!(module < voice);
+		assert!(requests.contains(&Recorded::Rate(settings.rate)));
+	}
+}
@@ -0,0 +1,224 @@
+//! Keeping speech alive when the connection to speech-dispatcher breaks.
+//!
+//! When [`crate::handle_ssip_commands`] fails, the supervisor reconnects (spawning speech-dispatcher again
+//! if needed), waiting longer between each failed attempt. Once connected, the live speech
+//! settings are re-applied, the user is told that speech is back, and
+//! [`SpeechEvent::Reconnected`] is sent so Odilia knows speech-dispatcher has forgotten anything
+//! set for one utterance only.
+//!
+//! Messages requested in the meantime at `Important` or `Message` priority are kept and spoken
+//! after reconnecting; anything less urgent would be out of date by then, and is dropped, as are
+//! setting changes (the live settings are re-applied anyway).
+
+use std::{
+	collections::VecDeque,
+	sync::{Arc, Mutex, PoisonError},
+	time::Duration,
+};
+
+use async_channel::{Receiver, Sender};
+use async_io::Timer;
+use futures_lite::FutureExt;
+use futures_util::FutureExt as FatExt;
+use odilia_common::{errors::OdiliaError, settings::SpeechSettings};
+use smol_cancellation_token::CancellationToken;
+use ssip_client_async::{Priority, Request};
+
+use crate::{
+	backend::settings_requests, create_ssip_client, or_cancel, serve, SpeechEvent,
+	SsipConnection, SsipMessage,
+};
+
+/// How long to wait before the first attempt to reconnect.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(250);
+/// The longest wait between attempts to reconnect.
+const MAX_BACKOFF: Duration = Duration::from_secs(10);
+/// The most messages kept while disconnected; the oldest are dropped first.
+const BACKLOG_SIZE: usize = 16;
+
+/// Speech requested while speech-dispatcher could not be reached.
+#[derive(Debug, Default)]
+struct Backlog {
+	/// The priority set by the most recent `SetPriority` request.
+	priority: Option<Priority>,
+	/// Whether a `Speak` request has been seen, so the next lines sent are a message.
+	speaking: bool,
+	messages: VecDeque<(Priority, Vec<String>)>,
+}
+
+impl Backlog {
+	fn push(&mut self, request: Request) {
+		match request {
+			Request::SetPriority(priority) => self.priority = Some(priority),
+			Request::Speak => self.speaking = true,
+			Request::SendLines(lines) if self.speaking => {
+				self.speaking = false;
+				let priority = self.priority.clone().unwrap_or(Priority::Text);
+				if !matches!(priority, Priority::Important | Priority::Message) {
+					return;
+				}
+				if self.messages.len() == BACKLOG_SIZE {
+					self.messages.pop_front();
+				}
+				self.messages.push_back((priority, lines));
+			}
+			// the user asked for quiet; nothing from before this should be said later
+			Request::Cancel(_) | Request::Stop(_) => self.messages.clear(),
+			_ => {}
+		}
+	}
+}
+
+/// Wait for `delay`, keeping any speech requested in the meantime in `backlog`.
+/// Returns `false` if Odilia is shutting down.
+async fn wait(
+	delay: Duration,
+	requests: &Receiver<SsipMessage>,
+	backlog: &mut Backlog,
+	shutdown: &CancellationToken,
+) -> bool {
+	let mut timer = Timer::after(delay);
+	loop {
+		let next = requests.recv().map(Some).or((&mut timer).map(|_| None));
+		match or_cancel(next, shutdown).await {
+			// nobody can ask for speech any more, so there is no point in reconnecting
+			Err(_) | Ok(Some(Err(_))) => return false,
+			// a response can not be given; dropping `reply` lets the requester know
+			Ok(Some(Ok((req, _reply)))) => backlog.push(req),
+			Ok(None) => return true,
+		}
+	}
+}
+
+/// The requests which bring a new connection back to where the old one was: apply the live
+/// settings, let the user know, and say everything kept in `backlog`.
+fn restore(settings: &Mutex<SpeechSettings>, backlog: &mut Backlog) -> Vec<Request> {
+	let settings = settings.lock().unwrap_or_else(PoisonError::into_inner).clone();
+	let restored = (Priority::Message, vec!["Speech restored".to_string()]);
+	let messages = [restored].into_iter().chain(backlog.messages.drain(..));
+	settings_requests(&settings)
+		.into_iter()
+		.chain(messages.flat_map(|(priority, lines)| {
+			[Request::SetPriority(priority), Request::Speak, Request::SendLines(lines)]
+		}))
+		.collect()
+}
+
+/// Run [`crate::handle_ssip_commands`], reconnecting to speech-dispatcher whenever the connection
+/// fails. `settings` are the live speech settings, re-applied after each reconnection.
+///
+/// # Errors
+///
+/// None at the moment: failures are logged, and reconnecting is retried until `shutdown` is
+/// cancelled. The `Result` is kept so this can be used in place of
+/// [`crate::handle_ssip_commands`].
+#[tracing::instrument(level = "debug", skip_all, err)]
+pub async fn supervise_ssip_commands(
+	mut connection: SsipConnection,
+	requests: Receiver<SsipMessage>,
+	events: Sender<SpeechEvent>,
+	settings: Arc<Mutex<SpeechSettings>>,
+	shutdown: CancellationToken,
+) -> Result<(), OdiliaError> {
+	let mut setup = Vec::new();
+	loop {
+		let handled = serve(
+			connection,
+			std::mem::take(&mut setup),
+			requests.clone(),
+			events.clone(),
+			shutdown.clone(),
+		)
+		.await;
+		let Err(e) = handled else {
+			return Ok(());
+		};
+		tracing::error!(error = ?e, "Lost the connection to speech-dispatcher; reconnecting");
+		let mut backlog = Backlog::default();
+		let mut delay = INITIAL_BACKOFF;
+		connection = loop {
+			if !wait(delay, &requests, &mut backlog, &shutdown).await {
+				return Ok(());
+			}
+			delay = (delay * 2).min(MAX_BACKOFF);
+			match create_ssip_client().await {
+				Ok(new_connection) => break new_connection,
+				Err(e) => {
+					tracing::debug!(error = ?e, ?delay, "Could not reconnect");
+				}
+			}
+		};
+		setup = restore(&settings, &mut backlog);
+		tracing::info!("Reconnected to speech-dispatcher");
+		if let Err(e) = events.try_send(SpeechEvent::Reconnected) {
+			tracing::debug!(error = %e, "Dropping the reconnection event");
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Mutex;
+
+	use odilia_common::settings::SpeechSettings;
+	use ssip_client_async::{ClientScope, MessageScope, Priority, Request};
+
+	use super::{restore, Backlog, BACKLOG_SIZE};
+
+	fn speak(backlog: &mut Backlog, priority: Priority, text: &str) {
+		backlog.push(Request::SetPriority(priority));
+		backlog.push(Request::Speak);
+		backlog.push(Request::SendLines(vec![text.to_string()]));
+	}
+
+	fn texts(backlog: &Backlog) -> Vec<&str> {
+		backlog.messages.iter().map(|(_, lines)| lines[0].as_str()).collect()
+	}
+
+	#[test]
+	fn only_urgent_messages_are_kept() {
+		let mut backlog = Backlog::default();
+		speak(&mut backlog, Priority::Text, "OK push button");
+		speak(&mut backlog, Priority::Important, "Battery low");
+		speak(&mut backlog, Priority::Notification, "new notification");
+		speak(&mut backlog, Priority::Message, "Saved");
+		backlog.push(Request::SetRate(ClientScope::Current, 10));
+		assert_eq!(texts(&backlog), ["Battery low", "Saved"]);
+	}
+
+	#[test]
+	fn cancelling_forgets_the_backlog() {
+		let mut backlog = Backlog::default();
+		speak(&mut backlog, Priority::Important, "Battery low");
+		backlog.push(Request::Cancel(MessageScope::Last));
+		assert!(backlog.messages.is_empty());
+	}
+
+	#[test]
+	fn oldest_messages_are_dropped() {
+		let mut backlog = Backlog::default();
+		for i in 0..=BACKLOG_SIZE {
+			speak(&mut backlog, Priority::Message, &i.to_string());
+		}
+		assert_eq!(backlog.messages.len(), BACKLOG_SIZE);
+		assert_eq!(texts(&backlog)[0], "1");
+	}
+
+	#[test]
+	fn restoring_applies_settings_then_speaks_the_backlog() {
+		let mut backlog = Backlog::default();
+		speak(&mut backlog, Priority::Important, "Battery low");
+		let settings = Mutex::new(SpeechSettings::default());
+		let requests = restore(&settings, &mut backlog);
+		let spoken = requests
+			.iter()
+			.filter_map(|req| match req {
+				Request::SendLines(lines) => Some(lines[0].as_str()),
+				_ => None,
+			})
+			.collect::<Vec<_>>();
+		assert_eq!(spoken, ["Speech restored", "Battery low"]);
+		assert!(matches!(requests[0], Request::SetPitch(..)));
+		assert!(backlog.messages.is_empty());
+	}
+}